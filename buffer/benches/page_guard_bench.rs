@@ -0,0 +1,59 @@
+//! Throughput of acquiring and releasing page guards. Run with `cargo bench -p buffer`.
+//!
+//! Every page is resident, so numbers only show the cost of pinning, latching and unpinning a frame.
+use std::{
+    thread,
+    time::{Duration, Instant},
+};
+
+use buffer::BufferPoolManager;
+use storage::MemoryManager;
+
+const FRAMES: usize = 64;
+const K_DIST: usize = 2;
+const ROUNDS: usize = 200_000;
+
+fn bench(name: &str, threads: usize, body: impl Fn(&BufferPoolManager, usize) + Sync) {
+    let bpm = BufferPoolManager::new(FRAMES, K_DIST, Box::new(MemoryManager::new(FRAMES)));
+    for _ in 0..FRAMES {
+        let page_id = bpm.new_page_id();
+        drop(bpm.write_page(page_id));
+    }
+
+    let start = Instant::now();
+    thread::scope(|s| {
+        for t in 0..threads {
+            let bpm = &bpm;
+            let body = &body;
+            s.spawn(move || {
+                for i in 0..ROUNDS {
+                    body(bpm, (t * 7 + i) % FRAMES);
+                }
+            });
+        }
+    });
+    report(name, threads, start.elapsed());
+}
+
+fn report(name: &str, threads: usize, elapsed: Duration) {
+    let ops = (threads * ROUNDS) as f64;
+    println!(
+        "{name:<24} threads={threads:<2} {:>10.0} guards/s {:>8.1} ns/guard",
+        ops / elapsed.as_secs_f64(),
+        elapsed.as_nanos() as f64 / ops,
+    );
+}
+
+fn main() {
+    for threads in [1, 4, 8] {
+        bench("read guard", threads, |bpm, page_id| {
+            drop(bpm.read_page(page_id).unwrap());
+        });
+        bench("write guard", threads, |bpm, page_id| {
+            drop(bpm.write_page(page_id).unwrap());
+        });
+        bench("same page read guard", threads, |bpm, _| {
+            drop(bpm.read_page(0).unwrap());
+        });
+    }
+}
@@ -1,83 +1,124 @@
 #![allow(dead_code, unused_variables)]
 use std::{
     collections::{HashMap, HashSet},
+    io,
     ops::DerefMut,
+    path::Path,
     sync::{
-        atomic::{AtomicU16, AtomicUsize, Ordering},
-        mpsc::{channel, Sender},
-        Arc, Mutex, RwLock,
+        atomic::{AtomicUsize, Ordering},
+        Arc, Condvar, Mutex,
     },
-    thread, usize,
+    time::{Duration, Instant},
 };
 
-use storage::{DiskScheduler, FrameHeader, PageOperator};
-use tokio::sync::oneshot;
+use parking_lot::RwLock;
+use storage::{DiskRequest, DiskScheduler, Frame, PageData, PageOperator, UnpinListener};
 
-use crate::lruk_replacer::LruKReplacer;
-type DecTxSender = Sender<(usize, oneshot::Sender<()>)>;
+use crate::{
+    lruk_replacer::{LruKReplacer, ReplacementPolicy, Residency},
+    stats::{BufferPoolStats, Counters, FrameStats},
+    warm_up::WarmUpState,
+    BufferPoolError,
+};
 
 struct Protected {
-    frames: Vec<Arc<RwLock<FrameHeader>>>,
     free_frame_ids: Vec<usize>,
+    // frames from this id on are being retired by a shrinking `resize`, they are not handed out again.
+    frame_limit: usize,
     // page_id to frame_id
     page_table: HashMap<usize, usize>,
-
-    // this is just for test purpose. As frames may be write lock, we need a proxy way to get this.
-    frame_pin_count: HashMap<usize, AtomicU16>,
-    replacer: LruKReplacer,
     written_pages: HashSet<usize>,
+    deleted_pages: HashSet<usize>,
+    // pages with other than normal residency, resident or not.
+    residency: HashMap<usize, Residency>,
+    // most pages `Residency::NeverEvict` can be given to.
+    resident_budget: usize,
+}
+
+/// Handed to page guards. Marks a frame evictable once its pin count drops to zero, and wakes up fetches
+/// waiting for an evictable frame.
+struct UnpinNotifier {
+    protected: Arc<Mutex<Protected>>,
+    replacer: Arc<LruKReplacer>,
+    frame_released: Condvar,
+    // fetches registered here before they look for a frame, see `BufferPoolManager::pin_page_until`.
+    waiters: AtomicUsize,
 }
+
+impl UnpinNotifier {
+    fn notify_waiters(&self) {
+        if self.waiters.load(Ordering::SeqCst) == 0 {
+            return;
+        }
+        // a waiter holds bpm lock from the time it registers until it sleeps on condvar. Going through the
+        // lock makes sure it is asleep, or has seen the released frame, before it is notified.
+        // Poisoned or not, lock did its job once it is acquired.
+        drop(self.protected.lock());
+        self.frame_released.notify_all();
+    }
+}
+
+impl UnpinListener for UnpinNotifier {
+    fn on_unpinned(&self, frame: &Frame) {
+        self.replacer.set_evictable_if_unpinned(frame);
+        self.notify_waiters();
+    }
+}
+
 pub struct BufferPoolManager {
-    num_frames: usize,
+    // only changed by `resize` while holding bpm lock. Kept outside bpm lock, so frames can be inspected without
+    // waiting on it. A parking_lot lock, like frame latches, so a panic while holding it does not poison it.
+    frames: RwLock<Vec<Arc<Frame>>>,
+    // one resize at a time, a shrinking one releases bpm lock while it waits for frames to unpin.
+    resize_lock: Mutex<()>,
     disk_scheduler: DiskScheduler,
     next_page_id: AtomicUsize,
     protected: Arc<Mutex<Protected>>,
-
-    dec_tx: DecTxSender,
+    replacer: Arc<LruKReplacer>,
+    unpin_notifier: Arc<UnpinNotifier>,
+    counters: Counters,
 }
 
 impl BufferPoolManager {
     pub fn new(num_frames: usize, k_dist: usize, page_operator: Box<dyn PageOperator>) -> Self {
+        Self::with_policy(num_frames, ReplacementPolicy::LruK(k_dist), page_operator)
+    }
+
+    /// Same as `new`, with replacer evicting by `policy`.
+    pub fn with_policy(
+        num_frames: usize,
+        policy: ReplacementPolicy,
+        page_operator: Box<dyn PageOperator>,
+    ) -> Self {
         let disk_scheduler = DiskScheduler::new("my_db", page_operator);
-        let frame_pin_count = (0..num_frames).map(|i| (i, AtomicU16::default())).collect();
-        let frames = (0..num_frames)
-            .into_iter()
-            .map(|i| Arc::new(RwLock::new(FrameHeader::new(i))))
-            .collect();
+        let frames = (0..num_frames).map(|i| Arc::new(Frame::new(i))).collect();
 
         let protected = Arc::new(Mutex::new(Protected {
-            frames,
             free_frame_ids: (0..num_frames).collect(),
+            frame_limit: num_frames,
             page_table: HashMap::with_capacity(num_frames),
-            frame_pin_count,
-            replacer: LruKReplacer::new(num_frames, k_dist),
             written_pages: HashSet::new(),
+            deleted_pages: HashSet::new(),
+            residency: HashMap::new(),
+            resident_budget: num_frames / 4,
         }));
-        let cloned_protected = protected.clone();
-        let (tx, rx) = channel::<(usize, oneshot::Sender<()>)>();
-
-        thread::spawn(move || loop {
-            let (dec_frame_id, dec_sender) = rx.recv().unwrap();
-            let guard = cloned_protected.lock().unwrap();
-            guard
-                .frame_pin_count
-                .get(&dec_frame_id)
-                .unwrap()
-                .fetch_sub(1, Ordering::SeqCst);
-            if let Some(count) = guard.frame_pin_count.get(&dec_frame_id) {
-                if count.load(Ordering::SeqCst) == 0 {
-                    guard.replacer.set_evictable(dec_frame_id, true);
-                }
-            }
-            dec_sender.send(()).unwrap();
+        let replacer = Arc::new(LruKReplacer::with_policy(num_frames, policy));
+        let unpin_notifier = Arc::new(UnpinNotifier {
+            protected: protected.clone(),
+            replacer: replacer.clone(),
+            frame_released: Condvar::new(),
+            waiters: AtomicUsize::default(),
         });
 
         Self {
-            num_frames,
+            frames: RwLock::new(frames),
+            resize_lock: Mutex::default(),
             disk_scheduler,
             next_page_id: AtomicUsize::default(),
             protected,
-            dec_tx: tx,
+            replacer,
+            unpin_notifier,
+            counters: Counters::default(),
         }
     }
 
@@ -85,105 +126,633 @@ impl BufferPoolManager {
         self.next_page_id.fetch_add(1, Ordering::SeqCst)
     }
 
-    // return none if there is not evitable frame.
-    pub fn read_page(&self, page_id: usize) -> Option<storage::ReadPageGuard> {
-        let mut protected = self.protected.lock().unwrap();
-        let Some(frame_id) = self.get_frame_id(protected.deref_mut(), page_id) else {
-            return None;
+    // `NoFreeFrame` if there is not evitable frame.
+    #[track_caller]
+    pub fn read_page(&self, page_id: usize) -> Result<storage::ReadPageGuard, BufferPoolError> {
+        let frame = self.pin_page(page_id)?;
+        Ok(storage::read_page_guard(frame, self.unpin_notifier.clone()))
+    }
+
+    // `NoFreeFrame` if there is not evitable frame.
+    #[track_caller]
+    pub fn write_page(&self, page_id: usize) -> Result<storage::WritePageGuard, BufferPoolError> {
+        let frame = self.pin_page(page_id)?;
+        Ok(storage::write_page_guard(
+            frame,
+            self.unpin_notifier.clone(),
+        ))
+    }
+
+    /// Same as `read_page`, but when every frame is pinned it waits for one to become evictable.
+    /// Gives up with `TimedOut` if neither a frame nor page latch is available within `timeout`.
+    #[track_caller]
+    pub fn read_page_timeout(
+        &self,
+        page_id: usize,
+        timeout: Duration,
+    ) -> Result<storage::ReadPageGuard, BufferPoolError> {
+        let deadline = Instant::now() + timeout;
+        let frame = self.pin_page_until(page_id, deadline)?;
+        let remaining = deadline.saturating_duration_since(Instant::now());
+        storage::try_read_page_guard_for(frame, self.unpin_notifier.clone(), remaining)
+            .ok_or(BufferPoolError::TimedOut)
+    }
+
+    /// Same as `write_page`, but when every frame is pinned it waits for one to become evictable.
+    /// Gives up with `TimedOut` if neither a frame nor page latch is available within `timeout`.
+    #[track_caller]
+    pub fn write_page_timeout(
+        &self,
+        page_id: usize,
+        timeout: Duration,
+    ) -> Result<storage::WritePageGuard, BufferPoolError> {
+        let deadline = Instant::now() + timeout;
+        let frame = self.pin_page_until(page_id, deadline)?;
+        let remaining = deadline.saturating_duration_since(Instant::now());
+        storage::try_write_page_guard_for(frame, self.unpin_notifier.clone(), remaining)
+            .ok_or(BufferPoolError::TimedOut)
+    }
+
+    /// Read latch which can later be upgraded to write latch without unpinning the page.
+    /// Only one upgradable reader is let in at a time, it shares the page with plain readers.
+    #[track_caller]
+    pub fn upgradable_read_page(
+        &self,
+        page_id: usize,
+    ) -> Result<storage::UpgradableReadPageGuard, BufferPoolError> {
+        let frame = self.pin_page(page_id)?;
+        Ok(storage::upgradable_read_page_guard(
+            frame,
+            self.unpin_notifier.clone(),
+        ))
+    }
+
+    // `WouldBlock` if page is write latched.
+    #[track_caller]
+    pub fn try_read_page(&self, page_id: usize) -> Result<storage::ReadPageGuard, BufferPoolError> {
+        let frame = self.pin_page(page_id)?;
+        storage::try_read_page_guard(frame, self.unpin_notifier.clone())
+            .ok_or(BufferPoolError::WouldBlock)
+    }
+
+    // `WouldBlock` if page is latched.
+    #[track_caller]
+    pub fn try_write_page(
+        &self,
+        page_id: usize,
+    ) -> Result<storage::WritePageGuard, BufferPoolError> {
+        let frame = self.pin_page(page_id)?;
+        storage::try_write_page_guard(frame, self.unpin_notifier.clone())
+            .ok_or(BufferPoolError::WouldBlock)
+    }
+
+    /// Pins page without latching it, see `OptimisticReadGuard`.
+    pub fn optimistic_read_page(
+        &self,
+        page_id: usize,
+    ) -> Result<storage::OptimisticReadGuard, BufferPoolError> {
+        let frame = self.pin_page(page_id)?;
+        Ok(storage::optimistic_read_guard(
+            frame,
+            self.unpin_notifier.clone(),
+        ))
+    }
+
+    /// Writes page back to disk if it is dirty. Page stays in buffer pool.
+    ///
+    /// It shares the page with readers, but waits for a writer holding it to finish, and keeps writers out
+    /// until the page is written.
+    pub fn flush_page(&self, page_id: usize) -> Result<(), BufferPoolError> {
+        let frame = {
+            let protected = self.protected.lock()?;
+            Self::check_page_id(&protected, page_id, &self.next_page_id)?;
+            let Some(&frame_id) = protected.page_table.get(&page_id) else {
+                return Ok(());
+            };
+            let frame = self.frame(frame_id);
+            // pinned, not accessed. Flushing should not make page look hot to replacer.
+            frame.pin();
+            if frame_id < protected.frame_limit {
+                self.replacer.set_evictable(frame_id, false);
+            }
+            frame
         };
-        let frame = protected.frames[frame_id].clone();
-        let pg_guard = storage::read_page_guard(frame, self.dec_tx.clone());
-        protected.replacer.record_access(frame_id, None);
-        protected.frame_pin_count.entry(frame_id).and_modify(|e| {
-            e.fetch_add(1, Ordering::SeqCst);
-        });
 
-        Some(pg_guard)
+        let guard = storage::read_page_guard(frame.clone(), self.unpin_notifier.clone());
+        if !frame.is_dirty() {
+            return Ok(());
+        }
+        // a writer can not come in while we hold read latch, so no write is lost by clearing dirty here.
+        let mut data = Box::<PageData>::default();
+        data.copy_from_slice(guard.get_read_guard().get_readable_data());
+        frame.set_dirty(false);
+
+        // guard is held until page is known to be on disk. Otherwise the now clean page could be evicted
+        // without a write back, and read back before this write lands.
+        if let Err(err) = self.write_to_disk(page_id, data) {
+            frame.set_dirty(true);
+            return Err(err);
+        }
+        self.protected.lock()?.written_pages.insert(page_id);
+        drop(guard);
+        Ok(())
+    }
+
+    /// Writes every dirty page in buffer pool back to disk.
+    pub fn flush_all_pages(&self) -> Result<(), BufferPoolError> {
+        let page_ids = self
+            .protected
+            .lock()?
+            .page_table
+            .keys()
+            .copied()
+            .collect::<Vec<_>>();
+        for page_id in page_ids {
+            self.flush_page(page_id)?;
+        }
+        Ok(())
     }
 
-    // return none if there is not evitable frame.
-    pub fn write_page(&self, page_id: usize) -> Option<storage::WritePageGuard> {
-        let mut protected = self.protected.lock().unwrap();
-        let Some(frame_id) = self.get_frame_id(protected.deref_mut(), page_id) else {
-            return None;
+    /// Grows or shrinks buffer pool to `num_frames` frames while it stays in use.
+    ///
+    /// Shrinking drops frames from the end. Pages held by them are written back if dirty and evicted, and
+    /// pinned ones are waited for until they are unpinned. Those pages can still be fetched meanwhile, which
+    /// pins them again. Gives up with `TimedOut` if they are not all unpinned within `timeout`, buffer pool
+    /// keeps its old size then, and with write back error if one fails.
+    ///
+    /// Resident budget is lowered to fit a smaller pool, see `set_resident_budget`. Shrinking is refused with
+    /// `ResidentBudgetExceeded` if more pages are `Residency::NeverEvict` than the lowered budget allows.
+    pub fn resize(&self, num_frames: usize, timeout: Duration) -> Result<(), BufferPoolError> {
+        let _resize_guard = self.resize_lock.lock()?;
+        let mut protected = self.protected.lock()?;
+        let old_num_frames = protected.frame_limit;
+        if num_frames >= old_num_frames {
+            let mut frames = self.frames.write();
+            frames.extend((old_num_frames..num_frames).map(|i| Arc::new(Frame::new(i))));
+            protected.free_frame_ids.extend(old_num_frames..num_frames);
+            protected.frame_limit = num_frames;
+            self.replacer.set_size(num_frames);
+            drop(frames);
+            drop(protected);
+            self.unpin_notifier.notify_waiters();
+            return Ok(());
+        }
+
+        let old_resident_budget = protected.resident_budget;
+        let resident_budget = old_resident_budget.min(num_frames.saturating_sub(1));
+        if Self::never_evict_count(&protected) > resident_budget {
+            return Err(BufferPoolError::ResidentBudgetExceeded(resident_budget));
+        }
+
+        let deadline = Instant::now() + timeout;
+        protected.frame_limit = num_frames;
+        protected.resident_budget = resident_budget;
+        protected
+            .free_frame_ids
+            .retain(|&frame_id| frame_id < num_frames);
+        self.replacer.set_size(num_frames);
+        let retiring = self.frames.read()[num_frames..].to_vec();
+
+        // registered the same way as a waiting fetch, see `pin_page_until`.
+        self.unpin_notifier.waiters.fetch_add(1, Ordering::SeqCst);
+        let res = loop {
+            let mut all_free = true;
+            let mut res = Ok(());
+            for frame in &retiring {
+                if frame.page_id().is_none() {
+                    continue;
+                }
+                if frame.pin_count() > 0 {
+                    all_free = false;
+                    continue;
+                }
+                res = self.evict_frame(&mut protected, frame);
+                if res.is_err() {
+                    break;
+                }
+            }
+            if res.is_err() || all_free {
+                break res;
+            }
+
+            let now = Instant::now();
+            if now >= deadline {
+                break Err(BufferPoolError::TimedOut);
+            }
+            protected = match self
+                .unpin_notifier
+                .frame_released
+                .wait_timeout(protected, deadline - now)
+            {
+                Ok((protected, _)) => protected,
+                Err(_) => {
+                    self.unpin_notifier.waiters.fetch_sub(1, Ordering::SeqCst);
+                    return Err(BufferPoolError::PoisonedLatch);
+                }
+            };
         };
-        let frame = protected.frames[frame_id].clone();
-        let pg_guard = storage::write_page_guard(frame, self.dec_tx.clone());
-        protected.replacer.record_access(frame_id, None);
-        protected.frame_pin_count.entry(frame_id).and_modify(|e| {
-            e.fetch_add(1, Ordering::SeqCst);
-        });
+        self.unpin_notifier.waiters.fetch_sub(1, Ordering::SeqCst);
+
+        if let Err(err) = res {
+            // hand retiring frames back, the ones still holding a page go back to replacer.
+            protected.frame_limit = old_num_frames;
+            protected.resident_budget = old_resident_budget;
+            self.replacer.set_size(old_num_frames);
+            for frame in &retiring {
+                let Some(page_id) = frame.page_id() else {
+                    protected.free_frame_ids.push(frame.frame_id());
+                    continue;
+                };
+                self.replacer.record_access(frame.frame_id(), None);
+                if let Some(&residency) = protected.residency.get(&page_id) {
+                    self.replacer.set_residency(frame.frame_id(), residency);
+                }
+                self.replacer.set_evictable_if_unpinned(frame);
+            }
+            return Err(err);
+        }
+        self.frames.write().truncate(num_frames);
+        Ok(())
+    }
+
+    /// Meant for a clean shutdown. Flushes every dirty page, then saves ids of resident pages along with their
+    /// access history to `path`, so that a later `warm_up` can bring them back.
+    pub fn save_warm_up(&self, path: impl AsRef<Path>) -> Result<(), BufferPoolError> {
+        self.flush_all_pages()?;
+        let protected = self.protected.lock()?;
+        let mut history = self.replacer.snapshot();
+        let mut state = WarmUpState {
+            next_page_id: self.next_page_id.load(Ordering::SeqCst),
+            written_pages: protected.written_pages.iter().copied().collect(),
+            deleted_pages: protected.deleted_pages.iter().copied().collect(),
+            pages: Vec::with_capacity(protected.page_table.len()),
+        };
+        drop(protected);
+        for frame in self.frames.read().iter() {
+            // a page never written back has nothing on disk to be loaded from.
+            let Some(page_id) = frame.page_id() else {
+                continue;
+            };
+            if !state.written_pages.contains(&page_id) {
+                continue;
+            }
+            let (_, _, history) = history.remove(&frame.frame_id()).unwrap_or_default();
+            state.pages.push((page_id, history));
+        }
+        state.written_pages.sort_unstable();
+        state.deleted_pages.sort_unstable();
+        state.save(path.as_ref())?;
+        Ok(())
+    }
+
+    /// Picks up page ids handed out, and pages written, before a restart from a file left by `save_warm_up`.
+    /// Then preloads pages resident at that time into free frames, most recently used first if they do not
+    /// all fit. Reads are all handed to disk scheduler at once, in ascending page order.
+    ///
+    /// Meant to be called on a fresh buffer pool, before it is used. Returns number of pages loaded, a missing
+    /// file is treated as nothing to warm up.
+    pub fn warm_up(&self, path: impl AsRef<Path>) -> Result<usize, BufferPoolError> {
+        let state = match WarmUpState::load(path.as_ref()) {
+            Ok(state) => state,
+            Err(err) if err.kind() == io::ErrorKind::NotFound => return Ok(0),
+            Err(err) => return Err(err.into()),
+        };
+        let mut protected = self.protected.lock()?;
+        self.next_page_id
+            .fetch_max(state.next_page_id, Ordering::SeqCst);
+        protected.written_pages.extend(state.written_pages);
+        protected.deleted_pages.extend(state.deleted_pages);
+
+        let mut pages = state
+            .pages
+            .into_iter()
+            .filter(|(page_id, _)| {
+                !protected.page_table.contains_key(page_id)
+                    && !protected.deleted_pages.contains(page_id)
+                    && protected.written_pages.contains(page_id)
+            })
+            .collect::<Vec<_>>();
+        pages.sort_unstable_by_key(|(_, history)| std::cmp::Reverse(history.last().copied()));
+        pages.truncate(protected.free_frame_ids.len());
+        pages.sort_unstable_by_key(|(page_id, _)| *page_id);
+
+        let mut reads = Vec::with_capacity(pages.len());
+        for (page_id, history) in pages {
+            let (request, rx) = DiskRequest::new_read(page_id, Box::default());
+            self.disk_scheduler
+                .schedule(request)
+                .map_err(|_| BufferPoolError::SchedulerShutdown)?;
+            reads.push((page_id, history, rx));
+        }
+
+        // pages read fine are kept even if another read failed.
+        let mut loaded = 0;
+        let mut res = Ok(());
+        for (page_id, history, rx) in reads {
+            let data = match rx.blocking_recv() {
+                Ok(Ok(data)) => data,
+                Ok(Err(err)) => {
+                    res = res.and(Err(err.into()));
+                    continue;
+                }
+                Err(_) => {
+                    res = res.and(Err(BufferPoolError::SchedulerShutdown));
+                    continue;
+                }
+            };
+            self.counters.disk_read();
+            let frame_id = protected.free_frame_ids.pop().unwrap();
+            let frame = self.frame(frame_id);
+            frame.latch().write().set_data(data);
+            frame.set_page_id(Some(page_id));
+            protected.page_table.insert(page_id, frame_id);
+            self.replacer.restore_history(frame_id, history);
+            if let Some(&residency) = protected.residency.get(&page_id) {
+                self.replacer.set_residency(frame_id, residency);
+            }
+            loaded += 1;
+        }
+        res.map(|_| loaded)
+    }
 
-        Some(pg_guard)
+    /// Tells replacer how hard to try keeping a page in buffer pool, meant for pages like upper levels of an
+    /// index which should outlive a large scan. Residency sticks to the page, whether it is resident or not,
+    /// until it is changed or page is deleted.
+    ///
+    /// `Residency::NeverEvict` is limited to resident budget, see `set_resident_budget`. Such a page still has
+    /// to be fetched once to be brought in.
+    pub fn set_residency(
+        &self,
+        page_id: usize,
+        residency: Residency,
+    ) -> Result<(), BufferPoolError> {
+        let mut protected = self.protected.lock()?;
+        Self::check_page_id(&protected, page_id, &self.next_page_id)?;
+        if residency == Residency::NeverEvict
+            && protected.residency.get(&page_id) != Some(&Residency::NeverEvict)
+            && Self::never_evict_count(&protected) >= protected.resident_budget
+        {
+            return Err(BufferPoolError::ResidentBudgetExceeded(
+                protected.resident_budget,
+            ));
+        }
+
+        if residency == Residency::Normal {
+            protected.residency.remove(&page_id);
+        } else {
+            protected.residency.insert(page_id, residency);
+        }
+        if let Some(&frame_id) = protected.page_table.get(&page_id) {
+            if frame_id < protected.frame_limit {
+                self.replacer.set_residency(frame_id, residency);
+            }
+        }
+        Ok(())
+    }
+
+    /// Most pages which can be `Residency::NeverEvict` at once, a quarter of frames unless set here. It is
+    /// capped so that at least one frame is left for other pages. Pages already over a lowered budget keep
+    /// their residency.
+    pub fn set_resident_budget(&self, pages: usize) -> Result<(), BufferPoolError> {
+        let mut protected = self.protected.lock()?;
+        protected.resident_budget = pages.min(protected.frame_limit.saturating_sub(1));
+        Ok(())
     }
 
-    fn flush_page(&self, page_id: usize) {}
-    fn flush_all_pages(&self) {}
+    /// Counters and frame counts of buffer pool. Neither bpm lock nor page latches are taken, so it can be
+    /// called while pool is under load.
+    pub fn stats(&self) -> BufferPoolStats {
+        let mut stats = self.counters.snapshot();
+        let frames = self.frames.read();
+        stats.num_frames = frames.len();
+        for frame in frames.iter() {
+            if frame.page_id().is_none() {
+                stats.free_frames += 1;
+            }
+            if frame.pin_count() > 0 {
+                stats.pinned_frames += 1;
+            }
+            if frame.is_dirty() {
+                stats.dirty_frames += 1;
+            }
+        }
+        stats
+    }
+
+    /// State of every frame, ordered by frame id. Only replacer lock is taken, for a copy of access histories.
+    pub fn frame_stats(&self) -> Vec<FrameStats> {
+        let mut replacer = self.replacer.snapshot();
+        self.frames
+            .read()
+            .iter()
+            .map(|frame| {
+                let (evictable, residency, history) =
+                    replacer.remove(&frame.frame_id()).unwrap_or_default();
+                FrameStats {
+                    frame_id: frame.frame_id(),
+                    page_id: frame.page_id(),
+                    pin_count: frame.pin_count(),
+                    is_dirty: frame.is_dirty(),
+                    evictable,
+                    residency,
+                    history,
+                }
+            })
+            .collect()
+    }
 
     // this is internal info and only required for testing.
-    // we will use proxy for frame count and should not be used else where.
     fn get_pin_count(&self, page_id: usize) -> Option<u16> {
         let protected = self.protected.lock().unwrap();
-        let Some(frame_id) = protected.page_table.get(&page_id) else {
-            return None;
-        };
+        let frame_id = protected.page_table.get(&page_id)?;
+        Some(self.frame(*frame_id).pin_count())
+    }
+
+    fn never_evict_count(protected: &Protected) -> usize {
         protected
-            .frame_pin_count
-            .get(frame_id)
-            .map(|it| it.load(Ordering::SeqCst))
+            .residency
+            .values()
+            .filter(|&&it| it == Residency::NeverEvict)
+            .count()
+    }
+
+    fn check_page_id(
+        protected: &Protected,
+        page_id: usize,
+        next_page_id: &AtomicUsize,
+    ) -> Result<(), BufferPoolError> {
+        if page_id >= next_page_id.load(Ordering::SeqCst) {
+            return Err(BufferPoolError::InvalidPageId(page_id));
+        }
+        if protected.deleted_pages.contains(&page_id) {
+            return Err(BufferPoolError::PageDeleted(page_id));
+        }
+        Ok(())
+    }
+
+    // Brings page in a frame and pins it. Only bpm lock is held here, page latch is acquired by caller
+    // after the lock is released. A pinned frame can not be evicted, so frame stays with the page in between.
+    fn pin_page(&self, page_id: usize) -> Result<Arc<Frame>, BufferPoolError> {
+        let mut protected = self.protected.lock()?;
+        Self::check_page_id(&protected, page_id, &self.next_page_id)?;
+        let frame_id = self
+            .get_frame_id(protected.deref_mut(), page_id)?
+            .ok_or(BufferPoolError::NoFreeFrame)?;
+        Ok(self.pin_frame(&protected, frame_id))
+    }
+
+    // Same as `pin_page`, but sleeps until a frame is released if there is no evictable frame.
+    fn pin_page_until(
+        &self,
+        page_id: usize,
+        deadline: Instant,
+    ) -> Result<Arc<Frame>, BufferPoolError> {
+        let mut protected = self.protected.lock()?;
+        // register before looking for a frame. A frame released after that either shows up in the search,
+        // or its releaser sees us registered and wakes us up.
+        self.unpin_notifier.waiters.fetch_add(1, Ordering::SeqCst);
+        let res = loop {
+            if let Err(err) = Self::check_page_id(&protected, page_id, &self.next_page_id) {
+                break Err(err);
+            }
+            match self.get_frame_id(protected.deref_mut(), page_id) {
+                Ok(Some(frame_id)) => break Ok(self.pin_frame(&protected, frame_id)),
+                Ok(None) => {}
+                Err(err) => break Err(err),
+            }
+
+            let now = Instant::now();
+            if now >= deadline {
+                break Err(BufferPoolError::TimedOut);
+            }
+            protected = match self
+                .unpin_notifier
+                .frame_released
+                .wait_timeout(protected, deadline - now)
+            {
+                Ok((protected, _)) => protected,
+                Err(_) => {
+                    self.unpin_notifier.waiters.fetch_sub(1, Ordering::SeqCst);
+                    return Err(BufferPoolError::PoisonedLatch);
+                }
+            };
+        };
+        self.unpin_notifier.waiters.fetch_sub(1, Ordering::SeqCst);
+
+        res
+    }
+
+    // It assumes lock is acquired on protected data.
+    fn pin_frame(&self, protected: &Protected, frame_id: usize) -> Arc<Frame> {
+        let frame = self.frame(frame_id);
+        frame.pin();
+        // a retiring frame is already out of replacer.
+        if frame_id < protected.frame_limit {
+            self.replacer.record_access(frame_id, None);
+            self.replacer.set_evictable(frame_id, false);
+        }
+        frame
+    }
+
+    fn frame(&self, frame_id: usize) -> Arc<Frame> {
+        self.frames.read()[frame_id].clone()
     }
 
     // this is only for internal use. It assumes lock is acquired on protected data.
-    fn get_frame_id(&self, protected: &mut Protected, page_id: usize) -> Option<usize> {
+    // Returns none if page is not in buffer pool and there is no free or evictable frame to bring it in.
+    // On error buffer pool is left as it was, apart from replacer history of a frame that failed to be evicted.
+    fn get_frame_id(
+        &self,
+        protected: &mut Protected,
+        page_id: usize,
+    ) -> Result<Option<usize>, BufferPoolError> {
         if let Some(&frame_id) = protected.page_table.get(&page_id) {
-            return Some(frame_id);
+            self.counters.hit();
+            return Ok(Some(frame_id));
         }
         if protected.free_frame_ids.is_empty() {
-            let Some(evicted_frame_id) = protected.replacer.evict() else {
-                return None;
+            let Some(evicted_frame_id) = self.replacer.evict() else {
+                return Ok(None);
             };
 
-            let mut evicted_frame = protected.frames[evicted_frame_id].write().unwrap();
-            if evicted_frame.is_dirty() {
-                let (tx, rx) = oneshot::channel();
-                let page_id = evicted_frame.get_page_id().unwrap();
-                self.disk_scheduler
-                    .schedule(storage::DiskRequest::Write {
-                        page_id,
-                        data_buf: evicted_frame.get_data_mut(),
-                        ack: tx,
-                    })
-                    .unwrap();
-                let data = rx.blocking_recv().unwrap().unwrap();
-                protected.written_pages.insert(page_id);
-                evicted_frame.set_data(data);
-                evicted_frame.set_dirty(false);
-                evicted_frame.set_page_id(None);
+            if let Err(err) = self.evict_frame(protected, &self.frame(evicted_frame_id)) {
+                // page stays where it is, and can be picked for eviction again.
+                self.replacer.record_access(evicted_frame_id, None);
+                let evicted_page_id = self.frame(evicted_frame_id).page_id().unwrap();
+                if let Some(&residency) = protected.residency.get(&evicted_page_id) {
+                    self.replacer.set_residency(evicted_frame_id, residency);
+                }
+                self.replacer.set_evictable(evicted_frame_id, true);
+                return Err(err);
             }
-
             protected.free_frame_ids.push(evicted_frame_id);
         }
 
-        let mut assigned_frame = protected.frames[protected.free_frame_ids.pop().unwrap()]
-            .write()
-            .unwrap();
-        assigned_frame.set_page_id(Some(page_id));
+        let frame_id = *protected.free_frame_ids.last().unwrap();
+        let frame = self.frame(frame_id);
+        let mut assigned_frame = frame.latch().write();
         if protected.written_pages.contains(&page_id) {
-            let (tx, rx) = oneshot::channel();
-            self.disk_scheduler
-                .schedule(storage::DiskRequest::Read {
-                    page_id,
-                    data_buf: assigned_frame.get_data_mut(),
-                    ack: tx,
-                })
-                .unwrap();
-            let data = rx.blocking_recv().unwrap().unwrap();
+            // frame is still free if read fails.
+            let data = self.read_from_disk(page_id)?;
             assigned_frame.set_data(data);
+        } else {
+            // frame may still hold bytes of the page evicted from it.
+            assigned_frame.get_writeable_data().fill(0);
         }
-        protected
-            .page_table
-            .insert(page_id, assigned_frame.frame_id());
-        Some(assigned_frame.frame_id())
+        frame.set_page_id(Some(page_id));
+        drop(assigned_frame);
+        self.counters.miss();
+
+        protected.free_frame_ids.pop();
+        protected.page_table.insert(page_id, frame_id);
+        if let Some(&residency) = protected.residency.get(&page_id) {
+            self.replacer.set_residency(frame_id, residency);
+        }
+        Ok(Some(frame_id))
+    }
+
+    // Writes page held by an unpinned frame back if it is dirty, and takes it out of page table.
+    // It assumes lock is acquired on protected data. Frame is left untouched if write back fails.
+    fn evict_frame(&self, protected: &mut Protected, frame: &Frame) -> Result<(), BufferPoolError> {
+        let evicted_frame = frame.latch().write();
+        let evicted_page_id = frame.page_id().unwrap();
+        if frame.is_dirty() {
+            let mut data = Box::<PageData>::default();
+            data.copy_from_slice(evicted_frame.get_readable_data());
+            self.write_to_disk(evicted_page_id, data)?;
+            protected.written_pages.insert(evicted_page_id);
+            frame.set_dirty(false);
+            self.counters.dirty_write_back();
+        }
+        frame.set_page_id(None);
+        drop(evicted_frame);
+        self.counters.eviction();
+
+        protected.page_table.remove(&evicted_page_id);
+        Ok(())
+    }
+
+    fn write_to_disk(&self, page_id: usize, data: Box<PageData>) -> Result<(), BufferPoolError> {
+        let (request, rx) = DiskRequest::new_write(page_id, data);
+        self.disk_scheduler
+            .schedule(request)
+            .map_err(|_| BufferPoolError::SchedulerShutdown)?;
+        rx.blocking_recv()
+            .map_err(|_| BufferPoolError::SchedulerShutdown)??;
+        self.counters.disk_write();
+        Ok(())
+    }
+
+    fn read_from_disk(&self, page_id: usize) -> Result<Box<PageData>, BufferPoolError> {
+        let (request, rx) = DiskRequest::new_read(page_id, Box::default());
+        self.disk_scheduler
+            .schedule(request)
+            .map_err(|_| BufferPoolError::SchedulerShutdown)?;
+        let data = rx
+            .blocking_recv()
+            .map_err(|_| BufferPoolError::SchedulerShutdown)??;
+        self.counters.disk_read();
+        Ok(data)
     }
 
     /// Removes a page from the database, both on disk and in memory.
@@ -194,50 +763,68 @@ impl BufferPoolManager {
     /// pages on disk used to occupy should somehow be made available to new pages allocated by `NewPage`. But for later.
     ///
     /// `false` if the page exists but could not be deleted, `true` if the page didn't exist or deletion succeeded.
-    pub fn delete_page(&self, page_id: usize) -> bool {
-        let mut protected = self.protected.lock().unwrap();
+    pub fn delete_page(&self, page_id: usize) -> Result<bool, BufferPoolError> {
+        let mut protected = self.protected.lock()?;
+        if page_id >= self.next_page_id.load(Ordering::SeqCst) {
+            return Err(BufferPoolError::InvalidPageId(page_id));
+        }
 
         let Some(&frame_id) = protected.page_table.get(&page_id) else {
             protected.written_pages.remove(&page_id);
-            return true;
+            protected.deleted_pages.insert(page_id);
+            protected.residency.remove(&page_id);
+            drop(protected);
+            // waiters on this page need to learn it is gone.
+            self.unpin_notifier.notify_waiters();
+            return Ok(true);
         };
 
-        if 0 != protected
-            .frame_pin_count
-            .get(&frame_id)
-            .unwrap()
-            .load(Ordering::SeqCst)
-        {
-            return false;
+        let frame = self.frame(frame_id);
+        if 0 != frame.pin_count() {
+            return Ok(false);
         }
 
         protected.written_pages.remove(&page_id);
-        {
-            let mut associated_frame = protected.frames[frame_id].write().unwrap();
-            associated_frame.set_dirty(false);
-            associated_frame.set_page_id(None);
-        }
-
+        protected.deleted_pages.insert(page_id);
+        protected.residency.remove(&page_id);
+        frame.set_dirty(false);
+        let latch = frame.latch().write();
+        frame.set_page_id(None);
+        drop(latch);
+
+        self.replacer.remove(frame_id);
         protected.page_table.remove(&page_id);
-        protected.free_frame_ids.push(frame_id);
+        // a retiring frame is left for `resize` to drop.
+        if frame_id < protected.frame_limit {
+            protected.free_frame_ids.push(frame_id);
+        }
+        drop(protected);
+        // a frame is free now, and waiters on this page need to learn it is gone.
+        self.unpin_notifier.notify_waiters();
 
         //TODO remove data from disk or memory
 
-        true
+        Ok(true)
     }
 }
 
 #[cfg(test)]
+#[allow(clippy::bool_assert_comparison)]
 mod test {
     use std::{
-        sync::atomic::{AtomicBool, Ordering},
+        io,
+        sync::{
+            atomic::{AtomicBool, Ordering},
+            Arc,
+        },
         thread,
         time::Duration,
     };
 
-    use storage::MemoryManager;
+    use storage::{MemoryManager, PageOperator, PAGE_SIZE};
 
     use super::BufferPoolManager;
+    use crate::{BufferPoolError, Residency};
 
     const FRAMES: usize = 10;
     const K_DIST: usize = 5;
@@ -305,11 +892,17 @@ mod test {
             // as there are two frames only, any new page should not be assigned frame.
             let temp1 = bpm.new_page_id();
             let temp_1_guard = bpm.write_page(temp1);
-            assert_eq!(true, temp_1_guard.is_none());
+            assert_eq!(
+                true,
+                matches!(temp_1_guard, Err(BufferPoolError::NoFreeFrame))
+            );
 
             let temp2 = bpm.new_page_id();
             let temp_2_guard = bpm.read_page(temp2);
-            assert_eq!(true, temp_2_guard.is_none());
+            assert_eq!(
+                true,
+                matches!(temp_2_guard, Err(BufferPoolError::NoFreeFrame))
+            );
 
             drop(page_0_guard);
             drop(page_1_guard);
@@ -322,11 +915,11 @@ mod test {
             // now both should have frames as pervious frame pin count are 0 and thus evictable.
             let temp1 = bpm.new_page_id();
             let temp_1_guard = bpm.write_page(temp1);
-            assert_eq!(true, temp_1_guard.is_some());
+            assert_eq!(true, temp_1_guard.is_ok());
 
             let temp2 = bpm.new_page_id();
             let temp_2_guard = bpm.read_page(temp2);
-            assert_eq!(true, temp_2_guard.is_some());
+            assert_eq!(true, temp_2_guard.is_ok());
         }
 
         {
@@ -401,7 +994,10 @@ mod test {
         for i in 0..FRAMES {
             let page_id = bpm.new_page_id();
             let page_guard = bpm.write_page(page_id);
-            assert_eq!(true, page_guard.is_none());
+            assert_eq!(
+                true,
+                matches!(page_guard, Err(BufferPoolError::NoFreeFrame))
+            );
         }
 
         // Scenario: Drop the last 5 pages to unpin them.
@@ -421,7 +1017,7 @@ mod test {
         for i in 0..(FRAMES / 2) - 1 {
             let page_id = bpm.new_page_id();
             let page_guard = bpm.write_page(page_id);
-            assert_eq!(true, page_guard.is_some());
+            assert_eq!(true, page_guard.is_ok());
         }
 
         // Scenario: There should be one frame available, and we should be able to fetch the data we wrote a while ago.
@@ -485,17 +1081,15 @@ mod test {
                     // While we are reading, nobody should be able to modify the data.
                     let guard = bpm.read_page(pid).unwrap();
                     // Save the data we observe.
-                    let cloned_data = String::from_utf8(Vec::from(
-                        guard.get_read_guard().get_readable_data().clone(),
-                    ))
-                    .unwrap();
+                    let cloned_data =
+                        String::from_utf8(Vec::from(*guard.get_read_guard().get_readable_data()))
+                            .unwrap();
 
                     // Sleep for a bit. If latching is working properly, nothing should be writing to the page.
                     thread::sleep(Duration::from_millis(10));
-                    let cloned_data_again = String::from_utf8(Vec::from(
-                        guard.get_read_guard().get_readable_data().clone(),
-                    ))
-                    .unwrap();
+                    let cloned_data_again =
+                        String::from_utf8(Vec::from(*guard.get_read_guard().get_readable_data()))
+                            .unwrap();
                     // Check that the data is unmodified.
                     assert_eq!(true, cloned_data.eq(&cloned_data_again));
                 }
@@ -536,8 +1130,624 @@ mod test {
             // Think about what might happen if you hold a certain "all-encompassing" latch for too long...
 
             // While holding page 0, take the latch on page 1.
-            bpm.write_page(page_id_1);
+            let guard = bpm.write_page(page_id_1);
+        });
+    }
+
+    #[test]
+    fn concurrent_unpin_test() {
+        let disk_manager = MemoryManager::new(1000);
+        let bpm = BufferPoolManager::new(1, K_DIST, Box::new(disk_manager));
+        let pid = bpm.new_page_id();
+
+        thread::scope(|s| {
+            for _ in 0..8 {
+                s.spawn(|| {
+                    for _ in 0..100 {
+                        let guard = bpm.read_page(pid).unwrap();
+                        assert_eq!(true, bpm.get_pin_count(pid).unwrap() > 0);
+                        drop(guard);
+                    }
+                });
+            }
+        });
+        assert_eq!(0, bpm.get_pin_count(pid).unwrap());
+
+        // a re-pinned frame must not stay evictable from its previous unpin.
+        let guard = bpm.read_page(pid).unwrap();
+        let other = bpm.new_page_id();
+        assert_eq!(
+            true,
+            matches!(bpm.read_page(other), Err(BufferPoolError::NoFreeFrame))
+        );
+        drop(guard);
+        assert_eq!(true, bpm.read_page(other).is_ok());
+        assert_eq!(None, bpm.get_pin_count(pid));
+    }
+
+    #[test]
+    fn upgrade_keeps_pin_test() {
+        let disk_manager = MemoryManager::new(1000);
+        let bpm = BufferPoolManager::new(FRAMES, K_DIST, Box::new(disk_manager));
+        let pid = bpm.new_page_id();
+
+        let upgradable = bpm.upgradable_read_page(pid).unwrap();
+        // plain readers can share the page with an upgradable one, writers can not.
+        assert_eq!(true, bpm.try_read_page(pid).is_ok());
+        assert_eq!(
+            true,
+            matches!(bpm.try_write_page(pid), Err(BufferPoolError::WouldBlock))
+        );
+        assert_eq!(1, bpm.get_pin_count(pid).unwrap());
+
+        let mut write_guard = upgradable.upgrade();
+        assert_eq!(1, bpm.get_pin_count(pid).unwrap());
+        write_guard.get_write_guard().get_writeable_data()[0] = 7;
+        assert_eq!(
+            true,
+            matches!(bpm.try_read_page(pid), Err(BufferPoolError::WouldBlock))
+        );
+        assert_eq!(1, bpm.get_pin_count(pid).unwrap());
+        drop(write_guard);
+
+        assert_eq!(0, bpm.get_pin_count(pid).unwrap());
+        let guard = bpm.try_read_page(pid).unwrap();
+        assert_eq!(7, guard.get_read_guard().get_readable_data()[0]);
+    }
+
+    #[test]
+    fn optimistic_read_test() {
+        let disk_manager = MemoryManager::new(1000);
+        let bpm = BufferPoolManager::new(FRAMES, K_DIST, Box::new(disk_manager));
+        let pid = bpm.new_page_id();
+
+        let optimistic = bpm.optimistic_read_page(pid).unwrap();
+        assert_eq!(true, optimistic.validate());
+        assert_eq!(
+            Some(0),
+            optimistic.try_read_validated(|page| page.get_readable_data()[0])
+        );
+
+        // readers do not invalidate it.
+        drop(bpm.read_page(pid).unwrap());
+        assert_eq!(true, optimistic.validate());
+
+        // an active writer does, and page can not be read meanwhile.
+        let mut write_guard = bpm.write_page(pid).unwrap();
+        assert_eq!(false, optimistic.validate());
+        assert_eq!(
+            None,
+            optimistic.try_read_validated(|page| page.get_readable_data()[0])
+        );
+        write_guard.get_write_guard().get_writeable_data()[0] = 1;
+        drop(write_guard);
+
+        assert_eq!(false, optimistic.validate());
+        assert_eq!(
+            None,
+            optimistic.try_read_validated(|page| page.get_readable_data()[0])
+        );
+        assert_eq!(1, bpm.get_pin_count(pid).unwrap());
+        drop(optimistic);
+
+        let optimistic = bpm.optimistic_read_page(pid).unwrap();
+        assert_eq!(
+            Some(1),
+            optimistic.try_read_validated(|page| page.get_readable_data()[0])
+        );
+        drop(optimistic);
+        assert_eq!(0, bpm.get_pin_count(pid).unwrap());
+    }
+
+    #[test]
+    fn fetch_timeout_test() {
+        let disk_manager = MemoryManager::new(1000);
+        let bpm = BufferPoolManager::new(1, K_DIST, Box::new(disk_manager));
+        let page_id_0 = bpm.new_page_id();
+        let page_id_1 = bpm.new_page_id();
+
+        let guard = bpm.read_page(page_id_0).unwrap();
+        let res = bpm.read_page_timeout(page_id_1, Duration::from_millis(50));
+        assert_eq!(true, matches!(res, Err(BufferPoolError::TimedOut)));
+
+        // page is in the pool, but latch is not granted in time.
+        let res = bpm.write_page_timeout(page_id_0, Duration::from_millis(50));
+        assert_eq!(true, matches!(res, Err(BufferPoolError::TimedOut)));
+        assert_eq!(1, bpm.get_pin_count(page_id_0).unwrap());
+        drop(guard);
+
+        let pinned = AtomicBool::new(false);
+        thread::scope(|s| {
+            s.spawn(|| {
+                let guard = bpm.read_page(page_id_0).unwrap();
+                pinned.store(true, Ordering::SeqCst);
+                thread::sleep(Duration::from_millis(100));
+                drop(guard);
+            });
+            while !pinned.load(Ordering::SeqCst) {
+                std::hint::spin_loop();
+            }
+
+            // waits for the other thread to release the only frame.
+            let guard = bpm.write_page_timeout(page_id_1, Duration::from_secs(10));
+            assert_eq!(true, guard.is_ok());
+        });
+    }
+
+    #[test]
+    fn fetch_deleted_page_test() {
+        let disk_manager = MemoryManager::new(1000);
+        let bpm = BufferPoolManager::new(1, K_DIST, Box::new(disk_manager));
+        let page_id_0 = bpm.new_page_id();
+        let page_id_1 = bpm.new_page_id();
+
+        let guard = bpm.read_page(page_id_0).unwrap();
+        thread::scope(|s| {
+            let waiter = s.spawn(|| {
+                bpm.read_page_timeout(page_id_1, Duration::from_secs(10))
+                    .map(drop)
+            });
+
+            thread::sleep(Duration::from_millis(100));
+            assert_eq!(true, bpm.delete_page(page_id_1).unwrap());
+            let res = waiter.join().unwrap();
+            assert_eq!(
+                true,
+                matches!(res, Err(BufferPoolError::PageDeleted(id)) if id == page_id_1)
+            );
+        });
+        drop(guard);
+    }
+
+    #[test]
+    fn fetch_io_error_test() {
+        let faults = Faults::default();
+        let bpm = BufferPoolManager::new(1, K_DIST, faults.operator());
+        let page_id_0 = bpm.new_page_id();
+        let page_id_1 = bpm.new_page_id();
+        drop(bpm.write_page(page_id_0).unwrap());
+        // evicts page 0, which has to be read back from disk then.
+        drop(bpm.write_page(page_id_1).unwrap());
+
+        faults.fail_reads.store(true, Ordering::SeqCst);
+        let res = bpm.read_page_timeout(page_id_0, Duration::from_secs(1));
+        assert_eq!(true, matches!(res, Err(BufferPoolError::Io(_))));
+    }
+
+    // Fails reads or writes of the page operator it hands out, on demand.
+    #[derive(Default, Clone)]
+    struct Faults {
+        fail_reads: Arc<AtomicBool>,
+        fail_writes: Arc<AtomicBool>,
+        panic: Arc<AtomicBool>,
+    }
+
+    struct FailingOperator {
+        memory: MemoryManager,
+        faults: Faults,
+    }
+
+    impl Faults {
+        fn operator(&self) -> Box<dyn PageOperator> {
+            Box::new(FailingOperator {
+                memory: MemoryManager::new(100),
+                faults: self.clone(),
+            })
+        }
+
+        fn check(&self, fail: &AtomicBool) -> io::Result<()> {
+            if self.panic.load(Ordering::SeqCst) {
+                panic!("page operator crashed");
+            }
+            if fail.load(Ordering::SeqCst) {
+                return Err(io::Error::other("bad sector"));
+            }
+            Ok(())
+        }
+    }
+
+    impl PageOperator for FailingOperator {
+        fn write_page(&mut self, page_id: usize, data: &[u8; PAGE_SIZE]) -> io::Result<()> {
+            self.faults.check(&self.faults.fail_writes)?;
+            self.memory.write_page(page_id, data)
+        }
+
+        fn read_page(&mut self, page_id: usize, data: &mut [u8; PAGE_SIZE]) -> io::Result<()> {
+            self.faults.check(&self.faults.fail_reads)?;
+            self.memory.read_page(page_id, data)
+        }
+    }
+
+    fn write_str(bpm: &BufferPoolManager, page_id: usize, content: &str) {
+        let mut guard = bpm.write_page(page_id).unwrap();
+        let data = guard.get_write_guard().get_writeable_data();
+        data[..content.len()].copy_from_slice(content.as_bytes());
+    }
+
+    fn read_str(bpm: &BufferPoolManager, page_id: usize, len: usize) -> String {
+        let guard = bpm.read_page(page_id).unwrap();
+        String::from_utf8(guard.get_read_guard().get_readable_data()[..len].to_vec()).unwrap()
+    }
+
+    #[test]
+    fn failed_write_back_keeps_page_test() {
+        let faults = Faults::default();
+        let bpm = BufferPoolManager::new(1, K_DIST, faults.operator());
+        let page_id_0 = bpm.new_page_id();
+        let page_id_1 = bpm.new_page_id();
+        write_str(&bpm, page_id_0, "page0");
+
+        faults.fail_writes.store(true, Ordering::SeqCst);
+        let res = bpm.write_page(page_id_1);
+        assert_eq!(true, matches!(res, Err(BufferPoolError::Io(_))));
+        assert_eq!(
+            true,
+            matches!(bpm.flush_page(page_id_0), Err(BufferPoolError::Io(_)))
+        );
+        assert_eq!(
+            true,
+            matches!(bpm.flush_all_pages(), Err(BufferPoolError::Io(_)))
+        );
+
+        // page 0 is still in the pool, and still dirty.
+        assert_eq!(Some(0), bpm.get_pin_count(page_id_0));
+        assert_eq!("page0", read_str(&bpm, page_id_0, 5));
+
+        faults.fail_writes.store(false, Ordering::SeqCst);
+        write_str(&bpm, page_id_1, "page1");
+        assert_eq!(None, bpm.get_pin_count(page_id_0));
+        assert_eq!("page0", read_str(&bpm, page_id_0, 5));
+    }
+
+    #[test]
+    fn failed_write_back_keeps_residency_test() {
+        let faults = Faults::default();
+        let bpm = BufferPoolManager::new(2, K_DIST, faults.operator());
+        let warm = bpm.new_page_id();
+        bpm.set_residency(warm, Residency::KeepWarm).unwrap();
+        write_str(&bpm, warm, "warm");
+
+        // warm page is the only one left to evict.
+        let _pinned = bpm.read_page(bpm.new_page_id()).unwrap();
+        faults.fail_writes.store(true, Ordering::SeqCst);
+        let res = bpm.read_page(bpm.new_page_id());
+        assert_eq!(true, matches!(res, Err(BufferPoolError::Io(_))));
+
+        let frame = bpm
+            .frame_stats()
+            .into_iter()
+            .find(|it| it.page_id == Some(warm))
+            .unwrap();
+        assert_eq!(Residency::KeepWarm, frame.residency);
+        assert_eq!(true, frame.evictable);
+    }
+
+    #[test]
+    fn failed_read_keeps_frame_free_test() {
+        let faults = Faults::default();
+        let bpm = BufferPoolManager::new(1, K_DIST, faults.operator());
+        let page_id_0 = bpm.new_page_id();
+        let page_id_1 = bpm.new_page_id();
+        write_str(&bpm, page_id_0, "page0");
+        bpm.flush_all_pages().unwrap();
+        write_str(&bpm, page_id_1, "page1");
+
+        faults.fail_reads.store(true, Ordering::SeqCst);
+        assert_eq!(
+            true,
+            matches!(bpm.read_page(page_id_0), Err(BufferPoolError::Io(_)))
+        );
+        assert_eq!(None, bpm.get_pin_count(page_id_0));
+
+        faults.fail_reads.store(false, Ordering::SeqCst);
+        assert_eq!("page0", read_str(&bpm, page_id_0, 5));
+        assert_eq!("page1", read_str(&bpm, page_id_1, 5));
+    }
+
+    #[test]
+    fn scheduler_shutdown_test() {
+        let faults = Faults::default();
+        let bpm = BufferPoolManager::new(1, K_DIST, faults.operator());
+        let page_id_0 = bpm.new_page_id();
+        write_str(&bpm, page_id_0, "page0");
+
+        faults.panic.store(true, Ordering::SeqCst);
+        let res = bpm.flush_page(page_id_0);
+        assert_eq!(true, matches!(res, Err(BufferPoolError::SchedulerShutdown)));
+        let res = bpm.flush_page(page_id_0);
+        assert_eq!(true, matches!(res, Err(BufferPoolError::SchedulerShutdown)));
+    }
+
+    #[test]
+    fn invalid_and_deleted_page_test() {
+        let disk_manager = MemoryManager::new(1000);
+        let bpm = BufferPoolManager::new(FRAMES, K_DIST, Box::new(disk_manager));
+        let page_id = bpm.new_page_id();
+
+        let res = bpm.read_page(page_id + 1);
+        assert_eq!(true, matches!(res, Err(BufferPoolError::InvalidPageId(_))));
+        let res = bpm.delete_page(page_id + 1);
+        assert_eq!(true, matches!(res, Err(BufferPoolError::InvalidPageId(_))));
+
+        let guard = bpm.read_page(page_id).unwrap();
+        assert_eq!(false, bpm.delete_page(page_id).unwrap());
+        drop(guard);
+        assert_eq!(true, bpm.delete_page(page_id).unwrap());
+        let res = bpm.write_page(page_id);
+        assert_eq!(true, matches!(res, Err(BufferPoolError::PageDeleted(_))));
+    }
+
+    #[test]
+    fn poisoned_latch_test() {
+        let disk_manager = MemoryManager::new(1000);
+        let bpm = BufferPoolManager::new(FRAMES, K_DIST, Box::new(disk_manager));
+        let page_id = bpm.new_page_id();
+
+        thread::scope(|s| {
+            let res = s
+                .spawn(|| {
+                    let _protected = bpm.protected.lock().unwrap();
+                    panic!("panic while holding bpm lock");
+                })
+                .join();
+            assert_eq!(true, res.is_err());
+        });
+
+        let res = bpm.read_page(page_id);
+        assert_eq!(true, matches!(res, Err(BufferPoolError::PoisonedLatch)));
+    }
+
+    #[test]
+    fn stats_test() {
+        let disk_manager = MemoryManager::new(1000);
+        let bpm = BufferPoolManager::new(2, K_DIST, Box::new(disk_manager));
+        let page_ids = (0..3).map(|_| bpm.new_page_id()).collect::<Vec<_>>();
+
+        write_str(&bpm, page_ids[0], "page0");
+        let guard = bpm.read_page(page_ids[0]).unwrap();
+        let stats = bpm.stats();
+        assert_eq!((1, 1), (stats.hits, stats.misses));
+        assert_eq!(
+            (1, 1, 1),
+            (stats.pinned_frames, stats.dirty_frames, stats.free_frames)
+        );
+
+        // page 1 goes to the free frame, page 2 evicts it as page 0 is pinned.
+        write_str(&bpm, page_ids[1], "page1");
+        write_str(&bpm, page_ids[2], "page2");
+        let stats = bpm.stats();
+        assert_eq!((1, 3, 1), (stats.hits, stats.misses, stats.evictions));
+        assert_eq!(
+            (1, 0, 1),
+            (stats.dirty_write_backs, stats.disk_reads, stats.disk_writes)
+        );
+        assert_eq!(
+            (2, 1, 2, 0),
+            (
+                stats.num_frames,
+                stats.pinned_frames,
+                stats.dirty_frames,
+                stats.free_frames
+            )
+        );
+
+        let frames = bpm.frame_stats();
+        assert_eq!(2, frames.len());
+        let frame = frames
+            .iter()
+            .find(|it| it.page_id == Some(page_ids[0]))
+            .unwrap();
+        assert_eq!(
+            (1, true, false),
+            (frame.pin_count, frame.is_dirty, frame.evictable)
+        );
+        assert_eq!(2, frame.history.len());
+        let frame = frames
+            .iter()
+            .find(|it| it.page_id == Some(page_ids[2]))
+            .unwrap();
+        assert_eq!(
+            (0, true, true),
+            (frame.pin_count, frame.is_dirty, frame.evictable)
+        );
+
+        drop(guard);
+        bpm.flush_all_pages().unwrap();
+        assert_eq!("page1", read_str(&bpm, page_ids[1], 5));
+        let stats = bpm.stats();
+        assert_eq!((1, 3), (stats.disk_reads, stats.disk_writes));
+        assert_eq!(0, stats.pinned_frames);
+    }
+
+    #[test]
+    fn shrink_below_resident_budget_test() {
+        let disk_manager = MemoryManager::new(1000);
+        let bpm = BufferPoolManager::new(8, K_DIST, Box::new(disk_manager));
+        let page_ids = (0..3).map(|_| bpm.new_page_id()).collect::<Vec<_>>();
+        // a quarter of 8 frames.
+        for &page_id in &page_ids[..2] {
+            bpm.set_residency(page_id, Residency::NeverEvict).unwrap();
+            drop(bpm.read_page(page_id).unwrap());
+        }
+
+        // two frames leave room for a single never evict page.
+        let res = bpm.resize(2, Duration::ZERO);
+        assert_eq!(
+            true,
+            matches!(res, Err(BufferPoolError::ResidentBudgetExceeded(1)))
+        );
+        assert_eq!(8, bpm.stats().num_frames);
+        bpm.set_residency(page_ids[2], Residency::NeverEvict)
+            .unwrap_err();
+
+        bpm.set_residency(page_ids[1], Residency::Normal).unwrap();
+        bpm.resize(2, Duration::ZERO).unwrap();
+        let res = bpm.set_residency(page_ids[1], Residency::NeverEvict);
+        assert_eq!(
+            true,
+            matches!(res, Err(BufferPoolError::ResidentBudgetExceeded(1)))
+        );
+        // once read back, never evict page keeps its frame and the other one is shared by every other page.
+        for &page_id in &page_ids {
+            drop(bpm.read_page(page_id).unwrap());
+        }
+        assert_eq!(true, bpm.get_pin_count(page_ids[0]).is_some());
+    }
+
+    #[test]
+    fn grow_test() {
+        let disk_manager = MemoryManager::new(1000);
+        let bpm = BufferPoolManager::new(1, K_DIST, Box::new(disk_manager));
+        let page_id_0 = bpm.new_page_id();
+        let page_id_1 = bpm.new_page_id();
+
+        let guard = bpm.read_page(page_id_0).unwrap();
+        assert_eq!(
+            true,
+            matches!(bpm.read_page(page_id_1), Err(BufferPoolError::NoFreeFrame))
+        );
+        bpm.resize(2, Duration::ZERO).unwrap();
+        assert_eq!(true, bpm.read_page(page_id_1).is_ok());
+        assert_eq!(2, bpm.stats().num_frames);
+        drop(guard);
+    }
+
+    #[test]
+    fn shrink_test() {
+        let disk_manager = MemoryManager::new(1000);
+        let bpm = BufferPoolManager::new(4, K_DIST, Box::new(disk_manager));
+        let page_ids = (0..4).map(|_| bpm.new_page_id()).collect::<Vec<_>>();
+        for (i, page_id) in page_ids.iter().enumerate() {
+            write_str(&bpm, *page_id, &format!("page{i}"));
+        }
+
+        // free frames are handed out from the end, so page 0 sits in last frame.
+        let guard = bpm.read_page(page_ids[0]).unwrap();
+        let res = bpm.resize(1, Duration::from_millis(10));
+        assert_eq!(true, matches!(res, Err(BufferPoolError::TimedOut)));
+        assert_eq!(4, bpm.stats().num_frames);
+        assert_eq!("page3", read_str(&bpm, page_ids[3], 5));
+
+        thread::scope(|s| {
+            let resize = s.spawn(|| bpm.resize(1, Duration::from_secs(10)));
+            thread::sleep(Duration::from_millis(10));
+            // still resident, so it can be fetched while pool shrinks.
+            assert_eq!("page0", read_str(&bpm, page_ids[0], 5));
+            drop(guard);
+            resize.join().unwrap().unwrap();
         });
+
+        let stats = bpm.stats();
+        assert_eq!((1, 0), (stats.num_frames, stats.free_frames));
+        assert_eq!(1, bpm.frame_stats().len());
+        // dropped frames were written back.
+        for (i, page_id) in page_ids.iter().enumerate() {
+            assert_eq!(format!("page{i}"), read_str(&bpm, *page_id, 5));
+        }
+    }
+
+    // Outlives buffer pools handed its clones, like a disk does across restarts.
+    #[derive(Clone)]
+    struct SharedMemory(Arc<std::sync::Mutex<MemoryManager>>);
+
+    impl PageOperator for SharedMemory {
+        fn write_page(&mut self, page_id: usize, data: &[u8; PAGE_SIZE]) -> io::Result<()> {
+            self.0.lock().unwrap().write_page(page_id, data)
+        }
+
+        fn read_page(&mut self, page_id: usize, data: &mut [u8; PAGE_SIZE]) -> io::Result<()> {
+            self.0.lock().unwrap().read_page(page_id, data)
+        }
+    }
+
+    #[test]
+    fn warm_up_test() {
+        let path = std::env::temp_dir().join(format!("warm_up_test_{}", std::process::id()));
+        let disk = SharedMemory(Arc::new(std::sync::Mutex::new(MemoryManager::new(100))));
+
+        let bpm = BufferPoolManager::new(3, K_DIST, Box::new(disk.clone()));
+        assert_eq!(0, bpm.warm_up(&path).unwrap());
+        let page_ids = (0..4).map(|_| bpm.new_page_id()).collect::<Vec<_>>();
+        for (i, page_id) in page_ids.iter().enumerate() {
+            write_str(&bpm, *page_id, &format!("page{i}"));
+        }
+        // page 1 is the least recently used one left.
+        read_str(&bpm, page_ids[2], 5);
+        read_str(&bpm, page_ids[3], 5);
+        bpm.save_warm_up(&path).unwrap();
+        drop(bpm);
+
+        // only two frames this time, so page 1 is left out.
+        let bpm = BufferPoolManager::new(2, K_DIST, Box::new(disk));
+        assert_eq!(2, bpm.warm_up(&path).unwrap());
+        assert_eq!(4, bpm.new_page_id());
+        let stats = bpm.stats();
+        assert_eq!((2, 0), (stats.disk_reads, stats.free_frames));
+        assert_eq!(None, bpm.get_pin_count(page_ids[1]));
+        for frame in bpm.frame_stats() {
+            assert_eq!((true, 2), (frame.evictable, frame.history.len()));
+        }
+        assert_eq!("page2", read_str(&bpm, page_ids[2], 5));
+        assert_eq!("page3", read_str(&bpm, page_ids[3], 5));
+        assert_eq!(2, bpm.stats().hits);
+        // pages left out are still read from disk, not handed out blank.
+        assert_eq!("page1", read_str(&bpm, page_ids[1], 5));
+        std::fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn residency_test() {
+        let disk_manager = MemoryManager::new(1000);
+        let bpm = BufferPoolManager::new(4, K_DIST, Box::new(disk_manager));
+        let root = bpm.new_page_id();
+        let internal = bpm.new_page_id();
+        let leaf = bpm.new_page_id();
+        bpm.set_residency(root, Residency::NeverEvict).unwrap();
+        bpm.set_residency(internal, Residency::KeepWarm).unwrap();
+        // a quarter of 4 frames.
+        let res = bpm.set_residency(leaf, Residency::NeverEvict);
+        assert_eq!(
+            true,
+            matches!(res, Err(BufferPoolError::ResidentBudgetExceeded(1)))
+        );
+        for page_id in [root, internal, leaf] {
+            write_str(&bpm, page_id, "index");
+        }
+
+        // a scan through many pages only cycles through the two normal frames.
+        for _ in 0..20 {
+            let page_id = bpm.new_page_id();
+            drop(bpm.read_page(page_id).unwrap());
+        }
+        assert_eq!(true, bpm.get_pin_count(root).is_some());
+        assert_eq!(true, bpm.get_pin_count(internal).is_some());
+        assert_eq!(None, bpm.get_pin_count(leaf));
+        let frame = bpm
+            .frame_stats()
+            .into_iter()
+            .find(|it| it.page_id == Some(root))
+            .unwrap();
+        assert_eq!(Residency::NeverEvict, frame.residency);
+
+        // with every normal frame pinned, a keep warm page makes room but a never evict one does not.
+        let scan = (0..2)
+            .map(|_| bpm.read_page(bpm.new_page_id()).unwrap())
+            .collect::<Vec<_>>();
+        let leaf_guard = bpm.read_page(leaf).unwrap();
+        assert_eq!(None, bpm.get_pin_count(internal));
+        let res = bpm.read_page(internal);
+        assert_eq!(true, matches!(res, Err(BufferPoolError::NoFreeFrame)));
+        drop(leaf_guard);
+        drop(scan);
+
+        bpm.set_resident_budget(2).unwrap();
+        bpm.set_residency(leaf, Residency::NeverEvict).unwrap();
+        bpm.set_residency(root, Residency::Normal).unwrap();
+        assert_eq!(true, bpm.delete_page(leaf).unwrap());
+        assert_eq!(
+            true,
+            bpm.set_residency(internal, Residency::NeverEvict).is_ok()
+        );
     }
 
     // #[test]
@@ -0,0 +1,178 @@
+use std::{
+    collections::HashMap,
+    sync::{Arc, RwLock},
+};
+
+use catalog::PoolAssignment;
+use storage::PageOperator;
+
+use crate::{BufferPoolError, BufferPoolManager, BufferPoolStats, ReplacementPolicy};
+
+/// Name of the pool tables and indexes go to unless catalog assigns them another one.
+pub const DEFAULT_BUFFER_POOL: &str = "default";
+
+/// Named buffer pools of a database, each with its own frames, replacer and disk file.
+///
+/// A page id only means something within its pool, every pool hands out its own. Tables and indexes are
+/// routed to a pool by name their catalog entry carries, see `PoolAssignment`.
+pub struct BufferPools {
+    pools: RwLock<HashMap<String, Arc<BufferPoolManager>>>,
+}
+
+impl BufferPools {
+    pub fn new(default_pool: Arc<BufferPoolManager>) -> Self {
+        let pools = HashMap::from([(DEFAULT_BUFFER_POOL.to_string(), default_pool)]);
+        Self {
+            pools: RwLock::new(pools),
+        }
+    }
+
+    /// Adds a pool of `num_frames` frames whose replacer evicts by `policy`. `ReplacementPolicy::Fifo` or
+    /// `ReplacementPolicy::LruK(1)` suit a pool meant for scans.
+    pub fn create(
+        &self,
+        name: impl Into<String>,
+        num_frames: usize,
+        policy: ReplacementPolicy,
+        page_operator: Box<dyn PageOperator>,
+    ) -> Result<Arc<BufferPoolManager>, BufferPoolError> {
+        let name = name.into();
+        let mut pools = self.pools.write()?;
+        if pools.contains_key(&name) {
+            return Err(BufferPoolError::PoolExists(name));
+        }
+        let pool = Arc::new(BufferPoolManager::with_policy(
+            num_frames,
+            policy,
+            page_operator,
+        ));
+        pools.insert(name, pool.clone());
+        Ok(pool)
+    }
+
+    /// Takes a pool out of registry. Default pool can not be removed. Tables and indexes assigned to it fail
+    /// to be routed from then on, pages already fetched stay usable as long as pool is referenced.
+    pub fn remove(&self, name: &str) -> Result<Option<Arc<BufferPoolManager>>, BufferPoolError> {
+        if name == DEFAULT_BUFFER_POOL {
+            return Ok(None);
+        }
+        Ok(self.pools.write()?.remove(name))
+    }
+
+    pub fn get(&self, name: &str) -> Result<Arc<BufferPoolManager>, BufferPoolError> {
+        self.pools
+            .read()?
+            .get(name)
+            .cloned()
+            .ok_or_else(|| BufferPoolError::UnknownPool(name.to_string()))
+    }
+
+    /// Pool a table or index is assigned to in catalog.
+    pub fn pool_for(
+        &self,
+        object: &impl PoolAssignment,
+    ) -> Result<Arc<BufferPoolManager>, BufferPoolError> {
+        self.get(object.buffer_pool().unwrap_or(DEFAULT_BUFFER_POOL))
+    }
+
+    /// Stats of every pool, ordered by pool name.
+    pub fn stats_by_pool(&self) -> Result<Vec<(String, BufferPoolStats)>, BufferPoolError> {
+        let mut stats = self
+            .pools
+            .read()?
+            .iter()
+            .map(|(name, pool)| (name.clone(), pool.stats()))
+            .collect::<Vec<_>>();
+        stats.sort_unstable_by(|a, b| a.0.cmp(&b.0));
+        Ok(stats)
+    }
+
+    /// Stats of all pools added up.
+    pub fn stats(&self) -> Result<BufferPoolStats, BufferPoolError> {
+        let mut total = BufferPoolStats::default();
+        for pool in self.pools.read()?.values() {
+            total += pool.stats();
+        }
+        Ok(total)
+    }
+}
+
+#[cfg(test)]
+#[allow(clippy::bool_assert_comparison)]
+mod test {
+    use std::sync::Arc;
+
+    use catalog::{parse_create_stmt, IndexInfo, TableInfo};
+    use storage::MemoryManager;
+
+    use crate::{BufferPoolError, BufferPoolManager, ReplacementPolicy};
+
+    use super::{BufferPools, DEFAULT_BUFFER_POOL};
+
+    #[test]
+    fn route_by_catalog_test() {
+        let default_pool = Arc::new(BufferPoolManager::new(
+            4,
+            2,
+            Box::new(MemoryManager::new(100)),
+        ));
+        let pools = BufferPools::new(default_pool.clone());
+        let oltp = pools
+            .create(
+                "oltp",
+                8,
+                ReplacementPolicy::LruK(2),
+                Box::new(MemoryManager::new(100)),
+            )
+            .unwrap();
+        pools
+            .create(
+                "scan",
+                2,
+                ReplacementPolicy::Fifo,
+                Box::new(MemoryManager::new(100)),
+            )
+            .unwrap();
+        let res = pools.create(
+            "scan",
+            2,
+            ReplacementPolicy::Fifo,
+            Box::new(MemoryManager::new(100)),
+        );
+        assert_eq!(true, matches!(res, Err(BufferPoolError::PoolExists(_))));
+
+        let mut table = TableInfo::new("orders", parse_create_stmt("id bigint"), 1);
+        let mut index = IndexInfo::new("orders_pk", parse_create_stmt("id bigint"), 1, "orders");
+        assert_eq!(
+            true,
+            Arc::ptr_eq(&default_pool, &pools.pool_for(&table).unwrap())
+        );
+        table.set_buffer_pool(Some("scan".into()));
+        index.set_buffer_pool(Some("oltp".into()));
+        assert_eq!(true, Arc::ptr_eq(&oltp, &pools.pool_for(&index).unwrap()));
+
+        // a scan through the table only ever touches frames of its own pool.
+        let scan = pools.pool_for(&table).unwrap();
+        for _ in 0..10 {
+            drop(scan.read_page(scan.new_page_id()).unwrap());
+        }
+        let page_id = oltp.new_page_id();
+        drop(oltp.write_page(page_id).unwrap());
+
+        let by_pool = pools.stats_by_pool().unwrap();
+        let names = by_pool.iter().map(|it| it.0.as_str()).collect::<Vec<_>>();
+        assert_eq!(vec![DEFAULT_BUFFER_POOL, "oltp", "scan"], names);
+        assert_eq!((1, 0), (by_pool[1].1.misses, by_pool[1].1.evictions));
+        assert_eq!((10, 8), (by_pool[2].1.misses, by_pool[2].1.evictions));
+        let total = pools.stats().unwrap();
+        assert_eq!(
+            (14, 11, 8),
+            (total.num_frames, total.misses, total.evictions)
+        );
+
+        assert_eq!(true, pools.remove(DEFAULT_BUFFER_POOL).unwrap().is_none());
+        assert_eq!(true, pools.remove("scan").unwrap().is_some());
+        let res = pools.pool_for(&table);
+        assert_eq!(true, matches!(res, Err(BufferPoolError::UnknownPool(_))));
+    }
+}
@@ -0,0 +1,70 @@
+use std::{fmt::Display, io, sync::PoisonError};
+
+#[derive(Debug)]
+pub enum BufferPoolError {
+    /// Every frame is pinned, so page can not be brought in.
+    NoFreeFrame,
+    /// No frame became evictable, or page latch was not granted, before deadline.
+    TimedOut,
+    /// Page latch is held by someone else, and caller asked not to wait for it.
+    WouldBlock,
+    /// Reading page in, or writing a dirty page back, failed.
+    Io(io::Error),
+    /// A thread panicked while holding buffer pool lock, its state can not be trusted anymore.
+    PoisonedLatch,
+    /// Page id was never handed out by `BufferPoolManager::new_page_id`.
+    InvalidPageId(usize),
+    /// Page was deleted through `BufferPoolManager::delete_page`.
+    PageDeleted(usize),
+    /// Disk scheduler is gone, no request can be served anymore.
+    SchedulerShutdown,
+    /// Page can not be made `Residency::NeverEvict`, buffer pool already holds as many such pages as allowed.
+    ResidentBudgetExceeded(usize),
+    /// No buffer pool of that name, see `BufferPools`.
+    UnknownPool(String),
+    /// A buffer pool of that name already exists.
+    PoolExists(String),
+}
+
+impl Display for BufferPoolError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            BufferPoolError::NoFreeFrame => write!(f, "all frames are pinned"),
+            BufferPoolError::TimedOut => {
+                write!(f, "timed out waiting for an evictable frame or page latch")
+            }
+            BufferPoolError::WouldBlock => write!(f, "page latch is held"),
+            BufferPoolError::Io(err) => write!(f, "disk io failed: {err}"),
+            BufferPoolError::PoisonedLatch => write!(f, "buffer pool lock is poisoned"),
+            BufferPoolError::InvalidPageId(page_id) => write!(f, "page {page_id} is not allocated"),
+            BufferPoolError::PageDeleted(page_id) => write!(f, "page {page_id} is deleted"),
+            BufferPoolError::SchedulerShutdown => write!(f, "disk scheduler is shut down"),
+            BufferPoolError::ResidentBudgetExceeded(budget) => {
+                write!(f, "no more than {budget} pages can be kept resident")
+            }
+            BufferPoolError::UnknownPool(name) => write!(f, "there is no buffer pool `{name}`"),
+            BufferPoolError::PoolExists(name) => write!(f, "buffer pool `{name}` already exists"),
+        }
+    }
+}
+
+impl std::error::Error for BufferPoolError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            BufferPoolError::Io(err) => Some(err),
+            _ => None,
+        }
+    }
+}
+
+impl From<io::Error> for BufferPoolError {
+    fn from(value: io::Error) -> Self {
+        BufferPoolError::Io(value)
+    }
+}
+
+impl<T> From<PoisonError<T>> for BufferPoolError {
+    fn from(_: PoisonError<T>) -> Self {
+        BufferPoolError::PoisonedLatch
+    }
+}
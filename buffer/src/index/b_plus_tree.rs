@@ -1,16 +1,62 @@
 #![allow(dead_code, unused_variables)]
 
-use std::{borrow::Borrow, marker::PhantomData, sync::Arc};
+use std::{borrow::Borrow, cmp::Ordering, marker::PhantomData, ops::RangeBounds, sync::Arc};
 
-use storage::{SizeHelper, BPLUS_TREE_INTERNAL_PAGE_HEADER_SIZE, BPLUS_TREE_LEAF_PAGE_HEADER_SIZE};
+use storage::{
+    BPlusTreeInternalPage, BPlusTreeLeafPage, BplusTreeHeaderPage, FrameHeader, PagePod,
+    ReadPageGuard, SizeHelper, WritePageGuard, INVALID_PAGE_ID,
+};
 
-use crate::BufferPoolManager;
+use super::{BPlusTreeIterator, IndexError, KeyComparator};
+use crate::{BufferPoolError, BufferPoolManager, Residency};
+
+// Key of internal pages, a whole leaf entry. Entries sharing a key are ordered by value, so every entry has a
+// place of its own in tree and internal pages lead to it, not only to its key.
+#[repr(C)]
+#[derive(Debug, Clone, Copy)]
+pub(super) struct Separator<KeyType, ValueType> {
+    pub(super) key: KeyType,
+    pub(super) value: ValueType,
+}
+
+impl<KeyType, ValueType> Separator<KeyType, ValueType> {
+    // Evaluated once a tree is made for a pair of types, see `BPlusTree::attach`.
+    const NO_PADDING: () = assert!(
+        size_of::<Self>() == size_of::<KeyType>() + size_of::<ValueType>(),
+        "key and value types leave padding in a separator"
+    );
+}
+
+// Safety: both fields are `PagePod`. A tree does not build for types which leave padding between or after
+// them, see `NO_PADDING`, and separators are not laid out in pages anywhere else.
+unsafe impl<KeyType: PagePod, ValueType: PagePod> PagePod for Separator<KeyType, ValueType> {}
+
+pub(super) type InternalPage<KeyType, ValueType, Page> =
+    BPlusTreeInternalPage<Separator<KeyType, ValueType>, usize, Page>;
+
+// Write latched internal pages on the way down, from the highest one a change may still reach. Each with its
+// page id and index of child taken.
+type Path = Vec<(usize, usize, WritePageGuard)>;
 
 /// Main class providing the API for the Interactive B+ Tree.
-pub struct BPlusTree<KeyType, ValueType, KeyComparator> {
+///
+/// Leaves split once they reach `leaf_max_size` entries, internal pages once they go over `internal_max_size`
+/// children. Pages are changed in place in their frames, see `BPlusTreeLeafPage` and `BPlusTreeInternalPage`.
+/// Tree maps a key to any number of values. Keys are ordered by `comparator`, see `KeyComparator`, and entries
+/// sharing a key by value. Internal pages are keyed by whole entries, see `Separator`, so a single entry is
+/// found and removed without looking through others with same key.
+///
+/// Root page id and height are kept in header page, see `BplusTreeHeaderPage`. Header page is latched for
+/// writing while a change may reach root, so root moves along with the split or merge causing it.
+///
+/// Tree can be shared between threads, every operation takes `&self`. Pages are latched top down, a latch
+/// on a child is taken before the one on its parent is let go. Lookups only take read latches. Inserts and
+/// removes first try with read latches down to leaf and a write latch on it, and go down again with write
+/// latches only when leaf has to split or fall under half full.
+pub struct BPlusTree<KeyType, ValueType, Comparator> {
     index_name: String,
-    bpm: Arc<BufferPoolManager>,
-    comparator: KeyComparator,
+    pub(super) bpm: Arc<BufferPoolManager>,
+    pub(super) comparator: Comparator,
 
     log: Vec<String>,
     leaf_max_size: u32,
@@ -21,66 +67,979 @@ pub struct BPlusTree<KeyType, ValueType, KeyComparator> {
     v: PhantomData<ValueType>,
 }
 
-impl<KeyType, ValueType, KeyComparator> BPlusTree<KeyType, ValueType, KeyComparator> {
+impl<KeyType: PagePod, ValueType: PagePod + Ord, Comparator: KeyComparator<KeyType>>
+    BPlusTree<KeyType, ValueType, Comparator>
+{
+    /// Creates an empty tree, laying out header page at `header_page_id` over whatever it held.
     pub fn new(
         index_name: String,
         header_page_id: usize,
         bpm: Arc<BufferPoolManager>,
-        comparator: KeyComparator,
+        comparator: Comparator,
         leaf_max_size: Option<u32>,
         internal_max_size: Option<u32>,
+    ) -> Result<Self, BufferPoolError> {
+        let leaf_max_size =
+            leaf_max_size.unwrap_or(SizeHelper::get_page_slot_cnt::<KeyType, ValueType>() as u32);
+        // internal pages take one child over max size before they split.
+        let internal_max_size = internal_max_size.unwrap_or(
+            SizeHelper::get_page_slot_cnt::<Separator<KeyType, ValueType>, usize>() as u32 - 1,
+        );
+        // both halves of a split must keep at least one key, internal ones at least two children.
+        assert!(leaf_max_size >= 2, "leaf max size must be at least 2");
+        assert!(
+            internal_max_size >= 3,
+            "internal max size must be at least 3"
+        );
+        let mut guard = bpm.write_page(header_page_id)?;
+        *guard.get_writeable_data_as() = BplusTreeHeaderPage::new(leaf_max_size, internal_max_size);
+        drop(guard);
+        Ok(Self::attach(
+            index_name,
+            header_page_id,
+            bpm,
+            comparator,
+            leaf_max_size,
+            internal_max_size,
+        ))
+    }
+
+    /// Reattaches to a tree made by `new` with same `header_page_id`, after a restart for instance. Max sizes
+    /// are the ones tree was made with. `NotAnIndexPage` if `header_page_id` holds no tree header.
+    pub fn open(
+        index_name: String,
+        header_page_id: usize,
+        bpm: Arc<BufferPoolManager>,
+        comparator: Comparator,
+    ) -> Result<Self, IndexError> {
+        let guard = bpm.read_page(header_page_id)?;
+        let header = *guard.get_readable_data_as::<BplusTreeHeaderPage>();
+        drop(guard);
+        if !header.is_valid() {
+            return Err(IndexError::NotAnIndexPage(header_page_id));
+        }
+        Ok(Self::attach(
+            index_name,
+            header_page_id,
+            bpm,
+            comparator,
+            header.leaf_max_size(),
+            header.internal_max_size(),
+        ))
+    }
+
+    fn attach(
+        index_name: String,
+        header_page_id: usize,
+        bpm: Arc<BufferPoolManager>,
+        comparator: Comparator,
+        leaf_max_size: u32,
+        internal_max_size: u32,
     ) -> Self {
-        let leaf_page_size = SizeHelper::get_internal_page_slot_cnt::<
-            BPLUS_TREE_LEAF_PAGE_HEADER_SIZE,
-            KeyType,
-            ValueType,
-        >() as u32;
-        let internal_page_size = SizeHelper::get_internal_page_slot_cnt::<
-            BPLUS_TREE_INTERNAL_PAGE_HEADER_SIZE,
-            KeyType,
-            ValueType,
-        >() as u32;
+        let () = Separator::<KeyType, ValueType>::NO_PADDING;
+        // every lookup starts at header page. It is only a hint, tree works the same without it.
+        let _ = bpm.set_residency(header_page_id, Residency::KeepWarm);
         Self {
             index_name,
             bpm,
             comparator,
             log: vec![],
-            leaf_max_size: leaf_max_size.unwrap_or(leaf_page_size),
-            internal_max_size: internal_max_size.unwrap_or(internal_page_size),
+            leaf_max_size,
+            internal_max_size,
             header_page_id,
             k: PhantomData,
             v: PhantomData,
         }
     }
-    pub fn is_empty() -> bool {
-        todo!()
+
+    pub fn is_empty(&self) -> Result<bool, BufferPoolError> {
+        let guard = self.bpm.read_page(self.header_page_id)?;
+        let header = guard.get_readable_data_as::<BplusTreeHeaderPage>();
+        Ok(header.root_page_id().is_none())
     }
 
-    pub fn insert(&mut self, key: KeyType, value: ValueType) -> bool {
-        true
+    /// Insert a key value pair. A key may be inserted with any number of values.
+    /// @return : false if pair is already in tree, it is left as it was then
+    ///
+    /// Goes down with read latches and only write latches leaf at first. Only if leaf is full it goes down
+    /// again, write latching every page on the way and letting go of those above once a page has room for
+    /// another child. An error from buffer pool in the middle of a split may leave tree broken.
+    pub fn insert(&self, key: KeyType, value: ValueType) -> Result<bool, BufferPoolError> {
+        if let Some((mut guard, _)) = self.write_leaf_optimistic(&key, &value)? {
+            let mut leaf = BPlusTreeLeafPage::new_mut(guard.get_write_guard());
+            match leaf.lookup(self.cmp_entry(&key, &value)) {
+                Ok(_) => return Ok(false),
+                Err(index) if leaf.len() + 1 < self.leaf_max_size as usize => {
+                    leaf.insert(index, key, value);
+                    return Ok(true);
+                }
+                Err(_) => {}
+            }
+        }
+        self.insert_pessimistic(key, value)
     }
 
-    /// Return the only value that associated with input key.
+    /// Return all values associated with input key, in value order.
     /// This method is used for point query
     /// @return : true means key exists
-    pub fn get_value(&self, key: KeyType, result: &mut Vec<ValueType>) -> bool {
-        true
+    ///
+    /// Values of a key may span several leaves, they are read left to right, each latched before the one
+    /// before is let go.
+    pub fn get_value(
+        &self,
+        key: KeyType,
+        result: &mut Vec<ValueType>,
+    ) -> Result<bool, BufferPoolError> {
+        let found = result.len();
+        // leftmost leaf which may hold key, separators before it have smaller keys.
+        let leaf = self.read_leaf_by(|page| {
+            page.keys()[1..].partition_point(|it| self.comparator.compare(&it.key, &key).is_lt())
+        })?;
+        let Some((_, mut guard)) = leaf else {
+            return Ok(false);
+        };
+        loop {
+            let leaf = BPlusTreeLeafPage::<KeyType, ValueType, _>::new(guard.get_read_guard());
+            let from = leaf
+                .keys()
+                .partition_point(|it| self.comparator.compare(it, &key).is_lt());
+            let to = leaf
+                .keys()
+                .partition_point(|it| self.comparator.compare(it, &key).is_le());
+            result.extend_from_slice(&leaf.values()[from..to]);
+            let next_page_id = leaf.next_page_id();
+            if to < leaf.len() || next_page_id == INVALID_PAGE_ID {
+                break;
+            }
+            guard = self.bpm.read_page(next_page_id)?;
+        }
+        Ok(result.len() > found)
     }
 
-    pub fn remove(&mut self, key: impl Borrow<KeyType>) {}
+    /// Iterator over all entries, in key order. Reverse it for descending order.
+    pub fn iter(
+        &self,
+    ) -> Result<BPlusTreeIterator<'_, KeyType, ValueType, Comparator>, BufferPoolError> {
+        self.range(..)
+    }
+
+    /// Iterator over entries from `key` on, in key order. `key` itself needs not be in tree.
+    pub fn iter_from(
+        &self,
+        key: KeyType,
+    ) -> Result<BPlusTreeIterator<'_, KeyType, ValueType, Comparator>, BufferPoolError> {
+        self.range(key..)
+    }
+
+    /// Iterator over entries with keys in `range`, in key order. It is double ended, `next_back` walks leaves
+    /// backward from end of range, and `rev` gives a descending scan.
+    ///
+    /// Leaves are walked along their chain, entries are copied out a leaf at a time and no latch is kept between
+    /// calls. See `BPlusTreeIterator` for what it sees of changes made while it runs.
+    pub fn range(
+        &self,
+        range: impl RangeBounds<KeyType>,
+    ) -> Result<BPlusTreeIterator<'_, KeyType, ValueType, Comparator>, BufferPoolError> {
+        BPlusTreeIterator::new(
+            self,
+            range.start_bound().cloned(),
+            range.end_bound().cloned(),
+        )
+    }
+
+    /// Remove a key value pair, other values of key stay. Leaves and internal pages falling under half full
+    /// borrow from a sibling, or are merged into one. Pages merged away are deleted from buffer pool.
+    /// @return : false if pair is not in tree
+    ///
+    /// Latches are taken the same way as in `insert`, pages above are let go once a page can lose a child.
+    /// An error from buffer pool in the middle of rebalancing may leave pages under half full.
+    pub fn remove(
+        &self,
+        key: impl Borrow<KeyType>,
+        value: impl Borrow<ValueType>,
+    ) -> Result<bool, BufferPoolError> {
+        let (key, value) = (key.borrow(), value.borrow());
+        if let Some((mut guard, is_root)) = self.write_leaf_optimistic(key, value)? {
+            let mut leaf =
+                BPlusTreeLeafPage::<KeyType, ValueType, _>::new_mut(guard.get_write_guard());
+            match leaf.lookup(self.cmp_entry(key, value)) {
+                Err(_) => return Ok(false),
+                Ok(index) if self.leaf_can_lose_one(leaf.len(), is_root) => {
+                    leaf.remove(index);
+                    return Ok(true);
+                }
+                Ok(_) => {}
+            }
+        }
+        self.remove_pessimistic(key, value)
+    }
+
+    /// Builds tree bottom up out of `entries`, which must come in tree order, keys ascending and values of a
+    /// key ascending. Leaves are filled to `fill_factor` of what they hold before splitting, internal pages to
+    /// that share of `internal_max_size` children. Last two pages of a level are evened out if the last one
+    /// would fall under min size. Header page is written once, after every other page.
+    ///
+    /// Tree must be empty, `NotEmpty` otherwise. Header page is latched throughout, other operations wait for
+    /// bulk load to end. Fails with `UnsortedInput` on an entry out of order and `DuplicateInput` on one
+    /// repeating the entry before. Pages written by then are deleted and tree stays empty, failing to delete
+    /// one does not hide error which stopped bulk load. Panics unless `0 < fill_factor <= 1`.
+    pub fn bulk_load(
+        &self,
+        entries: impl IntoIterator<Item = (KeyType, ValueType)>,
+        fill_factor: f64,
+    ) -> Result<(), IndexError> {
+        assert!(
+            fill_factor > 0.0 && fill_factor <= 1.0,
+            "fill factor must be in (0, 1]"
+        );
+        let mut header_guard = self.bpm.write_page(self.header_page_id)?;
+        let header = *header_guard.get_readable_data_as::<BplusTreeHeaderPage>();
+        if header.root_page_id().is_some() {
+            return Err(IndexError::NotEmpty);
+        }
+
+        // first entry and page id of each page of level being built on, leaves at first.
+        let mut level = vec![];
+        let mut res = self.bulk_load_leaves(entries, fill_factor, &mut level);
+        // pages of levels below one being built on.
+        let mut written = vec![];
+        let mut height = 0;
+        while res.is_ok() && level.len() > 1 {
+            let mut parents = vec![];
+            res = self.bulk_load_internal(&level, fill_factor, &mut parents);
+            written.extend(level.iter().map(|(_, page_id)| *page_id));
+            level = parents;
+            height += 1;
+        }
+        if let Err(err) = res {
+            // header never pointed to pages written so far, nobody else can pin them. A page left behind by a
+            // failed delete only takes space, it is not reachable from tree.
+            for page_id in written.into_iter().chain(level.into_iter().map(|it| it.1)) {
+                let _ = self.free_page(page_id);
+            }
+            return Err(err);
+        }
+        let root_page_id = level.first().map(|(_, page_id)| *page_id);
+        set_root(&mut header_guard, root_page_id, height);
+        Ok(())
+    }
+
+    // Writes leaves out of `entries`, checking their order, and adds each to `level`.
+    fn bulk_load_leaves(
+        &self,
+        entries: impl IntoIterator<Item = (KeyType, ValueType)>,
+        fill_factor: f64,
+        level: &mut Vec<(Separator<KeyType, ValueType>, usize)>,
+    ) -> Result<(), IndexError> {
+        let max = self.leaf_max_size as usize - 1;
+        let min = self.leaf_min_size();
+        let fill = fill_size(max, fill_factor, min);
+        let mut last: Option<(KeyType, ValueType)> = None;
+        let entries = entries.into_iter().enumerate().map(|(i, (key, value))| {
+            if let Some((last_key, last_value)) = &last {
+                match self.compare_entries((last_key, last_value), (&key, &value)) {
+                    Ordering::Less => {}
+                    Ordering::Equal => return Err(IndexError::DuplicateInput { position: i }),
+                    Ordering::Greater => return Err(IndexError::UnsortedInput { position: i }),
+                }
+            }
+            last = Some((key, value));
+            Ok((key, value))
+        });
+
+        // page id of next leaf, handed out when the one before it is written.
+        let mut next_page_id = None;
+        bulk_load_runs(entries, fill, (min, max), |run, more| {
+            let page_id = next_page_id
+                .take()
+                .unwrap_or_else(|| self.bpm.new_page_id());
+            let mut guard = self.bpm.write_page(page_id)?;
+            let mut leaf = BPlusTreeLeafPage::init(
+                guard.get_write_guard(),
+                self.leaf_max_size,
+                INVALID_PAGE_ID,
+            );
+            for (index, (key, value)) in run.iter().enumerate() {
+                leaf.insert(index, *key, *value);
+            }
+            leaf.set_prev_page_id(level.last().map_or(INVALID_PAGE_ID, |it| it.1));
+            if more {
+                let id = self.bpm.new_page_id();
+                leaf.set_next_page_id(id);
+                next_page_id = Some(id);
+            }
+            level.push((separator(&run[0].0, &run[0].1), page_id));
+            Ok(())
+        })
+    }
+
+    // Writes internal pages over pages of `level`, and adds each to `parents`.
+    fn bulk_load_internal(
+        &self,
+        level: &[(Separator<KeyType, ValueType>, usize)],
+        fill_factor: f64,
+        parents: &mut Vec<(Separator<KeyType, ValueType>, usize)>,
+    ) -> Result<(), IndexError> {
+        let max = self.internal_max_size as usize;
+        let min = self.internal_min_size();
+        let fill = fill_size(max, fill_factor, min);
+        bulk_load_runs(level.iter().map(Ok), fill, (min, max), |run, _| {
+            let page_id = self.bpm.new_page_id();
+            let mut guard = self.bpm.write_page(page_id)?;
+            let mut page =
+                BPlusTreeInternalPage::init(guard.get_write_guard(), self.internal_max_size);
+            // first key is never looked at, first entry of first child fills its slot.
+            for (index, (separator, child)) in run.iter().enumerate() {
+                page.insert(index, *separator, *child);
+            }
+            parents.push((run[0].0, page_id));
+            Ok(())
+        })
+    }
+
+    // Goes down with read latches, each let go once the one below is taken, to leaf `pick` leads to. It gives
+    // index of child to take in an internal page. Page id comes along with leaf, None for an empty tree.
+    pub(super) fn read_leaf_by(
+        &self,
+        pick: impl Fn(&InternalPage<KeyType, ValueType, &FrameHeader>) -> usize,
+    ) -> Result<Option<(usize, ReadPageGuard)>, BufferPoolError> {
+        let header_guard = self.bpm.read_page(self.header_page_id)?;
+        let header = *header_guard.get_readable_data_as::<BplusTreeHeaderPage>();
+        let Some(mut page_id) = header.root_page_id() else {
+            return Ok(None);
+        };
+        let mut guard = self.bpm.read_page(page_id)?;
+        drop(header_guard);
+        for _ in 0..header.height() {
+            let page = InternalPage::<KeyType, ValueType, _>::new(guard.get_read_guard());
+            page_id = page.values()[pick(&page)];
+            guard = self.bpm.read_page(page_id)?;
+        }
+        Ok(Some((page_id, guard)))
+    }
+
+    // Goes down with read latches, each let go once the one below is taken, and write latches leaf which may
+    // hold pair of `key` and `value`. Tells whether leaf is root too. None for an empty tree.
+    fn write_leaf_optimistic(
+        &self,
+        key: &KeyType,
+        value: &ValueType,
+    ) -> Result<Option<(WritePageGuard, bool)>, BufferPoolError> {
+        let header_guard = self.bpm.read_page(self.header_page_id)?;
+        let header = *header_guard.get_readable_data_as::<BplusTreeHeaderPage>();
+        let Some(root_page_id) = header.root_page_id() else {
+            return Ok(None);
+        };
+        if header.height() == 0 {
+            return Ok(Some((self.bpm.write_page(root_page_id)?, true)));
+        }
+
+        let mut guard = self.bpm.read_page(root_page_id)?;
+        drop(header_guard);
+        for level in (1..=header.height()).rev() {
+            let page = InternalPage::<KeyType, ValueType, _>::new(guard.get_read_guard());
+            let child = page.values()[page.child_index(&separator(key, value), self.cmp())];
+            if level == 1 {
+                return Ok(Some((self.bpm.write_page(child)?, false)));
+            }
+            guard = self.bpm.read_page(child)?;
+        }
+        unreachable!("leaf is returned from last level")
+    }
+
+    fn insert_pessimistic(&self, key: KeyType, value: ValueType) -> Result<bool, BufferPoolError> {
+        let mut header_guard = Some(self.bpm.write_page(self.header_page_id)?);
+        let header = *header_guard
+            .as_ref()
+            .unwrap()
+            .get_readable_data_as::<BplusTreeHeaderPage>();
+        let Some(root_page_id) = header.root_page_id() else {
+            let page_id = self.bpm.new_page_id();
+            let mut guard = self.bpm.write_page(page_id)?;
+            BPlusTreeLeafPage::init(guard.get_write_guard(), self.leaf_max_size, INVALID_PAGE_ID)
+                .insert(0, key, value);
+            set_root(header_guard.as_mut().unwrap(), Some(page_id), 0);
+            return Ok(true);
+        };
+
+        let mut path = Path::new();
+        let mut page_id = root_page_id;
+        for _ in 0..header.height() {
+            let mut guard = self.bpm.write_page(page_id)?;
+            let page = InternalPage::<KeyType, ValueType, _>::new_mut(guard.get_write_guard());
+            let index = page.child_index(&separator(&key, &value), self.cmp());
+            let child = page.values()[index];
+            if page.len() < self.internal_max_size as usize {
+                // page takes another child without splitting, nothing above it changes.
+                header_guard = None;
+                path.clear();
+            }
+            path.push((page_id, index, guard));
+            page_id = child;
+        }
+
+        let mut guard = self.bpm.write_page(page_id)?;
+        let mut leaf = BPlusTreeLeafPage::new_mut(guard.get_write_guard());
+        let Err(index) = leaf.lookup(self.cmp_entry(&key, &value)) else {
+            return Ok(false);
+        };
+        // another thread may have made room since optimistic attempt.
+        if leaf.len() + 1 < self.leaf_max_size as usize {
+            leaf.insert(index, key, value);
+            return Ok(true);
+        }
+
+        // new page, and leaf after whose previous page id changes, are latched before leaf is touched. Failing
+        // to get either leaves tree as it was.
+        let right_page_id = self.bpm.new_page_id();
+        let mut right_guard = self.bpm.write_page(right_page_id)?;
+        let mut next_guard = self.write_next_leaf(leaf.next_page_id())?;
+        let mut right = BPlusTreeLeafPage::init(
+            right_guard.get_write_guard(),
+            self.leaf_max_size,
+            INVALID_PAGE_ID,
+        );
+        leaf.insert(index, key, value);
+        leaf.split_into(leaf.len() / 2, &mut right);
+        leaf.set_next_page_id(right_page_id);
+        right.set_prev_page_id(page_id);
+        if let Some(next_guard) = next_guard.as_mut() {
+            BPlusTreeLeafPage::<KeyType, ValueType, _>::new_mut(next_guard.get_write_guard())
+                .set_prev_page_id(right_page_id);
+        }
+        let separator = separator(&right.keys()[0], &right.values()[0]);
+        // parent is still latched, nobody gets to either half from above until it points to both.
+        drop((guard, right_guard, next_guard));
+        self.insert_into_parent(header_guard, path, page_id, separator, right_page_id)?;
+        Ok(true)
+    }
+
+    fn remove_pessimistic(
+        &self,
+        key: &KeyType,
+        value: &ValueType,
+    ) -> Result<bool, BufferPoolError> {
+        let mut header_guard = Some(self.bpm.write_page(self.header_page_id)?);
+        let header = *header_guard
+            .as_ref()
+            .unwrap()
+            .get_readable_data_as::<BplusTreeHeaderPage>();
+        let Some(root_page_id) = header.root_page_id() else {
+            return Ok(false);
+        };
+
+        let mut path = Path::new();
+        let mut page_id = root_page_id;
+        for level in 0..header.height() {
+            let mut guard = self.bpm.write_page(page_id)?;
+            let page = InternalPage::<KeyType, ValueType, _>::new_mut(guard.get_write_guard());
+            let index = page.child_index(&separator(key, value), self.cmp());
+            let child = page.values()[index];
+            // root can go down to two children, any other page to half full.
+            let min_size = if level == 0 {
+                2
+            } else {
+                self.internal_min_size()
+            };
+            if page.len() > min_size {
+                header_guard = None;
+                path.clear();
+            }
+            path.push((page_id, index, guard));
+            page_id = child;
+        }
+
+        let mut guard = self.bpm.write_page(page_id)?;
+        let mut leaf = BPlusTreeLeafPage::<KeyType, ValueType, _>::new_mut(guard.get_write_guard());
+        let Ok(index) = leaf.lookup(self.cmp_entry(key, value)) else {
+            return Ok(false);
+        };
+        let is_root = header.height() == 0;
+        let can_lose_one = self.leaf_can_lose_one(leaf.len(), is_root);
+        leaf.remove(index);
+        if can_lose_one {
+            return Ok(true);
+        }
+
+        if is_root {
+            set_root(header_guard.as_mut().unwrap(), None, 0);
+            drop(guard);
+            let _ = self.free_page(page_id)?;
+            return Ok(true);
+        }
+        self.rebalance_leaf(header_guard, path, page_id, guard)?;
+        Ok(true)
+    }
+
+    // Adds `right`, split off `left` at `key`, next to it in parent. Splits parents as long as they overflow,
+    // and grows a new root once the old one splits. Pages of `path` are all full, but for the first one.
+    fn insert_into_parent(
+        &self,
+        header_guard: Option<WritePageGuard>,
+        mut path: Path,
+        mut left: usize,
+        mut key: Separator<KeyType, ValueType>,
+        mut right: usize,
+    ) -> Result<(), BufferPoolError> {
+        loop {
+            let Some((parent_page_id, index, mut guard)) = path.pop() else {
+                let mut header_guard = header_guard.expect("header is latched while root is full");
+                let root_page_id = self.bpm.new_page_id();
+                let mut guard = self.bpm.write_page(root_page_id)?;
+                let mut root =
+                    BPlusTreeInternalPage::init(guard.get_write_guard(), self.internal_max_size);
+                // first key is never looked at, any key fills its slot.
+                root.insert(0, key, left);
+                root.insert(1, key, right);
+                let height = header_guard
+                    .get_readable_data_as::<BplusTreeHeaderPage>()
+                    .height();
+                set_root(&mut header_guard, Some(root_page_id), height + 1);
+                return Ok(());
+            };
+
+            let mut parent = BPlusTreeInternalPage::new_mut(guard.get_write_guard());
+            if parent.len() < self.internal_max_size as usize {
+                parent.insert(index + 1, key, right);
+                return Ok(());
+            }
+
+            let sibling_page_id = self.bpm.new_page_id();
+            let mut sibling_guard = self.bpm.write_page(sibling_page_id)?;
+            let mut sibling = BPlusTreeInternalPage::init(
+                sibling_guard.get_write_guard(),
+                self.internal_max_size,
+            );
+            parent.insert(index + 1, key, right);
+            parent.split_into(parent.len().div_ceil(2), &mut sibling);
+            (left, key, right) = (parent_page_id, sibling.keys()[0], sibling_page_id);
+        }
+    }
+
+    // Refills leaf at `page_id`, which just fell under half full, from a sibling under same parent, or merges
+    // the two when sibling has nothing to spare.
+    fn rebalance_leaf(
+        &self,
+        header_guard: Option<WritePageGuard>,
+        mut path: Path,
+        page_id: usize,
+        guard: WritePageGuard,
+    ) -> Result<(), BufferPoolError> {
+        let (parent_page_id, index, mut parent_guard) = path.pop().unwrap();
+        let mut parent =
+            InternalPage::<KeyType, ValueType, _>::new_mut(parent_guard.get_write_guard());
+        let (left_index, mut left_guard, mut right_guard) =
+            self.latch_siblings(&parent, index, guard)?;
+        let (left_page_id, right_page_id) =
+            (parent.values()[left_index], parent.values()[left_index + 1]);
+        let mut left =
+            BPlusTreeLeafPage::<KeyType, ValueType, _>::new_mut(left_guard.get_write_guard());
+        let mut right = BPlusTreeLeafPage::new_mut(right_guard.get_write_guard());
+
+        let min_size = self.leaf_min_size();
+        if left.len() > min_size && right.len() < min_size {
+            let (key, value) = left.remove(left.len() - 1);
+            right.insert(0, key, value);
+        } else if right.len() > min_size && left.len() < min_size {
+            let (key, value) = right.remove(0);
+            left.insert(left.len(), key, value);
+        } else {
+            let mut next_guard = self.write_next_leaf(right.next_page_id())?;
+            left.append(&mut right);
+            if let Some(next_guard) = next_guard.as_mut() {
+                BPlusTreeLeafPage::<KeyType, ValueType, _>::new_mut(next_guard.get_write_guard())
+                    .set_prev_page_id(left_page_id);
+            }
+            parent.remove(left_index + 1);
+            drop((left_guard, right_guard, next_guard));
+            let _ = self.free_page(right_page_id)?;
+            return self.rebalance_internal(header_guard, path, parent_page_id, parent_guard);
+        }
+        parent.set_key(
+            left_index + 1,
+            separator(&right.keys()[0], &right.values()[0]),
+        );
+        Ok(())
+    }
+
+    // Page at `page_id` just lost a child. Refills it from a sibling or merges it into one if it fell under
+    // half full, as long as that leaves its parent under half full too. A root left with a single child is
+    // dropped, that child becomes root.
+    fn rebalance_internal(
+        &self,
+        mut header_guard: Option<WritePageGuard>,
+        mut path: Path,
+        mut page_id: usize,
+        mut guard: WritePageGuard,
+    ) -> Result<(), BufferPoolError> {
+        loop {
+            let page = InternalPage::<KeyType, ValueType, _>::new_mut(guard.get_write_guard());
+            let Some((parent_page_id, index, mut parent_guard)) = path.pop() else {
+                // first page of path is root if header is still latched, otherwise it could lose a child.
+                let Some(header_guard) = header_guard.as_mut() else {
+                    return Ok(());
+                };
+                if page.len() > 1 {
+                    return Ok(());
+                }
+                let height = header_guard
+                    .get_readable_data_as::<BplusTreeHeaderPage>()
+                    .height();
+                set_root(header_guard, Some(page.values()[0]), height - 1);
+                drop(guard);
+                let _ = self.free_page(page_id)?;
+                return Ok(());
+            };
+            let min_size = self.internal_min_size();
+            if page.len() >= min_size {
+                return Ok(());
+            }
+
+            let mut parent =
+                InternalPage::<KeyType, ValueType, _>::new_mut(parent_guard.get_write_guard());
+            let (left_index, mut left_guard, mut right_guard) =
+                self.latch_siblings(&parent, index, guard)?;
+            let (left_page_id, right_page_id) =
+                (parent.values()[left_index], parent.values()[left_index + 1]);
+            let mut left =
+                InternalPage::<KeyType, ValueType, _>::new_mut(left_guard.get_write_guard());
+            let mut right = BPlusTreeInternalPage::new_mut(right_guard.get_write_guard());
+            // key in parent separating the two, it moves down into whichever page takes the child after it.
+            let separator = parent.keys()[left_index + 1];
+
+            if left.len() > min_size && right.len() < min_size {
+                let (key, child) = left.remove(left.len() - 1);
+                right.insert(0, key, child);
+                right.set_key(1, separator);
+                parent.set_key(left_index + 1, key);
+            } else if right.len() > min_size && left.len() < min_size {
+                let (_, child) = right.remove(0);
+                left.insert(left.len(), separator, child);
+                // first key of right is no longer valid, it is the one separating the two now.
+                parent.set_key(left_index + 1, right.keys()[0]);
+            } else {
+                right.set_key(0, separator);
+                left.append(&mut right);
+                parent.remove(left_index + 1);
+                drop((left_guard, right_guard));
+                let _ = self.free_page(right_page_id)?;
+                (page_id, guard) = (parent_page_id, parent_guard);
+                continue;
+            }
+            return Ok(());
+        }
+    }
+
+    // Write latches child at `index` of `parent` along with a sibling, left one preferred, and returns index
+    // of the left one of the two. `guard` is latch already held on child.
+    //
+    // Siblings are always latched left to right. Child is let go first if it is the right one, nothing gets to
+    // it from above meanwhile, parent is latched.
+    fn latch_siblings(
+        &self,
+        parent: &InternalPage<KeyType, ValueType, &mut FrameHeader>,
+        index: usize,
+        guard: WritePageGuard,
+    ) -> Result<(usize, WritePageGuard, WritePageGuard), BufferPoolError> {
+        if index == 0 {
+            let right_guard = self.bpm.write_page(parent.values()[1])?;
+            return Ok((0, guard, right_guard));
+        }
+        drop(guard);
+        let left_guard = self.bpm.write_page(parent.values()[index - 1])?;
+        let right_guard = self.bpm.write_page(parent.values()[index])?;
+        Ok((index - 1, left_guard, right_guard))
+    }
+
+    // Write latches leaf at `next_page_id`, whose previous page id is about to change, if there is one. It is
+    // to the right of every leaf latched already, which keeps leaves latched left to right.
+    fn write_next_leaf(
+        &self,
+        next_page_id: usize,
+    ) -> Result<Option<WritePageGuard>, BufferPoolError> {
+        if next_page_id == INVALID_PAGE_ID {
+            return Ok(None);
+        }
+        Ok(Some(self.bpm.write_page(next_page_id)?))
+    }
+
+    // Order of separators, in the form internal pages take it.
+    fn cmp(
+        &self,
+    ) -> impl Fn(&Separator<KeyType, ValueType>, &Separator<KeyType, ValueType>) -> Ordering + '_
+    {
+        |lhs, rhs| self.compare_entries((&lhs.key, &lhs.value), (&rhs.key, &rhs.value))
+    }
+
+    // Order of leaf entries against pair of `key` and `value`, in the form leaves take it.
+    fn cmp_entry<'a>(
+        &'a self,
+        key: &'a KeyType,
+        value: &'a ValueType,
+    ) -> impl Fn(&KeyType, &ValueType) -> Ordering + 'a {
+        move |k, v| self.compare_entries((k, v), (key, value))
+    }
+
+    pub(super) fn compare_entries(
+        &self,
+        lhs: (&KeyType, &ValueType),
+        rhs: (&KeyType, &ValueType),
+    ) -> Ordering {
+        self.comparator
+            .compare(lhs.0, rhs.0)
+            .then_with(|| lhs.1.cmp(rhs.1))
+    }
+
+    // leaves split into halves of `leaf_max_size / 2` and up, root leaf goes down to a single key.
+    fn leaf_can_lose_one(&self, len: usize, is_root: bool) -> bool {
+        if is_root {
+            len > 1
+        } else {
+            len > self.leaf_min_size()
+        }
+    }
+
+    fn leaf_min_size(&self) -> usize {
+        self.leaf_max_size as usize / 2
+    }
+
+    // internal pages split `internal_max_size + 1` children into halves of this many and up.
+    fn internal_min_size(&self) -> usize {
+        (self.internal_max_size as usize).div_ceil(2)
+    }
+
+    // Deletes a page no longer reachable from tree. False if someone outside tree, an iterator stepping over
+    // leaves for instance, still has it pinned. Page is left in buffer pool then, taking space but harmless.
+    #[must_use = "a page left pinned is not deleted"]
+    fn free_page(&self, page_id: usize) -> Result<bool, BufferPoolError> {
+        self.bpm.delete_page(page_id)
+    }
+}
+
+fn separator<KeyType: Copy, ValueType: Copy>(
+    key: &KeyType,
+    value: &ValueType,
+) -> Separator<KeyType, ValueType> {
+    Separator {
+        key: *key,
+        value: *value,
+    }
+}
+
+// Number of entries or children bulk load puts in a page, `fill_factor` of `max` but no less than `min`.
+fn fill_size(max: usize, fill_factor: f64, min: usize) -> usize {
+    ((max as f64 * fill_factor).ceil() as usize).clamp(min.max(1), max)
+}
+
+// Cuts `items` into runs of `fill` and hands them to `write` in order, telling whether another run follows.
+// A run is kept back until the next one starts, so that a last run shorter than `min` is evened out with the
+// one before, or joins it if both fit in `max`.
+fn bulk_load_runs<T>(
+    items: impl Iterator<Item = Result<T, IndexError>>,
+    fill: usize,
+    (min, max): (usize, usize),
+    mut write: impl FnMut(Vec<T>, bool) -> Result<(), IndexError>,
+) -> Result<(), IndexError> {
+    let (mut held, mut run) = (None::<Vec<T>>, vec![]);
+    for item in items {
+        if run.len() == fill {
+            if let Some(held) = held.take() {
+                write(held, true)?;
+            }
+            held = Some(std::mem::take(&mut run));
+        }
+        run.push(item?);
+    }
+    let Some(mut held) = held else {
+        if !run.is_empty() {
+            write(run, false)?;
+        }
+        return Ok(());
+    };
+    if run.len() < min {
+        if held.len() + run.len() <= max {
+            held.append(&mut run);
+        } else {
+            let at = (held.len() + run.len()) / 2;
+            run.splice(0..0, held.drain(at..));
+        }
+    }
+    let more = !run.is_empty();
+    write(held, more)?;
+    if more {
+        write(run, false)?;
+    }
+    Ok(())
+}
+
+fn set_root(header_guard: &mut WritePageGuard, root_page_id: Option<usize>, height: u32) {
+    header_guard
+        .get_writeable_data_as::<BplusTreeHeaderPage>()
+        .set_root(root_page_id, height);
 }
 
 #[cfg(test)]
+#[allow(clippy::bool_assert_comparison)]
 mod test {
-    use std::{marker::PhantomData, sync::Arc};
+    use std::{
+        collections::BTreeSet,
+        env, io, process,
+        sync::{Arc, Mutex},
+        thread,
+    };
 
     use catalog::parse_create_stmt;
     use common::RID;
-    use storage::MemoryManager;
+    use storage::{
+        BPlusTreeLeafPage, BplusTreeHeaderPage, FrameHeader, MemoryManager, PageOperator,
+        INVALID_PAGE_ID, PAGE_SIZE,
+    };
+
+    use crate::{
+        index::{key_comparator::GenericComparator, GenericKey},
+        BufferPoolError, BufferPoolManager, IndexError,
+    };
+
+    use super::{BPlusTree, InternalPage};
+
+    type Tree = BPlusTree<GenericKey<8>, RID, GenericComparator<8>>;
+    type Leaf<'a> = BPlusTreeLeafPage<GenericKey<8>, RID, &'a FrameHeader>;
+    type Internal<'a> = InternalPage<GenericKey<8>, RID, &'a FrameHeader>;
+
+    // Outlives a buffer pool, as a disk file would.
+    #[derive(Clone)]
+    struct SharedMemory(Arc<Mutex<MemoryManager>>);
+
+    impl PageOperator for SharedMemory {
+        fn write_page(&mut self, page_id: usize, data: &[u8; PAGE_SIZE]) -> io::Result<()> {
+            self.0.lock().unwrap().write_page(page_id, data)
+        }
 
-    use crate::{index::GenericKey, BufferPoolManager};
+        fn read_page(&mut self, page_id: usize, data: &mut [u8; PAGE_SIZE]) -> io::Result<()> {
+            self.0.lock().unwrap().read_page(page_id, data)
+        }
+    }
+
+    fn comparator() -> GenericComparator<8> {
+        GenericComparator::new(&parse_create_stmt("a bigint"))
+    }
 
-    use super::BPlusTree;
+    // Keys are a single bigint column, see `GenericKey::from`.
+    fn key_value(key: &GenericKey<8>) -> i64 {
+        i64::from_le_bytes(key.data)
+    }
+
+    // Root page id and height, as header page holds them.
+    fn root(tree: &Tree) -> (Option<usize>, u32) {
+        let guard = tree.bpm.read_page(tree.header_page_id).unwrap();
+        let header = guard.get_readable_data_as::<BplusTreeHeaderPage>();
+        (header.root_page_id(), header.height())
+    }
+
+    // Walks whole tree checking it is a valid B+ tree. Returns entries in leaf chain order, and pages of tree.
+    fn check_tree(tree: &Tree) -> (Vec<(GenericKey<8>, RID)>, Vec<usize>) {
+        let (Some(root_page_id), height) = root(tree) else {
+            return (vec![], vec![]);
+        };
+        let (mut pages, mut leaves) = (vec![], vec![]);
+        check_subtree(
+            tree,
+            root_page_id,
+            height,
+            (None, None),
+            &mut pages,
+            &mut leaves,
+        );
+
+        let mut entries = vec![];
+        for (i, &page_id) in leaves.iter().enumerate() {
+            let guard = tree.bpm.read_page(page_id).unwrap();
+            let leaf = Leaf::new(guard.get_read_guard());
+            let next_page_id = leaves.get(i + 1).copied().unwrap_or(INVALID_PAGE_ID);
+            assert_eq!(next_page_id, leaf.next_page_id(), "leaf {page_id}");
+            let prev_page_id = i.checked_sub(1).map_or(INVALID_PAGE_ID, |i| leaves[i]);
+            assert_eq!(prev_page_id, leaf.prev_page_id(), "leaf {page_id}");
+            entries.extend(
+                leaf.keys()
+                    .iter()
+                    .copied()
+                    .zip(leaf.values().iter().copied()),
+            );
+        }
+        assert!(entries
+            .windows(2)
+            .all(|it| order(&it[0].0, &it[0].1) < order(&it[1].0, &it[1].1)));
+        (entries, pages)
+    }
+
+    // Entry as something ordered the way tree orders entries.
+    type Order = (i64, RID);
+
+    fn order(key: &GenericKey<8>, rid: &RID) -> Order {
+        (key_value(key), *rid)
+    }
+
+    // Entries in subtree must fall in `bounds`, lower bound included.
+    fn check_subtree(
+        tree: &Tree,
+        page_id: usize,
+        level: u32,
+        bounds: (Option<Order>, Option<Order>),
+        pages: &mut Vec<usize>,
+        leaves: &mut Vec<usize>,
+    ) {
+        let is_root = pages.is_empty();
+        pages.push(page_id);
+        let in_bounds = |entry: Order| {
+            bounds.0.is_none_or(|lower| lower <= entry)
+                && bounds.1.is_none_or(|upper| entry < upper)
+        };
+        if level == 0 {
+            let guard = tree.bpm.read_page(page_id).unwrap();
+            let leaf = Leaf::new(guard.get_read_guard());
+            let min_size = if is_root { 1 } else { tree.leaf_min_size() };
+            assert!(leaf.len() >= min_size, "leaf {page_id} underflows");
+            assert!(
+                leaf.len() < tree.leaf_max_size as usize,
+                "leaf {page_id} overflows"
+            );
+            assert!(
+                (0..leaf.len()).all(|i| in_bounds(order(&leaf.keys()[i], &leaf.values()[i]))),
+                "leaf {page_id} out of bounds"
+            );
+            leaves.push(page_id);
+            return;
+        }
+
+        let guard = tree.bpm.read_page(page_id).unwrap();
+        let page = Internal::new(guard.get_read_guard());
+        let min_size = if is_root { 2 } else { tree.internal_min_size() };
+        assert!(page.len() >= min_size, "page {page_id} underflows");
+        assert!(
+            page.len() <= tree.internal_max_size as usize,
+            "page {page_id} overflows"
+        );
+        let separators = page.keys()[1..]
+            .iter()
+            .map(|it| order(&it.key, &it.value))
+            .collect::<Vec<_>>();
+        let children = page.values().to_vec();
+        drop(guard);
+        assert!(
+            separators.iter().all(|&it| in_bounds(it)),
+            "page {page_id} out of bounds"
+        );
+        assert!(separators.windows(2).all(|it| it[0] < it[1]));
+        for (i, child) in children.into_iter().enumerate() {
+            let lower = if i == 0 {
+                bounds.0
+            } else {
+                Some(separators[i - 1])
+            };
+            let upper = separators.get(i).copied().or(bounds.1);
+            check_subtree(tree, child, level - 1, (lower, upper), pages, leaves);
+        }
+    }
 
     #[test]
     fn insert_test_1() {
@@ -89,23 +1048,29 @@ mod test {
         let bpm = Arc::new(BufferPoolManager::new(50, 10, Box::new(disk_manager)));
 
         let page_id = bpm.new_page_id();
-        let mut tree = BPlusTree::<GenericKey<8>, RID, PhantomData<u32>>::new(
+        let tree = Tree::new(
             "foo_pk".into(),
             page_id,
             bpm.clone(),
-            PhantomData,
+            GenericComparator::new(&key_schema),
             Some(2),
             Some(3),
-        );
+        )
+        .unwrap();
 
         let key = 42;
         let value = key & 0xFFFFFFFF;
         let rid = RID::new(key, value as u32);
         let index_key: GenericKey<8> = key.into();
-        tree.insert(index_key, rid);
+        assert_eq!(true, tree.insert(index_key, rid).unwrap());
 
-        let root_page_id = tree.header_page_id;
+        let header_guard = bpm.read_page(tree.header_page_id).unwrap();
+        let header = header_guard.get_readable_data_as::<BplusTreeHeaderPage>();
+        let root_page_id = header.root_page_id().unwrap();
+        assert_eq!(0, header.height());
         let root_page_guard = bpm.read_page(root_page_id).unwrap();
+        let root = Leaf::new(root_page_guard.get_read_guard());
+        assert_eq!(&[index_key], root.keys());
     }
 
     #[test]
@@ -115,30 +1080,551 @@ mod test {
         let bpm = Arc::new(BufferPoolManager::new(50, 10, Box::new(disk_manager)));
 
         let page_id = bpm.new_page_id();
-        let mut tree = BPlusTree::<GenericKey<8>, RID, PhantomData<u32>>::new(
+        let tree = Tree::new(
             "foo_pk".into(),
             page_id,
             bpm.clone(),
-            PhantomData,
+            GenericComparator::new(&key_schema),
             Some(2),
             Some(3),
-        );
+        )
+        .unwrap();
 
         for key in [1, 2, 3, 4, 5] {
             let slot_num = key & 0xFFFFFFFF;
-            tree.insert(key.into(), RID::new(key >> 32, slot_num as u32));
+            tree.insert(key.into(), RID::new(key >> 32, slot_num as u32))
+                .unwrap();
         }
 
         for key in [1, 2, 3, 4, 5] {
             let mut rids = Vec::new();
             let index_key: GenericKey<8> = key.into();
-            let is_present = tree.get_value(index_key, &mut rids);
+            let is_present = tree.get_value(index_key, &mut rids).unwrap();
+
+            assert_eq!(true, is_present);
+            assert_eq!(1, rids.len());
+            assert_eq!(0, rids[0].page_id);
+            let slot_num = key & 0xFFFFFFFF;
+            assert_eq!(slot_num as u32, rids[0].slot_num);
+        }
+    }
+
+    #[test]
+    fn insert_many_test() {
+        for (leaf_max_size, internal_max_size) in
+            [(Some(2), Some(3)), (Some(5), Some(4)), (None, None)]
+        {
+            let disk_manager = MemoryManager::new(10000);
+            let bpm = Arc::new(BufferPoolManager::new(50, 10, Box::new(disk_manager)));
+            let tree = Tree::new(
+                "foo_pk".into(),
+                bpm.new_page_id(),
+                bpm.clone(),
+                comparator(),
+                leaf_max_size,
+                internal_max_size,
+            )
+            .unwrap();
+            assert_eq!(true, tree.is_empty().unwrap());
+
+            // keys go in scrambled, every other key is left out.
+            let keys = (0..3000)
+                .map(|i| (i * 7919) % 3000 * 2)
+                .collect::<Vec<usize>>();
+            for &key in &keys {
+                assert_eq!(
+                    true,
+                    tree.insert(key.into(), RID::new(key, key as u32)).unwrap()
+                );
+            }
+            let rid = RID::new(keys[0], keys[0] as u32);
+            assert_eq!(false, tree.insert(keys[0].into(), rid).unwrap());
+            assert_eq!(false, tree.is_empty().unwrap());
+            assert_eq!(true, root(&tree).1 >= 1);
+
+            for key in 0..6000 {
+                let mut rids = Vec::new();
+                let is_present = tree.get_value(key.into(), &mut rids).unwrap();
+                assert_eq!(key % 2 == 0, is_present, "key {key}");
+                if is_present {
+                    assert_eq!(vec![RID::new(key, key as u32)], rids);
+                }
+            }
+            assert_eq!(0, bpm.stats().pinned_frames);
+        }
+    }
+
+    #[test]
+    fn remove_test() {
+        let disk_manager = MemoryManager::new(1000);
+        let bpm = Arc::new(BufferPoolManager::new(50, 10, Box::new(disk_manager)));
+        let tree = Tree::new(
+            "foo_pk".into(),
+            bpm.new_page_id(),
+            bpm.clone(),
+            comparator(),
+            Some(3),
+            Some(3),
+        )
+        .unwrap();
+        for key in 0..20 {
+            tree.insert(key.into(), RID::new(key, 0)).unwrap();
+        }
+        let (_, pages) = check_tree(&tree);
+        assert_eq!(
+            false,
+            tree.remove(GenericKey::from(100), RID::new(100, 0))
+                .unwrap()
+        );
+        // key is there, but not with this value.
+        assert_eq!(
+            false,
+            tree.remove(GenericKey::from(5), RID::new(5, 1)).unwrap()
+        );
+
+        for key in 0..20 {
+            let rid = RID::new(key, 0);
+            assert_eq!(true, tree.remove(GenericKey::from(key), rid).unwrap());
+            assert_eq!(false, tree.remove(GenericKey::from(key), rid).unwrap());
+            let (entries, _) = check_tree(&tree);
+            let expected = (key + 1..20)
+                .map(|key| (GenericKey::from(key), RID::new(key, 0)))
+                .collect::<Vec<_>>();
+            assert_eq!(expected, entries);
+        }
+        assert_eq!(true, tree.is_empty().unwrap());
+        assert_eq!(0, root(&tree).1);
+        // every page tree ever used went back to buffer pool.
+        for page_id in pages {
+            let res = bpm.read_page(page_id);
+            assert_eq!(true, matches!(res, Err(BufferPoolError::PageDeleted(_))));
+        }
+    }
+
+    #[test]
+    fn duplicate_keys_test() {
+        let disk_manager = MemoryManager::new(1000);
+        let bpm = Arc::new(BufferPoolManager::new(50, 10, Box::new(disk_manager)));
+        let tree = Tree::new(
+            "foo_idx".into(),
+            bpm.new_page_id(),
+            bpm.clone(),
+            comparator(),
+            Some(3),
+            Some(3),
+        )
+        .unwrap();
+        // values of key 7 go in scrambled and end up spread over many leaves, between its neighbours.
+        for key in 0..20 {
+            tree.insert(key.into(), RID::new(key, 0)).unwrap();
+        }
+        for slot in (0..30).map(|i| (i * 17) % 30 + 1) {
+            assert_eq!(true, tree.insert(7.into(), RID::new(7, slot)).unwrap());
+        }
+        assert_eq!(false, tree.insert(7.into(), RID::new(7, 5)).unwrap());
+        assert_eq!(50, check_tree(&tree).0.len());
+
+        let mut rids = vec![];
+        assert_eq!(true, tree.get_value(7.into(), &mut rids).unwrap());
+        assert_eq!(
+            (0..=30).map(|slot| RID::new(7, slot)).collect::<Vec<_>>(),
+            rids
+        );
+        rids.clear();
+        assert_eq!(true, tree.get_value(8.into(), &mut rids).unwrap());
+        assert_eq!(vec![RID::new(8, 0)], rids);
+
+        // only the pair asked for goes, other values of key stay.
+        for slot in (0..=30).step_by(2) {
+            assert_eq!(
+                true,
+                tree.remove(GenericKey::from(7), RID::new(7, slot)).unwrap()
+            );
+            assert_eq!(
+                false,
+                tree.remove(GenericKey::from(7), RID::new(7, slot)).unwrap()
+            );
+            check_tree(&tree);
+        }
+        rids.clear();
+        assert_eq!(true, tree.get_value(7.into(), &mut rids).unwrap());
+        let odd = (1..=30).step_by(2).map(|slot| RID::new(7, slot));
+        assert_eq!(odd.collect::<Vec<_>>(), rids);
+        for slot in (1..=30).step_by(2) {
+            assert_eq!(
+                true,
+                tree.remove(GenericKey::from(7), RID::new(7, slot)).unwrap()
+            );
+        }
+        rids.clear();
+        assert_eq!(false, tree.get_value(7.into(), &mut rids).unwrap());
+        assert_eq!(true, rids.is_empty());
+        assert_eq!(19, check_tree(&tree).0.len());
+        assert_eq!(0, bpm.stats().pinned_frames);
+    }
+
+    #[test]
+    fn bulk_load_test() {
+        let disk_manager = MemoryManager::new(1000);
+        let bpm = Arc::new(BufferPoolManager::new(50, 10, Box::new(disk_manager)));
+        // leaves hold up to 4 entries, no less than 2 however low fill factor goes.
+        for (fill_factor, leaves) in [(1.0, 50), (0.5, 100), (0.7, 67), (0.01, 100)] {
+            let tree = Tree::new(
+                "foo_pk".into(),
+                bpm.new_page_id(),
+                bpm.clone(),
+                comparator(),
+                Some(5),
+                Some(4),
+            )
+            .unwrap();
+            let entries = (0..200).map(|key| (GenericKey::from(key), RID::new(key, 0)));
+            tree.bulk_load(entries.clone(), fill_factor).unwrap();
+            let (loaded, _) = check_tree(&tree);
+            assert_eq!(entries.collect::<Vec<_>>(), loaded);
+            assert_eq!(0, bpm.stats().pinned_frames);
+
+            let (mut count, mut page_id) = (0, tree.read_leaf_by(|_| 0).unwrap().unwrap().0);
+            while page_id != INVALID_PAGE_ID {
+                let guard = bpm.read_page(page_id).unwrap();
+                page_id = Leaf::new(guard.get_read_guard()).next_page_id();
+                count += 1;
+            }
+            assert_eq!(leaves, count, "fill factor {fill_factor}");
+            let mut rids = vec![];
+            assert_eq!(true, tree.get_value(123.into(), &mut rids).unwrap());
+            assert_eq!(vec![RID::new(123, 0)], rids);
+
+            // tree loaded is like any other to insert to and remove from.
+            for key in (0..200).step_by(3) {
+                assert_eq!(
+                    true,
+                    tree.remove(GenericKey::from(key), RID::new(key, 0))
+                        .unwrap()
+                );
+                assert_eq!(true, tree.insert(key.into(), RID::new(key, 1)).unwrap());
+            }
+            assert_eq!(200, check_tree(&tree).0.len());
+        }
+    }
+
+    #[test]
+    fn bulk_load_invalid_input_test() {
+        let disk_manager = MemoryManager::new(1000);
+        let bpm = Arc::new(BufferPoolManager::new(50, 10, Box::new(disk_manager)));
+        let tree = Tree::new(
+            "foo_idx".into(),
+            bpm.new_page_id(),
+            bpm.clone(),
+            comparator(),
+            Some(3),
+            Some(3),
+        )
+        .unwrap();
+        let entry = |key: i64, slot| (GenericKey::from(key as usize), RID::new(key as _, slot));
+        let unsorted = (0..50).map(|key| entry(key, 0)).chain([entry(10, 0)]);
+        let repeated = (0..50).map(|key| entry(key, 0)).chain([entry(49, 0)]);
+        let values_unsorted = (0..50)
+            .map(|key| entry(key, 0))
+            .chain([entry(49, 2), entry(49, 1)]);
+        for (entries, expected) in [
+            (unsorted.collect::<Vec<_>>(), ("unsorted", 50)),
+            (repeated.collect(), ("duplicate", 50)),
+            (values_unsorted.collect(), ("unsorted", 51)),
+        ] {
+            let found = match tree.bulk_load(entries, 1.0) {
+                Err(IndexError::UnsortedInput { position }) => ("unsorted", position),
+                Err(IndexError::DuplicateInput { position }) => ("duplicate", position),
+                res => panic!("bulk load of bad input gave {res:?}"),
+            };
+            assert_eq!(expected, found);
+            assert_eq!(true, tree.is_empty().unwrap());
+            assert_eq!(0, bpm.stats().pinned_frames);
+        }
+
+        tree.bulk_load([], 1.0).unwrap();
+        assert_eq!(true, tree.is_empty().unwrap());
+        // values of a key only need to go in ascending.
+        let duplicates = (0..5).map(|slot| entry(7, slot));
+        tree.bulk_load(duplicates, 1.0).unwrap();
+        let mut rids = vec![];
+        assert_eq!(true, tree.get_value(7.into(), &mut rids).unwrap());
+        assert_eq!(5, rids.len());
+        assert_eq!(5, check_tree(&tree).0.len());
+
+        let res = tree.bulk_load([entry(8, 0)], 1.0);
+        assert_eq!(true, matches!(res, Err(IndexError::NotEmpty)));
+    }
+
+    #[test]
+    fn random_insert_remove_test() {
+        // xorshift, enough to shuffle operations and keeps test reproducible.
+        let mut state = 0x2545_f491_4f6c_dd1du64;
+        let mut next = move |n: usize| {
+            state ^= state << 13;
+            state ^= state >> 7;
+            state ^= state << 17;
+            (state % n as u64) as usize
+        };
 
-            // assert_eq!(true, is_present);
-            // assert_eq!(1, rids.len());
-            // assert_eq!(0, rids[0].page_id);
-            // let slot_num = key & 0xFFFFFFFF;
-            // assert_eq!(slot_num as u32, rids[0].slot_num);
+        for (leaf_max_size, internal_max_size) in [(2, 3), (3, 4), (5, 5)] {
+            // whole tree stays resident, it is walked after every operation.
+            let disk_manager = MemoryManager::new(10000);
+            let bpm = Arc::new(BufferPoolManager::new(1000, 10, Box::new(disk_manager)));
+            let tree = Tree::new(
+                "foo_pk".into(),
+                bpm.new_page_id(),
+                bpm.clone(),
+                comparator(),
+                Some(leaf_max_size),
+                Some(internal_max_size),
+            )
+            .unwrap();
+            let mut model = BTreeSet::new();
+            let mut used_pages = vec![];
+
+            for _ in 0..2000 {
+                let key = next(300);
+                // few values per key, so keys keep showing up more than once.
+                let rid = RID::new(key, next(3) as u32);
+                // inserts win a little, so tree grows while it churns.
+                if next(9) < 5 {
+                    let inserted = tree.insert(key.into(), rid).unwrap();
+                    assert_eq!(model.insert((key, rid)), inserted);
+                } else {
+                    let removed = tree.remove(GenericKey::from(key), rid).unwrap();
+                    assert_eq!(model.remove(&(key, rid)), removed);
+                }
+
+                let (entries, pages) = check_tree(&tree);
+                let expected = model
+                    .iter()
+                    .map(|&(key, rid)| (GenericKey::from(key), rid))
+                    .collect::<Vec<_>>();
+                assert_eq!(expected, entries);
+                used_pages.extend(pages);
+            }
+
+            for key in 0..300 {
+                let mut rids = vec![];
+                let found = tree.get_value(key.into(), &mut rids).unwrap();
+                let expected = model
+                    .range((key, RID::new(0, 0))..(key + 1, RID::new(0, 0)))
+                    .map(|it| it.1)
+                    .collect::<Vec<_>>();
+                assert_eq!(!expected.is_empty(), found);
+                assert_eq!(expected, rids);
+            }
+            for (key, rid) in model {
+                assert_eq!(true, tree.remove(GenericKey::from(key), rid).unwrap());
+                check_tree(&tree);
+            }
+            assert_eq!(true, tree.is_empty().unwrap());
+            used_pages.sort_unstable();
+            used_pages.dedup();
+            for page_id in used_pages {
+                let res = bpm.read_page(page_id);
+                assert_eq!(true, matches!(res, Err(BufferPoolError::PageDeleted(_))));
+            }
+            assert_eq!(0, bpm.stats().pinned_frames);
+        }
+    }
+
+    #[test]
+    fn concurrent_insert_remove_test() {
+        fn assert_sync<T: Sync>() {}
+        assert_sync::<Tree>();
+
+        const THREADS: usize = 4;
+        const KEYS_PER_THREAD: usize = 1000;
+        // odd keys of a thread, in an order which splits and merges all over tree.
+        let keys_of = |t: usize| {
+            (0..KEYS_PER_THREAD)
+                .map(|i| (i * 7919) % KEYS_PER_THREAD)
+                .filter(|i| i % 2 == 1)
+                .map(|i| t * KEYS_PER_THREAD + i)
+                .collect::<Vec<_>>()
+        };
+        // scans run into leaves other threads change meanwhile, keys there all along must show up in order.
+        let check_scans = |tree: &Tree, t: usize| {
+            let (lo, hi) = (t * KEYS_PER_THREAD, (t + 1) * KEYS_PER_THREAD);
+            let range = GenericKey::from(lo)..GenericKey::from(hi);
+            let ascending = tree.range(range.clone()).unwrap();
+            let mut descending = tree
+                .range(range)
+                .unwrap()
+                .rev()
+                .map(|entry| entry.unwrap().0)
+                .collect::<Vec<_>>();
+            descending.reverse();
+            for scanned in [
+                ascending.map(|entry| entry.unwrap().0).collect(),
+                descending,
+            ] {
+                assert_eq!(
+                    true,
+                    scanned
+                        .windows(2)
+                        .all(|it: &[GenericKey<8>]| key_value(&it[0]) < key_value(&it[1]))
+                );
+                for key in (lo..hi).step_by(2) {
+                    assert_eq!(
+                        true,
+                        scanned
+                            .binary_search_by_key(&(key as i64), key_value)
+                            .is_ok(),
+                        "{key}"
+                    );
+                }
+            }
+        };
+        for (leaf_max_size, internal_max_size) in [(2, 3), (4, 4)] {
+            let disk_manager = MemoryManager::new(100000);
+            let bpm = Arc::new(BufferPoolManager::new(200, 10, Box::new(disk_manager)));
+            let tree = Tree::new(
+                "foo_pk".into(),
+                bpm.new_page_id(),
+                bpm.clone(),
+                comparator(),
+                Some(leaf_max_size),
+                Some(internal_max_size),
+            )
+            .unwrap();
+            // every other key is there from start to end, readers check they never go missing.
+            let total = THREADS * KEYS_PER_THREAD;
+            for key in (0..total).step_by(2) {
+                tree.insert(key.into(), RID::new(key, 0)).unwrap();
+            }
+
+            thread::scope(|s| {
+                for t in 0..THREADS {
+                    let tree = &tree;
+                    s.spawn(move || {
+                        let mine = keys_of(t);
+                        for (i, &key) in mine.iter().enumerate() {
+                            if i % 100 == 0 {
+                                check_scans(tree, (t + 1) % THREADS);
+                            }
+                            assert_eq!(true, tree.insert(key.into(), RID::new(key, 0)).unwrap());
+                            let mut result = vec![];
+                            let present = key - 1;
+                            assert_eq!(true, tree.get_value(present.into(), &mut result).unwrap());
+                            assert_eq!(vec![RID::new(present, 0)], result);
+                        }
+                        check_scans(tree, (t + 1) % THREADS);
+                        for &key in mine.iter().step_by(2) {
+                            let rid = RID::new(key, 0);
+                            assert_eq!(true, tree.remove(GenericKey::from(key), rid).unwrap());
+                            assert_eq!(false, tree.remove(GenericKey::from(key), rid).unwrap());
+                        }
+                    });
+                }
+            });
+
+            let (entries, _) = check_tree(&tree);
+            let mut expected = (0..total).step_by(2).collect::<Vec<_>>();
+            for t in 0..THREADS {
+                expected.extend(keys_of(t).into_iter().skip(1).step_by(2));
+            }
+            expected.sort_unstable();
+            assert_eq!(
+                expected
+                    .into_iter()
+                    .map(|key| (GenericKey::from(key), RID::new(key, 0)))
+                    .collect::<Vec<_>>(),
+                entries
+            );
+            assert_eq!(0, bpm.stats().pinned_frames);
         }
     }
+
+    // Runs with `latch-debug`, any self deadlock or latch order cycle panics. Pool is kept small so pages are
+    // evicted and read back all along, order seen on them has to be forgotten each time.
+    #[cfg(feature = "latch-debug")]
+    #[test]
+    fn latch_debug_test() {
+        const THREADS: usize = 4;
+        const KEYS_PER_THREAD: usize = 500;
+        let disk_manager = MemoryManager::new(10000);
+        let bpm = Arc::new(BufferPoolManager::new(64, 10, Box::new(disk_manager)));
+        let tree = Tree::new(
+            "foo_pk".into(),
+            bpm.new_page_id(),
+            bpm.clone(),
+            comparator(),
+            Some(3),
+            Some(4),
+        )
+        .unwrap();
+
+        thread::scope(|s| {
+            for t in 0..THREADS {
+                let tree = &tree;
+                s.spawn(move || {
+                    let keys = (0..KEYS_PER_THREAD)
+                        .map(|i| (i * 7919) % KEYS_PER_THREAD * THREADS + t)
+                        .collect::<Vec<_>>();
+                    for &key in &keys {
+                        assert_eq!(true, tree.insert(key.into(), RID::new(key, 0)).unwrap());
+                    }
+                    let range = GenericKey::from(0)..GenericKey::from(KEYS_PER_THREAD * THREADS);
+                    tree.range(range.clone()).unwrap().for_each(drop);
+                    tree.range(range).unwrap().rev().for_each(drop);
+                    for &key in keys.iter().step_by(2) {
+                        let rid = RID::new(key, 0);
+                        assert_eq!(true, tree.remove(GenericKey::from(key), rid).unwrap());
+                    }
+                });
+            }
+        });
+
+        let (entries, _) = check_tree(&tree);
+        assert_eq!(THREADS * KEYS_PER_THREAD / 2, entries.len());
+        assert_eq!(0, bpm.stats().pinned_frames);
+    }
+
+    #[test]
+    fn open_after_restart_test() {
+        let path = env::temp_dir().join(format!("b_plus_tree_open_test_{}", process::id()));
+        let disk = SharedMemory(Arc::new(Mutex::new(MemoryManager::new(1000))));
+
+        let bpm = Arc::new(BufferPoolManager::new(10, 2, Box::new(disk.clone())));
+        let header_page_id = bpm.new_page_id();
+        let tree = Tree::new(
+            "foo_pk".into(),
+            header_page_id,
+            bpm.clone(),
+            comparator(),
+            Some(4),
+            Some(4),
+        )
+        .unwrap();
+        for key in 0..200 {
+            tree.insert(key.into(), RID::new(key, 0)).unwrap();
+        }
+        for key in 0..100 {
+            tree.remove(GenericKey::from(key), RID::new(key, 0))
+                .unwrap();
+        }
+        let (entries, _) = check_tree(&tree);
+        bpm.save_warm_up(&path).unwrap();
+        drop((tree, bpm));
+
+        let bpm = Arc::new(BufferPoolManager::new(10, 2, Box::new(disk)));
+        bpm.warm_up(&path).unwrap();
+        let tree = Tree::open("foo_pk".into(), header_page_id, bpm.clone(), comparator()).unwrap();
+        assert_eq!((4, 4), (tree.leaf_max_size, tree.internal_max_size));
+        assert_eq!(entries, check_tree(&tree).0);
+        tree.insert(GenericKey::from(0), RID::new(0, 0)).unwrap();
+        assert_eq!(101, check_tree(&tree).0.len());
+
+        // any other page is refused.
+        let page_id = bpm.new_page_id();
+        let res = Tree::open("foo_pk".into(), page_id, bpm, comparator());
+        assert_eq!(
+            true,
+            matches!(res, Err(IndexError::NotAnIndexPage(it)) if it == page_id)
+        );
+        std::fs::remove_file(path).unwrap();
+    }
 }
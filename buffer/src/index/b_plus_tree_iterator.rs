@@ -0,0 +1,563 @@
+use std::{
+    cmp::Ordering,
+    collections::VecDeque,
+    ops::{Bound, Range},
+};
+
+use storage::{BPlusTreeLeafPage, FrameHeader, PagePod, ReadPageGuard, INVALID_PAGE_ID};
+
+use super::{b_plus_tree::BPlusTree, KeyComparator};
+use crate::BufferPoolError;
+
+type Leaf<'a, KeyType, ValueType> = BPlusTreeLeafPage<KeyType, ValueType, &'a FrameHeader>;
+
+/// Entries of a B+ tree in key order, values of a key in value order, from either end, see `BPlusTree::range`.
+///
+/// Each end copies out entries of one leaf at a time and keeps no latch between calls. Moving on to the next
+/// leaf latches it before letting go of the one at hand. Leaves are latched left to right by tree as well,
+/// so going backward only takes a latch on the previous leaf if it is free right away. Otherwise it lets
+/// go, waits on both leaves left one first, and checks they are still next to each other. Whenever a leaf
+/// an end was at changed too much to tell where to go on, that end goes down from root again.
+///
+/// Iterator is not a snapshot. Keys added or removed while it runs may or may not show up, keys there all
+/// along always do. Entries never show up twice or out of order, and both ends stop where they meet.
+///
+/// An error from buffer pool is returned once, iterator ends after it.
+pub struct BPlusTreeIterator<'a, KeyType, ValueType, Comparator> {
+    tree: &'a BPlusTree<KeyType, ValueType, Comparator>,
+    // entries neither end returned yet, each end moves its bound past entries it returns.
+    start: EntryBound<KeyType, ValueType>,
+    end: EntryBound<KeyType, ValueType>,
+    front: Option<Cursor<KeyType, ValueType>>,
+    back: Option<Cursor<KeyType, ValueType>>,
+    done: bool,
+}
+
+// Bound on keys at first, then right past the last entry an end returned. Entries sharing a key are ordered by
+// value, an end can stop in the middle of them.
+enum EntryBound<KeyType, ValueType> {
+    Key(Bound<KeyType>),
+    Past(KeyType, ValueType),
+}
+
+// Leaf an end of iterator is at, with its entries that end is yet to return.
+struct Cursor<KeyType, ValueType> {
+    page_id: usize,
+    entries: VecDeque<(KeyType, ValueType)>,
+}
+
+impl<'a, KeyType: PagePod, ValueType: PagePod + Ord, Comparator: KeyComparator<KeyType>>
+    BPlusTreeIterator<'a, KeyType, ValueType, Comparator>
+{
+    // Front end is placed right away, back end only once it is asked for an entry.
+    pub(super) fn new(
+        tree: &'a BPlusTree<KeyType, ValueType, Comparator>,
+        start: Bound<KeyType>,
+        end: Bound<KeyType>,
+    ) -> Result<Self, BufferPoolError> {
+        let mut iter = Self {
+            tree,
+            start: EntryBound::Key(start),
+            end: EntryBound::Key(end),
+            front: None,
+            back: None,
+            done: false,
+        };
+        if !iter.load_front()? {
+            iter.finish();
+        }
+        Ok(iter)
+    }
+
+    fn finish(&mut self) {
+        self.done = true;
+        self.front = None;
+        self.back = None;
+    }
+
+    // Moves front end to first leaf holding entries after `start`. False if there is none.
+    fn load_front(&mut self) -> Result<bool, BufferPoolError> {
+        let relatched = match self.front.take() {
+            // entries left of leaf all come before its first one, none of them can be past `start`.
+            Some(cursor) => self.relatch(cursor.page_id, |leaf| {
+                !self.after_start(&leaf.keys()[0], &leaf.values()[0])
+            })?,
+            None => None,
+        };
+        let leaf = match relatched {
+            Some(leaf) => Some(leaf),
+            // separators are entries too, children before first one past `start` hold none past it.
+            None => self.tree.read_leaf_by(|page| {
+                page.keys()[1..].partition_point(|it| !self.after_start(&it.key, &it.value))
+            })?,
+        };
+        let Some((mut page_id, mut guard)) = leaf else {
+            return Ok(false);
+        };
+
+        loop {
+            let leaf = Leaf::<KeyType, ValueType>::new(guard.get_read_guard());
+            let from = partition_point(&leaf, |key, value| !self.after_start(key, value));
+            if from < leaf.len() {
+                self.front = Some(Cursor::new(page_id, &leaf, from..leaf.len()));
+                return Ok(true);
+            }
+            let next_page_id = leaf.next_page_id();
+            if next_page_id == INVALID_PAGE_ID {
+                return Ok(false);
+            }
+            // next leaf is latched before this one is let go, it can not be merged away in between.
+            guard = self.tree.bpm.read_page(next_page_id)?;
+            page_id = next_page_id;
+        }
+    }
+
+    // Moves back end to last leaf holding entries before `end`. False if there is none.
+    fn load_back(&mut self) -> Result<bool, BufferPoolError> {
+        let relatched = match self.back.take() {
+            Some(cursor) => self.relatch(cursor.page_id, |leaf| self.holds_end(leaf))?,
+            None => None,
+        };
+        let leaf = match relatched {
+            Some(leaf) => Some(leaf),
+            None => self.read_leaf_before_end()?,
+        };
+        let Some((mut page_id, mut guard)) = leaf else {
+            return Ok(false);
+        };
+
+        loop {
+            let leaf = Leaf::<KeyType, ValueType>::new(guard.get_read_guard());
+            let to = partition_point(&leaf, |key, value| self.before_end(key, value));
+            if to > 0 {
+                self.back = Some(Cursor::new(page_id, &leaf, 0..to));
+                return Ok(true);
+            }
+            let prev_page_id = leaf.prev_page_id();
+            if prev_page_id == INVALID_PAGE_ID {
+                return Ok(false);
+            }
+            let prev = match self.tree.bpm.try_read_page(prev_page_id) {
+                Ok(prev_guard) => Some((prev_page_id, prev_guard)),
+                Err(BufferPoolError::WouldBlock) => {
+                    drop(guard);
+                    match self.latch_prev(prev_page_id, page_id)? {
+                        Some(prev) => Some(prev),
+                        None => self.read_leaf_before_end()?,
+                    }
+                }
+                Err(err) => return Err(err),
+            };
+            let Some(prev) = prev else {
+                return Ok(false);
+            };
+            (page_id, guard) = prev;
+        }
+    }
+
+    // Latches leaf at `prev_page_id` and then leaf at `page_id`, waiting on both. Leaf to go on with is the
+    // right one if entries before `end` moved in meanwhile, otherwise the left one as long as it still comes
+    // right before. None if either changed too much for that.
+    fn latch_prev(
+        &self,
+        prev_page_id: usize,
+        page_id: usize,
+    ) -> Result<Option<(usize, ReadPageGuard)>, BufferPoolError> {
+        let Some(prev_guard) = self.read_leaf_page(prev_page_id)? else {
+            return Ok(None);
+        };
+        let Some(guard) = self.read_leaf_page(page_id)? else {
+            return Ok(None);
+        };
+        let prev = Leaf::<KeyType, ValueType>::new(prev_guard.get_read_guard());
+        let leaf = Leaf::<KeyType, ValueType>::new(guard.get_read_guard());
+        if prev.is_empty()
+            || leaf.is_empty()
+            || prev.next_page_id() != page_id
+            || !self.holds_end(&leaf)
+        {
+            return Ok(None);
+        }
+        if self.before_end(&leaf.keys()[0], &leaf.values()[0]) {
+            return Ok(Some((page_id, guard)));
+        }
+        Ok(Some((prev_page_id, prev_guard)))
+    }
+
+    // Latches again leaf an end of iterator was at. None if it is gone, or if entries that end has yet to return
+    // may have moved out of it to a leaf beyond, `holds_bound` tells.
+    fn relatch(
+        &self,
+        page_id: usize,
+        holds_bound: impl Fn(&Leaf<KeyType, ValueType>) -> bool,
+    ) -> Result<Option<(usize, ReadPageGuard)>, BufferPoolError> {
+        let Some(guard) = self.read_leaf_page(page_id)? else {
+            return Ok(None);
+        };
+        let leaf = Leaf::new(guard.get_read_guard());
+        if leaf.is_empty() || !holds_bound(&leaf) {
+            return Ok(None);
+        }
+        Ok(Some((page_id, guard)))
+    }
+
+    // None if leaf was merged away and deleted. One that could not be deleted is left empty.
+    fn read_leaf_page(&self, page_id: usize) -> Result<Option<ReadPageGuard>, BufferPoolError> {
+        match self.tree.bpm.read_page(page_id) {
+            Ok(guard) => Ok(Some(guard)),
+            Err(BufferPoolError::PageDeleted(_)) => Ok(None),
+            Err(err) => Err(err),
+        }
+    }
+
+    // Leaf holding last entry before `end`, or the one after it if that entry would go to its start.
+    fn read_leaf_before_end(&self) -> Result<Option<(usize, ReadPageGuard)>, BufferPoolError> {
+        self.tree.read_leaf_by(|page| {
+            page.keys()[1..].partition_point(|it| self.before_end(&it.key, &it.value))
+        })
+    }
+
+    // entries right of leaf all come after its last one, none of them can be before `end`.
+    fn holds_end(&self, leaf: &Leaf<KeyType, ValueType>) -> bool {
+        let last = leaf.len() - 1;
+        !self.before_end(&leaf.keys()[last], &leaf.values()[last])
+    }
+
+    fn after_start(&self, key: &KeyType, value: &ValueType) -> bool {
+        let comparator = &self.tree.comparator;
+        match &self.start {
+            EntryBound::Key(Bound::Included(start)) => comparator.compare(key, start).is_ge(),
+            EntryBound::Key(Bound::Excluded(start)) => comparator.compare(key, start).is_gt(),
+            EntryBound::Key(Bound::Unbounded) => true,
+            EntryBound::Past(start, start_value) => self
+                .tree
+                .compare_entries((key, value), (start, start_value))
+                .is_gt(),
+        }
+    }
+
+    fn before_end(&self, key: &KeyType, value: &ValueType) -> bool {
+        let comparator = &self.tree.comparator;
+        match &self.end {
+            EntryBound::Key(Bound::Included(end)) => comparator.compare(key, end).is_le(),
+            EntryBound::Key(Bound::Excluded(end)) => comparator.compare(key, end).is_lt(),
+            EntryBound::Key(Bound::Unbounded) => true,
+            EntryBound::Past(end, end_value) => self
+                .tree
+                .compare_entries((key, value), (end, end_value))
+                .is_lt(),
+        }
+    }
+}
+
+// Number of entries at start of `leaf` which `pred` holds for, it holds for none after the first it does not.
+fn partition_point<KeyType: PagePod, ValueType: PagePod>(
+    leaf: &Leaf<KeyType, ValueType>,
+    pred: impl Fn(&KeyType, &ValueType) -> bool,
+) -> usize {
+    let index = leaf.lookup(|key, value| {
+        if pred(key, value) {
+            Ordering::Less
+        } else {
+            Ordering::Greater
+        }
+    });
+    index.unwrap_err()
+}
+
+impl<KeyType: PagePod, ValueType: PagePod> Cursor<KeyType, ValueType> {
+    fn new(page_id: usize, leaf: &Leaf<KeyType, ValueType>, range: Range<usize>) -> Self {
+        let keys = leaf.keys()[range.clone()].iter().copied();
+        let values = leaf.values()[range].iter().copied();
+        Self {
+            page_id,
+            entries: keys.zip(values).collect(),
+        }
+    }
+}
+
+impl<KeyType: PagePod, ValueType: PagePod + Ord, Comparator: KeyComparator<KeyType>> Iterator
+    for BPlusTreeIterator<'_, KeyType, ValueType, Comparator>
+{
+    type Item = Result<(KeyType, ValueType), BufferPoolError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        while !self.done {
+            let entry = self.front.as_mut().and_then(|it| it.entries.pop_front());
+            if let Some((key, value)) = entry {
+                if !self.before_end(&key, &value) {
+                    break;
+                }
+                self.start = EntryBound::Past(key, value);
+                return Some(Ok((key, value)));
+            }
+            match self.load_front() {
+                Ok(true) => {}
+                Ok(false) => break,
+                Err(err) => {
+                    self.finish();
+                    return Some(Err(err));
+                }
+            }
+        }
+        self.finish();
+        None
+    }
+}
+
+impl<KeyType: PagePod, ValueType: PagePod + Ord, Comparator: KeyComparator<KeyType>>
+    DoubleEndedIterator for BPlusTreeIterator<'_, KeyType, ValueType, Comparator>
+{
+    fn next_back(&mut self) -> Option<Self::Item> {
+        while !self.done {
+            let entry = self.back.as_mut().and_then(|it| it.entries.pop_back());
+            if let Some((key, value)) = entry {
+                if !self.after_start(&key, &value) {
+                    break;
+                }
+                self.end = EntryBound::Past(key, value);
+                return Some(Ok((key, value)));
+            }
+            match self.load_back() {
+                Ok(true) => {}
+                Ok(false) => break,
+                Err(err) => {
+                    self.finish();
+                    return Some(Err(err));
+                }
+            }
+        }
+        self.finish();
+        None
+    }
+}
+
+#[cfg(test)]
+#[allow(clippy::bool_assert_comparison)]
+mod test {
+    use std::{
+        ops::Bound,
+        sync::{Arc, Barrier},
+        thread,
+        time::Duration,
+    };
+
+    use catalog::parse_create_stmt;
+    use common::RID;
+    use storage::{BPlusTreeLeafPage, MemoryManager};
+
+    use crate::{
+        index::{b_plus_tree::BPlusTree, key_comparator::GenericComparator, GenericKey},
+        BufferPoolError, BufferPoolManager,
+    };
+
+    type Tree = BPlusTree<GenericKey<8>, RID, GenericComparator<8>>;
+
+    fn keys(
+        iter: impl Iterator<Item = Result<(GenericKey<8>, RID), BufferPoolError>>,
+    ) -> Vec<usize> {
+        iter.map(|entry| {
+            let (key, rid) = entry.unwrap();
+            assert_eq!(GenericKey::from(rid.page_id), key);
+            rid.page_id
+        })
+        .collect()
+    }
+
+    #[test]
+    fn range_test() {
+        let bpm = Arc::new(BufferPoolManager::new(
+            50,
+            2,
+            Box::new(MemoryManager::new(1000)),
+        ));
+        let tree = Tree::new(
+            "foo_pk".into(),
+            bpm.new_page_id(),
+            bpm.clone(),
+            GenericComparator::new(&parse_create_stmt("a bigint")),
+            Some(3),
+            Some(3),
+        )
+        .unwrap();
+        assert_eq!(Vec::<usize>::new(), keys(tree.iter().unwrap()));
+
+        // even keys only, scrambled so leaves split all over.
+        for i in 0..200 {
+            let key = (i * 37) % 200 * 2;
+            tree.insert(key.into(), RID::new(key, 0)).unwrap();
+        }
+        let all = (0..400).step_by(2).collect::<Vec<_>>();
+        assert_eq!(all, keys(tree.iter().unwrap()));
+        assert_eq!(all[50..], keys(tree.iter_from(100.into()).unwrap()));
+        assert_eq!(all[51..], keys(tree.iter_from(101.into()).unwrap()));
+        assert_eq!(
+            Vec::<usize>::new(),
+            keys(tree.iter_from(399.into()).unwrap())
+        );
+
+        let (lo, hi) = (GenericKey::from(100), GenericKey::from(120));
+        assert_eq!(all[50..60], keys(tree.range(lo..hi).unwrap()));
+        assert_eq!(all[50..=60], keys(tree.range(lo..=hi).unwrap()));
+        assert_eq!(all[..60], keys(tree.range(..hi).unwrap()));
+        assert_eq!(
+            all[51..=60],
+            keys(
+                tree.range((Bound::Excluded(lo), Bound::Included(hi)))
+                    .unwrap()
+            )
+        );
+        assert_eq!(
+            Vec::<usize>::new(),
+            keys(
+                tree.range(GenericKey::from(101)..GenericKey::from(102))
+                    .unwrap()
+            )
+        );
+
+        // descending, and from both ends till they meet.
+        let mut desc = all.clone();
+        desc.reverse();
+        assert_eq!(desc, keys(tree.iter().unwrap().rev()));
+        assert_eq!(desc[..10], keys(tree.iter().unwrap().rev().take(10)));
+        assert_eq!(
+            all[50..=60].iter().rev().copied().collect::<Vec<_>>(),
+            keys(tree.range(lo..=hi).unwrap().rev())
+        );
+        assert_eq!(
+            all[..60].iter().rev().copied().collect::<Vec<_>>(),
+            keys(tree.range(..GenericKey::from(119)).unwrap().rev())
+        );
+        let mut iter = tree.range(lo..hi).unwrap();
+        let (mut front, mut back) = (vec![], vec![]);
+        while let Some(entry) = iter.next() {
+            front.push(entry.unwrap().1.page_id);
+            if let Some(entry) = iter.next_back() {
+                back.push(entry.unwrap().1.page_id);
+            }
+        }
+        back.reverse();
+        front.extend(back);
+        assert_eq!(all[50..60], front);
+
+        // no leaf stays latched between calls.
+        let mut iter = tree.iter().unwrap();
+        for _ in 0..100 {
+            iter.next().unwrap().unwrap();
+            iter.next_back().unwrap().unwrap();
+            assert_eq!(0, bpm.stats().pinned_frames);
+        }
+        assert_eq!(true, iter.next().is_none());
+
+        // leaves merged away are left out of chain.
+        for key in (0..400).step_by(4) {
+            tree.remove(GenericKey::from(key), RID::new(key, 0))
+                .unwrap();
+        }
+        let left = (2..400).step_by(4).collect::<Vec<_>>();
+        assert_eq!(left, keys(tree.iter().unwrap()));
+        assert_eq!(
+            left.iter().rev().copied().collect::<Vec<_>>(),
+            keys(tree.iter().unwrap().rev())
+        );
+    }
+
+    #[test]
+    fn duplicate_keys_range_test() {
+        let bpm = Arc::new(BufferPoolManager::new(
+            50,
+            10,
+            Box::new(MemoryManager::new(1000)),
+        ));
+        let tree = Tree::new(
+            "foo_idx".into(),
+            bpm.new_page_id(),
+            bpm.clone(),
+            GenericComparator::new(&parse_create_stmt("a bigint")),
+            Some(3),
+            Some(3),
+        )
+        .unwrap();
+        for key in 0..10 {
+            for slot in 0..5 {
+                tree.insert(key.into(), RID::new(key, slot)).unwrap();
+            }
+        }
+        let entries = |iter: &mut dyn Iterator<Item = _>| {
+            iter.map(|entry: Result<(GenericKey<8>, RID), _>| entry.unwrap().1)
+                .collect::<Vec<_>>()
+        };
+        let values_of = |key| (0..5).map(move |slot| RID::new(key, slot));
+
+        // every value of a key falls in range of that key, in value order.
+        let all = values_of(3).chain(values_of(4)).collect::<Vec<_>>();
+        let range = GenericKey::from(3)..=GenericKey::from(4);
+        assert_eq!(all, entries(&mut tree.range(range.clone()).unwrap()));
+        let mut reversed = entries(&mut tree.range(range.clone()).unwrap().rev());
+        reversed.reverse();
+        assert_eq!(all, reversed);
+        // an excluded bound leaves out every value of its key.
+        let after = (
+            Bound::Excluded(GenericKey::from(3)),
+            Bound::Excluded(GenericKey::from(5)),
+        );
+        assert_eq!(
+            values_of(4).collect::<Vec<_>>(),
+            entries(&mut tree.range(after).unwrap())
+        );
+
+        // ends meet in the middle of values of a key, none is skipped or returned twice.
+        let mut iter = tree.range(range).unwrap();
+        let mut front = entries(&mut iter.by_ref().take(7));
+        let back = entries(&mut iter.by_ref().rev());
+        front.extend(back.into_iter().rev());
+        assert_eq!(all, front);
+    }
+
+    #[test]
+    fn reverse_waits_for_latched_leaf_test() {
+        let bpm = Arc::new(BufferPoolManager::new(
+            50,
+            2,
+            Box::new(MemoryManager::new(1000)),
+        ));
+        let tree = Tree::new(
+            "foo_pk".into(),
+            bpm.new_page_id(),
+            bpm.clone(),
+            GenericComparator::new(&parse_create_stmt("a bigint")),
+            Some(4),
+            Some(4),
+        )
+        .unwrap();
+        for key in 0..100 {
+            tree.insert(key.into(), RID::new(key, 0)).unwrap();
+        }
+        let (_, guard) = tree.read_leaf_by(|page| page.len() - 1).unwrap().unwrap();
+        let leaf = BPlusTreeLeafPage::<GenericKey<8>, RID, _>::new(guard.get_read_guard());
+        let (last_len, prev_page_id) = (leaf.len(), leaf.prev_page_id());
+        drop(guard);
+
+        let mut iter = tree.iter().unwrap().rev();
+        assert_eq!(
+            (100 - last_len..100).rev().collect::<Vec<_>>(),
+            keys(iter.by_ref().take(last_len))
+        );
+        // previous leaf is write latched when iterator gets to it, it has to let go of last leaf and wait.
+        let latched = Barrier::new(2);
+        thread::scope(|s| {
+            s.spawn(|| {
+                let guard = bpm.write_page(prev_page_id).unwrap();
+                latched.wait();
+                thread::sleep(Duration::from_millis(50));
+                drop(guard);
+            });
+            latched.wait();
+            assert_eq!(
+                (0..100 - last_len).rev().collect::<Vec<_>>(),
+                keys(iter.by_ref())
+            );
+        });
+        assert_eq!(0, bpm.stats().pinned_frames);
+    }
+}
@@ -0,0 +1,49 @@
+use std::fmt::Display;
+
+use crate::BufferPoolError;
+
+/// Failure of an index operation which is about index itself rather than pages it lives in.
+#[derive(Debug)]
+pub enum IndexError {
+    /// Buffer pool could not hand out, bring in or write back a page of index.
+    BufferPool(BufferPoolError),
+    /// Page is not a B+ tree header page, so no index can be opened on it.
+    NotAnIndexPage(usize),
+    /// Bulk load entry at `position`, counting from 0, comes before the one preceding it in tree order.
+    UnsortedInput { position: usize },
+    /// Bulk load entry at `position`, counting from 0, is the same as the one preceding it.
+    DuplicateInput { position: usize },
+    /// Bulk load was asked to fill a tree which already holds entries.
+    NotEmpty,
+}
+
+impl Display for IndexError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            IndexError::BufferPool(err) => write!(f, "{err}"),
+            IndexError::NotAnIndexPage(page_id) => {
+                write!(f, "page {page_id} is not a B+ tree header page")
+            }
+            IndexError::UnsortedInput { position } => write!(f, "entry {position} is out of order"),
+            IndexError::DuplicateInput { position } => {
+                write!(f, "entry {position} repeats the one before")
+            }
+            IndexError::NotEmpty => write!(f, "tree is not empty"),
+        }
+    }
+}
+
+impl std::error::Error for IndexError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            IndexError::BufferPool(err) => Some(err),
+            _ => None,
+        }
+    }
+}
+
+impl From<BufferPoolError> for IndexError {
+    fn from(value: BufferPoolError) -> Self {
+        IndexError::BufferPool(value)
+    }
+}
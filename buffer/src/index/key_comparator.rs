@@ -0,0 +1,158 @@
+use std::cmp::Ordering;
+
+use catalog::Schema;
+use data_type::DataType;
+
+use super::{read, GenericKey};
+
+/// Orders keys of a B+ tree. Tree compares keys only through it, key types need not be `Ord`.
+pub trait KeyComparator<KeyType> {
+    fn compare(&self, lhs: &KeyType, rhs: &KeyType) -> Ordering;
+}
+
+/// Compares `GenericKey`s by typed values of key schema columns, first column that differs decides.
+///
+/// Columns are laid out one after another from start of key, each taking as many bytes as its type, see
+/// `DataType::get_type_size`. Numbers are stored little-endian, a boolean as 0 or 1, and a varchar as its bytes
+/// padded with zeros, see `GenericKey::from_values`. Decimals are ordered as by `f64::total_cmp`.
+#[derive(Debug, Clone)]
+pub struct GenericComparator<const N: usize> {
+    // type of each column with its offset in key.
+    columns: Vec<(DataType, usize)>,
+}
+
+impl<const N: usize> GenericComparator<N> {
+    /// Panics if a column type has no fixed size, or columns do not fit in `N` bytes.
+    pub fn new(key_schema: &Schema) -> Self {
+        Self {
+            columns: key_layout(key_schema, N),
+        }
+    }
+}
+
+// Type and offset of each column of `key_schema` in a key of `key_size` bytes, see `GenericComparator`.
+pub(super) fn key_layout(key_schema: &Schema, key_size: usize) -> Vec<(DataType, usize)> {
+    let mut offset = 0;
+    let mut columns = vec![];
+    for column in key_schema.get_columns() {
+        let Some(size) = column.data_type.get_type_size() else {
+            panic!(
+                "column {} of type {} can not be part of a key",
+                column.get_name(),
+                column.data_type
+            );
+        };
+        columns.push((column.data_type.clone(), offset));
+        offset += size;
+    }
+    assert!(
+        offset <= key_size,
+        "key columns take {offset} bytes, key has {key_size}"
+    );
+    columns
+}
+
+impl<const N: usize> KeyComparator<GenericKey<N>> for GenericComparator<N> {
+    fn compare(&self, lhs: &GenericKey<N>, rhs: &GenericKey<N>) -> Ordering {
+        self.columns
+            .iter()
+            .map(|(data_type, offset)| {
+                compare_column(data_type, &lhs.data[*offset..], &rhs.data[*offset..])
+            })
+            .find(|it| it.is_ne())
+            .unwrap_or(Ordering::Equal)
+    }
+}
+
+// Compares values of `data_type` at start of `lhs` and `rhs`.
+fn compare_column(data_type: &DataType, lhs: &[u8], rhs: &[u8]) -> Ordering {
+    match data_type {
+        DataType::Boolean => lhs[0].cmp(&rhs[0]),
+        DataType::TinyInt => (lhs[0] as i8).cmp(&(rhs[0] as i8)),
+        DataType::SmallInt => i16::from_le_bytes(read(lhs)).cmp(&i16::from_le_bytes(read(rhs))),
+        DataType::Integer => i32::from_le_bytes(read(lhs)).cmp(&i32::from_le_bytes(read(rhs))),
+        DataType::BigInt => i64::from_le_bytes(read(lhs)).cmp(&i64::from_le_bytes(read(rhs))),
+        DataType::Decimal => {
+            f64::from_le_bytes(read(lhs)).total_cmp(&f64::from_le_bytes(read(rhs)))
+        }
+        DataType::Timestamp => u64::from_le_bytes(read(lhs)).cmp(&u64::from_le_bytes(read(rhs))),
+        DataType::Varchar(size) => lhs[..*size as usize].cmp(&rhs[..*size as usize]),
+        DataType::Invalid | DataType::Vector => unreachable!("key column of type {data_type}"),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::cmp::Ordering;
+
+    use catalog::parse_create_stmt;
+
+    use super::{GenericComparator, KeyComparator};
+    use crate::index::GenericKey;
+
+    fn key(columns: &[&[u8]]) -> GenericKey<16> {
+        let mut data = [0u8; 16];
+        let mut offset = 0;
+        for column in columns {
+            data[offset..offset + column.len()].copy_from_slice(column);
+            offset += column.len();
+        }
+        GenericKey { data }
+    }
+
+    #[test]
+    fn compare_typed_columns_test() {
+        let comparator = GenericComparator::<16>::new(&parse_create_stmt("a int"));
+        // bytes alone would put 256 before 1, and -1 after both.
+        let (one, big, minus) = (
+            1i32.to_le_bytes(),
+            256i32.to_le_bytes(),
+            (-1i32).to_le_bytes(),
+        );
+        assert_eq!(
+            Ordering::Less,
+            comparator.compare(&key(&[&one]), &key(&[&big]))
+        );
+        assert_eq!(
+            Ordering::Less,
+            comparator.compare(&key(&[&minus]), &key(&[&one]))
+        );
+        assert_eq!(
+            Ordering::Equal,
+            comparator.compare(&key(&[&big]), &key(&[&big]))
+        );
+
+        let comparator = GenericComparator::<16>::new(&parse_create_stmt("d double"));
+        let (low, high) = ((-2.5f64).to_le_bytes(), 0.5f64.to_le_bytes());
+        assert_eq!(
+            Ordering::Less,
+            comparator.compare(&key(&[&low]), &key(&[&high]))
+        );
+    }
+
+    #[test]
+    fn compare_composite_key_test() {
+        let comparator =
+            GenericComparator::<16>::new(&parse_create_stmt("name varchar(4), id bigint"));
+        let (one, two) = (1i64.to_le_bytes(), 2i64.to_le_bytes());
+        // first column decides, second one only breaks ties.
+        assert_eq!(
+            Ordering::Less,
+            comparator.compare(&key(&[b"ab\0\0", &two]), &key(&[b"abc\0", &one]))
+        );
+        assert_eq!(
+            Ordering::Greater,
+            comparator.compare(&key(&[b"abc\0", &two]), &key(&[b"abc\0", &one]))
+        );
+        assert_eq!(
+            Ordering::Equal,
+            comparator.compare(&key(&[b"abc\0", &one]), &key(&[b"abc\0", &one]))
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "key columns take 12 bytes, key has 8")]
+    fn schema_larger_than_key_panics() {
+        GenericComparator::<8>::new(&parse_create_stmt("a bigint, b int"));
+    }
+}
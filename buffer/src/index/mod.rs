@@ -1,31 +1,229 @@
 #![allow(dead_code)]
 
-use std::{array, usize};
+use catalog::Schema;
+use data_type::{DataType, Value};
+use storage::PagePod;
 
 mod b_plus_tree;
+mod b_plus_tree_iterator;
+mod error;
+mod key_comparator;
+mod var_b_plus_tree;
 
-#[derive(Debug, PartialEq, Eq, PartialOrd, Ord)]
-struct GenericKey<const N: usize> {
-    data: [char; N],
+pub use b_plus_tree_iterator::BPlusTreeIterator;
+pub use error::IndexError;
+pub use key_comparator::{GenericComparator, KeyComparator};
+pub use var_b_plus_tree::VarBPlusTree;
+
+/// Key of `N` bytes holding values of key schema columns, see `GenericComparator` for how they are laid out.
+#[repr(C)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct GenericKey<const N: usize> {
+    data: [u8; N],
 }
 
-/// Only for testing
+// Safety: a byte array, valid for any bit pattern and without padding.
+unsafe impl<const N: usize> PagePod for GenericKey<N> {}
+
+impl<const N: usize> GenericKey<N> {
+    /// Key holding `values`, one for each column of `key_schema`, laid out the way `GenericComparator` reads
+    /// them. Panics if values do not match columns in number or type, or a varchar is longer than its column.
+    pub fn from_values(values: &[Value], key_schema: &Schema) -> Self {
+        let layout = key_comparator::key_layout(key_schema, N);
+        assert_eq!(
+            layout.len(),
+            values.len(),
+            "key has {} columns, got {} values",
+            layout.len(),
+            values.len()
+        );
+        let mut data = [0u8; N];
+        for ((data_type, offset), value) in layout.iter().zip(values) {
+            let column = &mut data[*offset..];
+            match (data_type, value) {
+                (DataType::Boolean, Value::Boolean(value)) => column[0] = *value as u8,
+                (DataType::TinyInt, Value::TinyInt(value)) => column[0] = *value as u8,
+                (DataType::SmallInt, Value::SmallInt(value)) => {
+                    column[..2].copy_from_slice(&value.to_le_bytes())
+                }
+                (DataType::Integer, Value::Integer(value)) => {
+                    column[..4].copy_from_slice(&value.to_le_bytes())
+                }
+                (DataType::BigInt, Value::BigInt(value)) => {
+                    column[..8].copy_from_slice(&value.to_le_bytes())
+                }
+                (DataType::Decimal, Value::Decimal(value)) => {
+                    column[..8].copy_from_slice(&value.to_le_bytes())
+                }
+                (DataType::Timestamp, Value::Timestamp(value)) => {
+                    column[..8].copy_from_slice(&value.to_le_bytes())
+                }
+                (DataType::Varchar(size), Value::Varchar(value)) => {
+                    assert!(
+                        value.len() <= *size as usize,
+                        "{value:?} does not fit in {data_type}"
+                    );
+                    column[..value.len()].copy_from_slice(value.as_bytes())
+                }
+                (data_type, value) => panic!("{value:?} is not a {data_type}"),
+            }
+        }
+        Self { data }
+    }
+
+    /// Key holding columns of `key_schema` picked by name out of `tuple`, a row of `table_schema`. Panics if
+    /// tuple does not have a value for each table column, or table has no column of that name, see
+    /// `from_values` for other cases.
+    pub fn from_tuple(tuple: &[Value], table_schema: &Schema, key_schema: &Schema) -> Self {
+        assert_eq!(
+            table_schema.get_col_count() as usize,
+            tuple.len(),
+            "table has {} columns, tuple has {} values",
+            table_schema.get_col_count(),
+            tuple.len()
+        );
+        let values = key_schema
+            .get_columns()
+            .iter()
+            .map(|column| {
+                let Some(col_idx) = table_schema.get_col_idx(column.get_name()) else {
+                    panic!("table has no key column {}", column.get_name());
+                };
+                tuple[col_idx as usize].clone()
+            })
+            .collect::<Vec<_>>();
+        Self::from_values(&values, key_schema)
+    }
+
+    /// Values of columns of `key_schema`, inverse of `from_values`. A varchar comes back without zero bytes
+    /// it was padded with, trailing ones of its own included.
+    pub fn values(&self, key_schema: &Schema) -> Vec<Value> {
+        let layout = key_comparator::key_layout(key_schema, N);
+        layout
+            .iter()
+            .map(|(data_type, offset)| {
+                let column = &self.data[*offset..];
+                match data_type {
+                    DataType::Boolean => Value::Boolean(column[0] != 0),
+                    DataType::TinyInt => Value::TinyInt(column[0] as i8),
+                    DataType::SmallInt => Value::SmallInt(i16::from_le_bytes(read(column))),
+                    DataType::Integer => Value::Integer(i32::from_le_bytes(read(column))),
+                    DataType::BigInt => Value::BigInt(i64::from_le_bytes(read(column))),
+                    DataType::Decimal => Value::Decimal(f64::from_le_bytes(read(column))),
+                    DataType::Timestamp => Value::Timestamp(u64::from_le_bytes(read(column))),
+                    DataType::Varchar(size) => {
+                        let bytes = &column[..*size as usize];
+                        let len = bytes.iter().rposition(|&it| it != 0).map_or(0, |it| it + 1);
+                        Value::Varchar(String::from_utf8_lossy(&bytes[..len]).into_owned())
+                    }
+                    DataType::Invalid | DataType::Vector => {
+                        unreachable!("key column of type {data_type}")
+                    }
+                }
+            })
+            .collect()
+    }
+}
+
+fn read<const S: usize>(bytes: &[u8]) -> [u8; S] {
+    bytes[..S].try_into().unwrap()
+}
+
+/// Only for testing. Value is stored as a single bigint key column, see `GenericComparator`. A key shorter
+/// than a bigint keeps low bytes of value only.
 impl<const N: usize> From<usize> for GenericKey<N> {
     fn from(value: usize) -> Self {
-        let value = value.to_string();
-        let data: [char; N] = array::from_fn(|i| value.chars().nth(i).unwrap_or('0'));
+        let bytes = (value as i64).to_le_bytes();
+        let len = N.min(bytes.len());
+        let mut data = [0u8; N];
+        data[..len].copy_from_slice(&bytes[..len]);
 
         Self { data }
     }
 }
 
 #[cfg(test)]
+#[allow(clippy::bool_assert_comparison)]
 mod test {
-    use super::GenericKey;
+    use std::cmp::Ordering;
+
+    use catalog::parse_create_stmt;
+
+    use data_type::Value;
+
+    use super::{GenericComparator, GenericKey, KeyComparator};
 
     #[test]
     fn generic_key_from_usize() {
-        let key: GenericKey<4> = 20usize.into();
-        assert_eq!(key.data, ['2', '0', '0', '0']);
+        let key: GenericKey<12> = 20usize.into();
+        assert_eq!(key.data, [20, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0]);
+        let key: GenericKey<2> = 0x0103_0201usize.into();
+        assert_eq!(key.data, [1, 2]);
+
+        // little-endian bytes alone would order 256 before 1.
+        let comparator = GenericComparator::<12>::new(&parse_create_stmt("a bigint"));
+        let (one, big) = (GenericKey::<12>::from(1), GenericKey::<12>::from(256));
+        assert_eq!(true, one.data > big.data);
+        assert_eq!(Ordering::Less, comparator.compare(&one, &big));
+        assert_eq!(Ordering::Less, comparator.compare(&9.into(), &10.into()));
+    }
+
+    #[test]
+    fn generic_key_values_round_trip() {
+        let key_schema =
+            parse_create_stmt("ok bool, level smallint, name varchar(6), score double, at bigint");
+        let values = vec![
+            Value::Boolean(true),
+            Value::SmallInt(-3),
+            Value::Varchar("bob".into()),
+            Value::Decimal(0.25),
+            Value::BigInt(1 << 40),
+        ];
+        let key = GenericKey::<32>::from_values(&values, &key_schema);
+        assert_eq!(values, key.values(&key_schema));
+
+        // key columns are picked out of a row by name, in key order.
+        let table_schema = parse_create_stmt("id bigint, name varchar(6), age int");
+        let key_schema = parse_create_stmt("age int, name varchar(6)");
+        let tuple = [
+            Value::BigInt(7),
+            Value::Varchar("alice".into()),
+            Value::Integer(30),
+        ];
+        let key = GenericKey::<16>::from_tuple(&tuple, &table_schema, &key_schema);
+        assert_eq!(
+            vec![Value::Integer(30), Value::Varchar("alice".into())],
+            key.values(&key_schema)
+        );
+
+        let comparator = GenericComparator::<16>::new(&key_schema);
+        let younger = GenericKey::<16>::from_values(
+            &[Value::Integer(9), Value::Varchar("zed".into())],
+            &key_schema,
+        );
+        assert_eq!(Ordering::Less, comparator.compare(&younger, &key));
+    }
+
+    #[test]
+    #[should_panic(expected = "table has 3 columns, tuple has 2 values")]
+    fn generic_key_from_short_tuple_panics() {
+        let table_schema = parse_create_stmt("id bigint, name varchar(6), age int");
+        let key_schema = parse_create_stmt("age int");
+        let tuple = [Value::BigInt(7), Value::Varchar("alice".into())];
+        GenericKey::<8>::from_tuple(&tuple, &table_schema, &key_schema);
+    }
+
+    #[test]
+    #[should_panic(expected = "is not a Integer")]
+    fn generic_key_from_mismatched_value_panics() {
+        let key_schema = parse_create_stmt("a int");
+        GenericKey::<8>::from_values(&[Value::BigInt(1)], &key_schema);
+    }
+
+    #[test]
+    #[should_panic(expected = "does not fit in Varchar(2)")]
+    fn generic_key_from_long_varchar_panics() {
+        let key_schema = parse_create_stmt("a varchar(2)");
+        GenericKey::<8>::from_values(&[Value::Varchar("abc".into())], &key_schema);
     }
 }
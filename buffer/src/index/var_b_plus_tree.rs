@@ -0,0 +1,661 @@
+use std::{cmp::Ordering, marker::PhantomData, sync::Arc};
+
+use storage::{
+    shortest_separator, BPlusTreeVarInternalPage, BPlusTreeVarLeafPage, BplusTreeHeaderPage,
+    FanOut, FrameHeader, PagePod, ReadPageGuard, WritePageGuard, INVALID_PAGE_ID, PAGE_SIZE,
+};
+
+use super::IndexError;
+use crate::{BufferPoolError, BufferPoolManager, Residency};
+
+type InternalPage<Page> = BPlusTreeVarInternalPage<usize, Page>;
+
+// Write latched internal pages on the way down, from root. Each with its page id and index of child taken.
+type Path = Vec<(usize, usize, WritePageGuard)>;
+
+/// B+ tree over variable length keys, byte strings ordered as bytes. Each key maps to a single value.
+///
+/// Pages are slotted, see `BPlusTreeVarLeafPage` and `BPlusTreeVarInternalPage`. Bytes all keys of a page
+/// share are stored once per page, and internal pages only keep as much of a key as tells two leaves apart,
+/// see `shortest_separator`. A page splits once an entry does not fit in it, into halves holding about as
+/// many bytes. A page falling under a quarter full is merged with a sibling if both fit in one page, it is
+/// left as it is otherwise. `fan_out` tells how many entries pages end up holding.
+///
+/// Root page id and height are kept in header page, with max key size in place of both max sizes. Lookups
+/// go down with read latches, each let go once the one below is taken. Inserts and removes take turns on a
+/// write latch on header page, and keep every page from root down to leaf write latched until they are done.
+pub struct VarBPlusTree<ValueType> {
+    index_name: String,
+    pub(super) bpm: Arc<BufferPoolManager>,
+    max_key_size: u32,
+    header_page_id: usize,
+    v: PhantomData<ValueType>,
+}
+
+impl<ValueType: PagePod> VarBPlusTree<ValueType> {
+    /// Creates an empty tree for keys of up to `max_key_size` bytes, laying out header page at
+    /// `header_page_id` over whatever it held. Panics unless four such keys fit in a page.
+    pub fn new(
+        index_name: String,
+        header_page_id: usize,
+        bpm: Arc<BufferPoolManager>,
+        max_key_size: u32,
+    ) -> Result<Self, BufferPoolError> {
+        // fails on keys too long, before anything is written.
+        let mut page = FrameHeader::new(0);
+        BPlusTreeVarLeafPage::<ValueType, _>::init(&mut page, max_key_size, INVALID_PAGE_ID);
+        let mut guard = bpm.write_page(header_page_id)?;
+        *guard.get_writeable_data_as() = BplusTreeHeaderPage::new(max_key_size, max_key_size);
+        drop(guard);
+        Ok(Self::attach(index_name, header_page_id, bpm, max_key_size))
+    }
+
+    /// Reattaches to a tree made by `new` with same `header_page_id`. `NotAnIndexPage` if `header_page_id`
+    /// holds no tree header.
+    pub fn open(
+        index_name: String,
+        header_page_id: usize,
+        bpm: Arc<BufferPoolManager>,
+    ) -> Result<Self, IndexError> {
+        let guard = bpm.read_page(header_page_id)?;
+        let header = *guard.get_readable_data_as::<BplusTreeHeaderPage>();
+        drop(guard);
+        if !header.is_valid() {
+            return Err(IndexError::NotAnIndexPage(header_page_id));
+        }
+        Ok(Self::attach(
+            index_name,
+            header_page_id,
+            bpm,
+            header.leaf_max_size(),
+        ))
+    }
+
+    fn attach(
+        index_name: String,
+        header_page_id: usize,
+        bpm: Arc<BufferPoolManager>,
+        max_key_size: u32,
+    ) -> Self {
+        // every lookup starts at header page. It is only a hint, tree works the same without it.
+        let _ = bpm.set_residency(header_page_id, Residency::KeepWarm);
+        Self {
+            index_name,
+            bpm,
+            max_key_size,
+            header_page_id,
+            v: PhantomData,
+        }
+    }
+
+    pub fn max_key_size(&self) -> u32 {
+        self.max_key_size
+    }
+
+    pub fn is_empty(&self) -> Result<bool, BufferPoolError> {
+        let guard = self.bpm.read_page(self.header_page_id)?;
+        let header = guard.get_readable_data_as::<BplusTreeHeaderPage>();
+        Ok(header.root_page_id().is_none())
+    }
+
+    /// Value of `key`, None if it is not in tree.
+    pub fn get_value(&self, key: &[u8]) -> Result<Option<ValueType>, BufferPoolError> {
+        let Some(guard) = self.read_leaf(key)? else {
+            return Ok(None);
+        };
+        let leaf = BPlusTreeVarLeafPage::<ValueType, _>::new(guard.get_read_guard());
+        Ok(leaf
+            .lookup(key, |_| Ordering::Equal)
+            .ok()
+            .map(|index| *leaf.value(index)))
+    }
+
+    /// Insert a key value pair.
+    /// @return : false if key is already in tree, it keeps the value it had then
+    ///
+    /// Panics if key is over max key size. An error from buffer pool in the middle of a split may leave tree
+    /// broken.
+    pub fn insert(&self, key: &[u8], value: ValueType) -> Result<bool, BufferPoolError> {
+        assert!(
+            key.len() <= self.max_key_size as usize,
+            "key of {} bytes is over max key size {}",
+            key.len(),
+            self.max_key_size
+        );
+        let mut header_guard = self.bpm.write_page(self.header_page_id)?;
+        let header = *header_guard.get_readable_data_as::<BplusTreeHeaderPage>();
+        let Some(root_page_id) = header.root_page_id() else {
+            let page_id = self.bpm.new_page_id();
+            let mut guard = self.bpm.write_page(page_id)?;
+            let mut leaf = BPlusTreeVarLeafPage::init(
+                guard.get_write_guard(),
+                self.max_key_size,
+                INVALID_PAGE_ID,
+            );
+            assert!(leaf.insert(0, key, value));
+            set_root(&mut header_guard, Some(page_id), 0);
+            return Ok(true);
+        };
+
+        let (path, page_id) = self.write_path(root_page_id, header.height(), key)?;
+        let mut guard = self.bpm.write_page(page_id)?;
+        let mut leaf = BPlusTreeVarLeafPage::new_mut(guard.get_write_guard());
+        let Err(index) = leaf.lookup(key, |_| Ordering::Equal) else {
+            return Ok(false);
+        };
+        if leaf.insert(index, key, value) {
+            return Ok(true);
+        }
+
+        // new page, and leaf after whose previous page id changes, are latched before leaf is touched. Failing
+        // to get either leaves tree as it was.
+        let right_page_id = self.bpm.new_page_id();
+        let mut right_guard = self.bpm.write_page(right_page_id)?;
+        let mut next_guard = self.write_next_leaf(leaf.next_page_id())?;
+        let mut right = BPlusTreeVarLeafPage::init(
+            right_guard.get_write_guard(),
+            self.max_key_size,
+            INVALID_PAGE_ID,
+        );
+        leaf.split_insert(index, key, value, &mut right);
+        leaf.set_next_page_id(right_page_id);
+        right.set_prev_page_id(page_id);
+        if let Some(next_guard) = next_guard.as_mut() {
+            BPlusTreeVarLeafPage::<ValueType, _>::new_mut(next_guard.get_write_guard())
+                .set_prev_page_id(right_page_id);
+        }
+        let separator = shortest_separator(&leaf.key(leaf.len() - 1), &right.key(0)).to_vec();
+        drop((guard, right_guard, next_guard));
+        self.insert_into_parent(&mut header_guard, path, page_id, separator, right_page_id)?;
+        Ok(true)
+    }
+
+    /// Remove a key and its value. Pages merged away are deleted from buffer pool.
+    /// @return : false if key is not in tree
+    ///
+    /// An error from buffer pool in the middle of a merge may leave pages under a quarter full.
+    pub fn remove(&self, key: &[u8]) -> Result<bool, BufferPoolError> {
+        let mut header_guard = self.bpm.write_page(self.header_page_id)?;
+        let header = *header_guard.get_readable_data_as::<BplusTreeHeaderPage>();
+        let Some(root_page_id) = header.root_page_id() else {
+            return Ok(false);
+        };
+
+        let (path, page_id) = self.write_path(root_page_id, header.height(), key)?;
+        let mut guard = self.bpm.write_page(page_id)?;
+        let mut leaf = BPlusTreeVarLeafPage::<ValueType, _>::new_mut(guard.get_write_guard());
+        let Ok(index) = leaf.lookup(key, |_| Ordering::Equal) else {
+            return Ok(false);
+        };
+        leaf.remove(index);
+        if header.height() == 0 {
+            if leaf.is_empty() {
+                set_root(&mut header_guard, None, 0);
+                drop(guard);
+                let _ = self.free_page(page_id)?;
+            }
+            return Ok(true);
+        }
+        if is_underfull(leaf.used_space()) {
+            self.merge_leaf(&mut header_guard, path, guard)?;
+        }
+        Ok(true)
+    }
+
+    /// Pages tree takes and entries they hold, counted level by level. Header page is read latched
+    /// throughout, so counts are those of tree at one point, changes wait for them.
+    pub fn fan_out(&self) -> Result<FanOut, BufferPoolError> {
+        let header_guard = self.bpm.read_page(self.header_page_id)?;
+        let header = *header_guard.get_readable_data_as::<BplusTreeHeaderPage>();
+        let mut level = header.root_page_id().into_iter().collect::<Vec<_>>();
+        let mut internal_pages = 0;
+        for _ in 0..header.height() {
+            let mut children = vec![];
+            for &page_id in &level {
+                let guard = self.bpm.read_page(page_id)?;
+                let page = InternalPage::new(guard.get_read_guard());
+                children.extend((0..page.len()).map(|i| *page.value(i)));
+            }
+            internal_pages += level.len();
+            level = children;
+        }
+        let mut entries = 0;
+        for &page_id in &level {
+            let guard = self.bpm.read_page(page_id)?;
+            entries += BPlusTreeVarLeafPage::<ValueType, _>::new(guard.get_read_guard()).len();
+        }
+        Ok(FanOut {
+            entries,
+            leaf_pages: level.len(),
+            internal_pages,
+            height: header.height(),
+        })
+    }
+
+    // Goes down with read latches, each let go once the one below is taken, to leaf which may hold `key`. None
+    // for an empty tree.
+    fn read_leaf(&self, key: &[u8]) -> Result<Option<ReadPageGuard>, BufferPoolError> {
+        let header_guard = self.bpm.read_page(self.header_page_id)?;
+        let header = *header_guard.get_readable_data_as::<BplusTreeHeaderPage>();
+        let Some(page_id) = header.root_page_id() else {
+            return Ok(None);
+        };
+        let mut guard = self.bpm.read_page(page_id)?;
+        drop(header_guard);
+        for _ in 0..header.height() {
+            let page = InternalPage::new(guard.get_read_guard());
+            let child = *page.value(page.child_index(key));
+            guard = self.bpm.read_page(child)?;
+        }
+        Ok(Some(guard))
+    }
+
+    // Write latches internal pages from root down to leaf which may hold `key`, and returns them along with
+    // page id of that leaf.
+    fn write_path(
+        &self,
+        root_page_id: usize,
+        height: u32,
+        key: &[u8],
+    ) -> Result<(Path, usize), BufferPoolError> {
+        let mut path = Path::new();
+        let mut page_id = root_page_id;
+        for _ in 0..height {
+            let mut guard = self.bpm.write_page(page_id)?;
+            let page = InternalPage::new_mut(guard.get_write_guard());
+            let index = page.child_index(key);
+            let child = *page.value(index);
+            path.push((page_id, index, guard));
+            page_id = child;
+        }
+        Ok((path, page_id))
+    }
+
+    // Adds `right`, split off `left` at `key`, next to it in parent. Splits parents as long as key does not
+    // fit, and grows a new root once the old one splits.
+    fn insert_into_parent(
+        &self,
+        header_guard: &mut WritePageGuard,
+        mut path: Path,
+        mut left: usize,
+        mut key: Vec<u8>,
+        mut right: usize,
+    ) -> Result<(), BufferPoolError> {
+        loop {
+            let Some((parent_page_id, index, mut guard)) = path.pop() else {
+                let root_page_id = self.bpm.new_page_id();
+                let mut guard = self.bpm.write_page(root_page_id)?;
+                let mut root = InternalPage::init(guard.get_write_guard(), self.max_key_size);
+                // first key is never looked at, separator fills its slot and keeps prefix of page.
+                assert!(root.insert(0, &key, left));
+                assert!(root.insert(1, &key, right));
+                let height = header_guard
+                    .get_readable_data_as::<BplusTreeHeaderPage>()
+                    .height();
+                set_root(header_guard, Some(root_page_id), height + 1);
+                return Ok(());
+            };
+
+            let mut parent = InternalPage::new_mut(guard.get_write_guard());
+            if parent.insert(index + 1, &key, right) {
+                return Ok(());
+            }
+            let sibling_page_id = self.bpm.new_page_id();
+            let mut sibling_guard = self.bpm.write_page(sibling_page_id)?;
+            let mut sibling =
+                InternalPage::init(sibling_guard.get_write_guard(), self.max_key_size);
+            parent.split_insert(index + 1, &key, right, &mut sibling);
+            (left, key, right) = (parent_page_id, sibling.key(0), sibling_page_id);
+        }
+    }
+
+    // Merges leaf of `guard` with a sibling under same parent, last page of `path`, if they fit in one leaf. A
+    // parent left with a single child, its sibling too full to take it, has no sibling to merge it with.
+    fn merge_leaf(
+        &self,
+        header_guard: &mut WritePageGuard,
+        mut path: Path,
+        guard: WritePageGuard,
+    ) -> Result<(), BufferPoolError> {
+        let (parent_page_id, index, mut parent_guard) = path.pop().unwrap();
+        let mut parent = InternalPage::new_mut(parent_guard.get_write_guard());
+        if parent.len() == 1 {
+            return Ok(());
+        }
+        let (left_index, mut left_guard, mut right_guard) =
+            self.latch_siblings(&parent, index, guard)?;
+        let (left_page_id, right_page_id) =
+            (*parent.value(left_index), *parent.value(left_index + 1));
+        let mut left = BPlusTreeVarLeafPage::<ValueType, _>::new_mut(left_guard.get_write_guard());
+        let mut right = BPlusTreeVarLeafPage::new_mut(right_guard.get_write_guard());
+
+        let mut next_guard = self.write_next_leaf(right.next_page_id())?;
+        if !left.append(&mut right) {
+            return Ok(());
+        }
+        if let Some(next_guard) = next_guard.as_mut() {
+            BPlusTreeVarLeafPage::<ValueType, _>::new_mut(next_guard.get_write_guard())
+                .set_prev_page_id(left_page_id);
+        }
+        parent.remove(left_index + 1);
+        drop((left_guard, right_guard, next_guard));
+        let _ = self.free_page(right_page_id)?;
+        self.merge_internal(header_guard, path, parent_page_id, parent_guard)
+    }
+
+    // Page at `page_id` just lost a child. Merges it with a sibling if it fell under a quarter full and both
+    // fit in one page, then goes on with their parent. A root left with a single child is dropped, that child
+    // becomes root.
+    fn merge_internal(
+        &self,
+        header_guard: &mut WritePageGuard,
+        mut path: Path,
+        mut page_id: usize,
+        mut guard: WritePageGuard,
+    ) -> Result<(), BufferPoolError> {
+        loop {
+            let page = InternalPage::new_mut(guard.get_write_guard());
+            let Some((parent_page_id, index, mut parent_guard)) = path.pop() else {
+                if page.len() > 1 {
+                    return Ok(());
+                }
+                let height = header_guard
+                    .get_readable_data_as::<BplusTreeHeaderPage>()
+                    .height();
+                set_root(header_guard, Some(*page.value(0)), height - 1);
+                drop(guard);
+                let _ = self.free_page(page_id)?;
+                return Ok(());
+            };
+            if !is_underfull(page.used_space()) {
+                return Ok(());
+            }
+
+            let mut parent = InternalPage::new_mut(parent_guard.get_write_guard());
+            if parent.len() == 1 {
+                return Ok(());
+            }
+            let (left_index, mut left_guard, mut right_guard) =
+                self.latch_siblings(&parent, index, guard)?;
+            let right_page_id = *parent.value(left_index + 1);
+            let mut left = InternalPage::new_mut(left_guard.get_write_guard());
+            let mut right = InternalPage::new_mut(right_guard.get_write_guard());
+            // key in parent separating the two moves down, in place of invalid first key of right.
+            if !left.append(&parent.key(left_index + 1), &mut right) {
+                return Ok(());
+            }
+            parent.remove(left_index + 1);
+            drop((left_guard, right_guard));
+            let _ = self.free_page(right_page_id)?;
+            (page_id, guard) = (parent_page_id, parent_guard);
+        }
+    }
+
+    // Write latches child at `index` of `parent` along with a sibling, left one preferred, and returns index
+    // of the left one of the two. `guard` is latch already held on child. Siblings are latched left to right,
+    // as `BPlusTree` does.
+    fn latch_siblings(
+        &self,
+        parent: &InternalPage<&mut FrameHeader>,
+        index: usize,
+        guard: WritePageGuard,
+    ) -> Result<(usize, WritePageGuard, WritePageGuard), BufferPoolError> {
+        if index == 0 {
+            let right_guard = self.bpm.write_page(*parent.value(1))?;
+            return Ok((0, guard, right_guard));
+        }
+        drop(guard);
+        let left_guard = self.bpm.write_page(*parent.value(index - 1))?;
+        let right_guard = self.bpm.write_page(*parent.value(index))?;
+        Ok((index - 1, left_guard, right_guard))
+    }
+
+    // Write latches leaf at `next_page_id`, whose previous page id is about to change, if there is one.
+    fn write_next_leaf(
+        &self,
+        next_page_id: usize,
+    ) -> Result<Option<WritePageGuard>, BufferPoolError> {
+        if next_page_id == INVALID_PAGE_ID {
+            return Ok(None);
+        }
+        Ok(Some(self.bpm.write_page(next_page_id)?))
+    }
+
+    // Deletes a page no longer reachable from tree. False if someone outside tree still has it pinned.
+    #[must_use = "a page left pinned is not deleted"]
+    fn free_page(&self, page_id: usize) -> Result<bool, BufferPoolError> {
+        self.bpm.delete_page(page_id)
+    }
+}
+
+// A page under a quarter full is merged with a sibling. Merging at half full would leave pages which split
+// again on the next few inserts.
+fn is_underfull(used_space: usize) -> bool {
+    used_space < PAGE_SIZE / 4
+}
+
+fn set_root(header_guard: &mut WritePageGuard, root_page_id: Option<usize>, height: u32) {
+    header_guard
+        .get_writeable_data_as::<BplusTreeHeaderPage>()
+        .set_root(root_page_id, height);
+}
+
+#[cfg(test)]
+#[allow(clippy::bool_assert_comparison)]
+mod test {
+    use std::sync::Arc;
+
+    use common::RID;
+    use storage::{
+        BPlusTreeVarLeafPage, BplusTreeHeaderPage, FanOut, MemoryManager, INVALID_PAGE_ID,
+    };
+
+    use crate::BufferPoolManager;
+
+    use super::{InternalPage, VarBPlusTree};
+
+    type Tree = VarBPlusTree<RID>;
+
+    fn tree(bpm: &Arc<BufferPoolManager>, max_key_size: u32) -> Tree {
+        Tree::new(
+            "url_idx".into(),
+            bpm.new_page_id(),
+            bpm.clone(),
+            max_key_size,
+        )
+        .unwrap()
+    }
+
+    // Walks whole tree checking keys are in order and within bounds internal pages set. Returns entries in leaf
+    // chain order.
+    fn check_tree(tree: &Tree) -> Vec<(Vec<u8>, RID)> {
+        let guard = tree.bpm.read_page(tree.header_page_id).unwrap();
+        let header = *guard.get_readable_data_as::<BplusTreeHeaderPage>();
+        drop(guard);
+        let Some(root_page_id) = header.root_page_id() else {
+            return vec![];
+        };
+        let mut leaves = vec![];
+        check_subtree(
+            tree,
+            root_page_id,
+            header.height(),
+            (None, None),
+            &mut leaves,
+        );
+
+        let mut entries = vec![];
+        for (i, &page_id) in leaves.iter().enumerate() {
+            let guard = tree.bpm.read_page(page_id).unwrap();
+            let leaf = BPlusTreeVarLeafPage::<RID, _>::new(guard.get_read_guard());
+            let next_page_id = leaves.get(i + 1).copied().unwrap_or(INVALID_PAGE_ID);
+            assert_eq!(next_page_id, leaf.next_page_id(), "leaf {page_id}");
+            let prev_page_id = i.checked_sub(1).map_or(INVALID_PAGE_ID, |i| leaves[i]);
+            assert_eq!(prev_page_id, leaf.prev_page_id(), "leaf {page_id}");
+            entries.extend((0..leaf.len()).map(|i| (leaf.key(i), *leaf.value(i))));
+        }
+        assert!(entries.windows(2).all(|it| it[0].0 < it[1].0));
+        entries
+    }
+
+    // Keys in subtree must fall in `bounds`, lower bound included.
+    fn check_subtree(
+        tree: &Tree,
+        page_id: usize,
+        level: u32,
+        bounds: (Option<Vec<u8>>, Option<Vec<u8>>),
+        leaves: &mut Vec<usize>,
+    ) {
+        let in_bounds = |key: &Vec<u8>| {
+            bounds.0.as_ref().is_none_or(|lower| lower <= key)
+                && bounds.1.as_ref().is_none_or(|upper| key < upper)
+        };
+        let guard = tree.bpm.read_page(page_id).unwrap();
+        if level == 0 {
+            let leaf = BPlusTreeVarLeafPage::<RID, _>::new(guard.get_read_guard());
+            assert!(
+                (0..leaf.len()).all(|i| in_bounds(&leaf.key(i))),
+                "leaf {page_id} out of bounds"
+            );
+            leaves.push(page_id);
+            return;
+        }
+
+        let page = InternalPage::new(guard.get_read_guard());
+        assert!(!page.is_empty(), "page {page_id} has no child");
+        let separators = (1..page.len()).map(|i| page.key(i)).collect::<Vec<_>>();
+        let children = (0..page.len()).map(|i| *page.value(i)).collect::<Vec<_>>();
+        drop(guard);
+        assert!(
+            separators.iter().all(in_bounds),
+            "page {page_id} out of bounds"
+        );
+        assert!(separators.windows(2).all(|it| it[0] < it[1]));
+        for (i, child) in children.into_iter().enumerate() {
+            let lower = if i == 0 {
+                bounds.0.clone()
+            } else {
+                Some(separators[i - 1].clone())
+            };
+            let upper = separators.get(i).cloned().or(bounds.1.clone());
+            check_subtree(tree, child, level - 1, (lower, upper), leaves);
+        }
+    }
+
+    #[test]
+    fn insert_remove_test() {
+        let bpm = Arc::new(BufferPoolManager::new(
+            50,
+            10,
+            Box::new(MemoryManager::new(10000)),
+        ));
+        let tree = tree(&bpm, 64);
+        assert_eq!(true, tree.is_empty().unwrap());
+
+        // keys go in scrambled, every other key is left out. A few keys share no prefix with the rest.
+        let key = |i: usize| match i % 500 {
+            0 => format!("{i}"),
+            _ => format!("customer/{i:06}/orders"),
+        };
+        let rid = |i: usize| RID::new(i, i as u32);
+        let ids = (0..20000)
+            .map(|i| (i * 7919) % 20000 * 2)
+            .collect::<Vec<_>>();
+        for &i in &ids {
+            assert_eq!(true, tree.insert(key(i).as_bytes(), rid(i)).unwrap());
+        }
+        assert_eq!(false, tree.insert(key(ids[0]).as_bytes(), rid(1)).unwrap());
+        assert_eq!(
+            Some(rid(ids[0])),
+            tree.get_value(key(ids[0]).as_bytes()).unwrap()
+        );
+        assert_eq!(true, tree.fan_out().unwrap().height >= 1);
+
+        let mut expected = ids
+            .iter()
+            .map(|&i| (key(i).into_bytes(), rid(i)))
+            .collect::<Vec<_>>();
+        expected.sort();
+        assert_eq!(expected, check_tree(&tree));
+        for i in 0..40000 {
+            let value = tree.get_value(key(i).as_bytes()).unwrap();
+            assert_eq!((i % 2 == 0).then(|| rid(i)), value, "key {}", key(i));
+        }
+
+        // tree comes back whole from its header page.
+        let reopened = Tree::open("url_idx".into(), tree.header_page_id, bpm.clone()).unwrap();
+        assert_eq!(64, reopened.max_key_size());
+        assert_eq!(
+            Some(rid(ids[1])),
+            reopened.get_value(key(ids[1]).as_bytes()).unwrap()
+        );
+
+        let pages = |tree: &Tree| {
+            let it = tree.fan_out().unwrap();
+            it.leaf_pages + it.internal_pages
+        };
+        let full = pages(&tree);
+        for (n, &i) in ids.iter().rev().enumerate() {
+            assert_eq!(true, tree.remove(key(i).as_bytes()).unwrap());
+            assert_eq!(false, tree.remove(key(i).as_bytes()).unwrap());
+            if n == ids.len() * 7 / 8 {
+                assert_eq!(ids.len() - n - 1, check_tree(&tree).len());
+                // leaves falling under a quarter full are merged.
+                assert_eq!(true, pages(&tree) < full / 2);
+            }
+        }
+        assert_eq!(true, tree.is_empty().unwrap());
+        assert_eq!(
+            FanOut {
+                entries: 0,
+                leaf_pages: 0,
+                internal_pages: 0,
+                height: 0
+            },
+            tree.fan_out().unwrap()
+        );
+        assert_eq!(0, bpm.stats().pinned_frames);
+    }
+
+    #[test]
+    #[should_panic(expected = "key of 9 bytes is over max key size 8")]
+    fn insert_too_long_key_panics() {
+        let bpm = Arc::new(BufferPoolManager::new(
+            10,
+            2,
+            Box::new(MemoryManager::new(100)),
+        ));
+        tree(&bpm, 8).insert(b"customers", RID::new(0, 0)).unwrap();
+    }
+
+    #[test]
+    fn fan_out_string_keys_test() {
+        let bpm = Arc::new(BufferPoolManager::new(
+            50,
+            10,
+            Box::new(MemoryManager::new(10000)),
+        ));
+        let tree = tree(&bpm, 64);
+        let count = 50_000;
+        for i in (0..count).map(|i| (i * 7919) % count) {
+            let key = format!("https://example.com/customers/{i:06}/orders");
+            assert_eq!(true, tree.insert(key.as_bytes(), RID::new(i, 0)).unwrap());
+        }
+        let var = tree.fan_out().unwrap();
+        // a varchar(64) key takes all of its 64 bytes when width is fixed, even with every page packed full.
+        let fixed = FanOut::fixed::<[u8; 64], RID>(count);
+
+        assert_eq!((count, count), (fixed.entries, var.entries));
+        assert_eq!(
+            true,
+            var.leaf_fan_out() > 1.5 * fixed.leaf_fan_out(),
+            "{var}, {fixed}"
+        );
+        // separators are cut down to a few bytes past prefix of page.
+        assert_eq!(
+            true,
+            var.internal_pages < fixed.internal_pages / 2,
+            "{var}, {fixed}"
+        );
+    }
+}
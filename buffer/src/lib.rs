@@ -1,4 +1,13 @@
 mod buffer_pool_manager;
+mod buffer_pools;
+mod error;
 mod index;
 mod lruk_replacer;
+mod stats;
+mod warm_up;
 pub use buffer_pool_manager::*;
+pub use buffer_pools::*;
+pub use error::*;
+pub use index::{GenericComparator, GenericKey, IndexError, KeyComparator, VarBPlusTree};
+pub use lruk_replacer::{ReplacementPolicy, Residency};
+pub use stats::*;
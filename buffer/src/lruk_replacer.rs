@@ -1,10 +1,12 @@
 #![allow(dead_code)]
 
 use std::collections::HashMap;
-use std::sync::atomic::{AtomicU32, Ordering};
+use std::sync::atomic::{AtomicU32, AtomicUsize, Ordering};
 use std::sync::{Arc, Mutex};
 use std::time::{SystemTime, UNIX_EPOCH};
 
+use storage::Frame;
+
 pub enum AccessType {
     Unknown,
     Lookup,
@@ -12,6 +14,28 @@ pub enum AccessType {
     Index,
 }
 
+/// How replacer of a buffer pool picks a frame to evict. Residency of pages is honoured by either policy.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ReplacementPolicy {
+    /// Frame with largest backward k-distance, see `LruKReplacer::evict`. `LruK(1)` is plain LRU.
+    LruK(usize),
+    /// Frame whose page was brought in first, however often it was accessed since. A page read once by a scan
+    /// goes out before one it came after, whatever scan did with the latter.
+    Fifo,
+}
+
+/// How hard replacer tries to keep a page in buffer pool, see `BufferPoolManager::set_residency`.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+pub enum Residency {
+    /// Evicted by replacement policy of its pool, like any other page.
+    #[default]
+    Normal,
+    /// Only evicted when no normal page can be.
+    KeepWarm,
+    /// Never picked for eviction. Pages held this way count against buffer pool's resident budget.
+    NeverEvict,
+}
+
 /// Each not in LruKCache has K last access history.
 /// It also has info about which frame.
 struct LruNode {
@@ -19,6 +43,7 @@ struct LruNode {
     frame_id: usize,
     k: usize,
     evictable: bool,
+    residency: Residency,
 }
 
 impl LruNode {
@@ -28,6 +53,7 @@ impl LruNode {
             frame_id,
             k,
             evictable: false,
+            residency: Residency::default(),
         }
     }
 
@@ -40,21 +66,38 @@ impl LruNode {
         let nanos = since_epoch.as_secs() * 1_000_000_000 + since_epoch.subsec_nanos() as u64;
         self.history.push(nanos);
     }
+
+    // Whether replacer can pick this frame, what `LruKReplacer::size` counts.
+    fn can_be_evicted(&self) -> bool {
+        self.evictable && self.residency != Residency::NeverEvict
+    }
 }
 
 pub(super) struct LruKReplacer {
-    size: usize,
+    // number of frames, frame ids run from 0 to size - 1.
+    size: AtomicUsize,
     node_store: Arc<Mutex<HashMap<usize, LruNode>>>,
     k: usize,
+    // only first access of a frame is kept, so it is evicted in order pages were brought in.
+    fifo: bool,
     current_size: AtomicU32,
 }
 
 impl LruKReplacer {
     pub(super) fn new(size: usize, k: usize) -> Self {
+        Self::with_policy(size, ReplacementPolicy::LruK(k))
+    }
+
+    pub(super) fn with_policy(size: usize, policy: ReplacementPolicy) -> Self {
+        let (k, fifo) = match policy {
+            ReplacementPolicy::LruK(k) => (k, false),
+            ReplacementPolicy::Fifo => (1, true),
+        };
         Self {
-            size,
+            size: AtomicUsize::new(size),
             node_store: Arc::default(),
             k,
+            fifo,
             current_size: AtomicU32::new(0),
         }
     }
@@ -66,7 +109,9 @@ impl LruKReplacer {
         self.panic_if_not_valid_frame_id(frame_id);
         let mut guard = self.node_store.lock().unwrap();
         if let Some(node) = guard.get_mut(&frame_id) {
-            node.add_history();
+            if !self.fifo || node.history.is_empty() {
+                node.add_history();
+            }
             return;
         }
 
@@ -75,7 +120,8 @@ impl LruKReplacer {
         guard.insert(frame_id, node);
     }
 
-    /// Number of evictable frame.
+    /// Number of frames which can be evicted. Evictable frames holding a `Residency::NeverEvict` page are left
+    /// out, they are never picked.
     pub(super) fn size(&self) -> usize {
         self.current_size.load(Ordering::SeqCst) as usize
     }
@@ -94,13 +140,27 @@ impl LruKReplacer {
         let Some(node) = guard.get_mut(&frame_id) else {
             return;
         };
+        self.change(node, |node| node.evictable = is_evictable);
+    }
 
-        if node.evictable && !is_evictable {
-            node.evictable = false;
-            self.current_size.fetch_sub(1, Ordering::SeqCst);
-        } else if !node.evictable && is_evictable {
-            node.evictable = true;
-            self.current_size.fetch_add(1, Ordering::SeqCst);
+    /// Mark frame evictable, but only if it is still unpinned once replacer lock is held.
+    ///
+    /// Pinning a frame bumps its pin count before calling `set_evictable(frame_id, false)`. So checking
+    /// pin count under replacer lock is enough to make sure a frame pinned in between is not left evictable.
+    ///
+    /// Frames beyond replacer size are being retired by a shrinking buffer pool, they are left alone.
+    pub(super) fn set_evictable_if_unpinned(&self, frame: &Frame) {
+        let frame_id = frame.frame_id();
+        let mut guard = self.node_store.lock().unwrap();
+        if frame_id >= self.size.load(Ordering::SeqCst) {
+            return;
+        }
+        let Some(node) = guard.get_mut(&frame_id) else {
+            return;
+        };
+
+        if frame.pin_count() == 0 {
+            self.change(node, |node| node.evictable = true);
         }
     }
 
@@ -114,8 +174,10 @@ impl LruKReplacer {
     /// If specified frame is not found, directly return from this function.
     pub(super) fn remove(&self, frame_id: usize) {
         let mut guard = self.node_store.lock().unwrap();
-        if let Some(_) = guard.remove(&frame_id) {
-            self.current_size.fetch_sub(1, Ordering::SeqCst);
+        if let Some(node) = guard.remove(&frame_id) {
+            if node.can_be_evicted() {
+                self.current_size.fetch_sub(1, Ordering::SeqCst);
+            }
         }
     }
 
@@ -128,42 +190,117 @@ impl LruKReplacer {
     ///
     /// Successful eviction of a frame should decrement the size of replacer and remove the frame's access history.
     ///
+    /// Residency of a frame comes before its k-distance, see `Residency`.
     pub(super) fn evict(&self) -> Option<usize> {
         let mut guard = self.node_store.lock().unwrap();
+        // keep warm frames are only looked at once no normal frame is left, never evict ones not at all.
+        let frame_id = [Residency::Normal, Residency::KeepWarm]
+            .into_iter()
+            .find_map(|residency| self.pick_victim(&guard, residency))?;
+        guard.remove(&frame_id);
+        self.current_size.fetch_sub(1, Ordering::SeqCst);
+        Some(frame_id)
+    }
+
+    /// Attach residency to a frame, it is dropped along with the rest of frame's history on eviction.
+    pub(super) fn set_residency(&self, frame_id: usize, residency: Residency) {
+        self.panic_if_not_valid_frame_id(frame_id);
+        let mut guard = self.node_store.lock().unwrap();
+        let node = guard
+            .entry(frame_id)
+            .or_insert_with(|| LruNode::new(frame_id, self.k));
+        self.change(node, |node| node.residency = residency);
+    }
+
+    // Applies `f` to node, keeping count of frames which can be evicted in step with it.
+    fn change(&self, node: &mut LruNode, f: impl FnOnce(&mut LruNode)) {
+        let before = node.can_be_evicted();
+        f(node);
+        match (before, node.can_be_evicted()) {
+            (true, false) => {
+                self.current_size.fetch_sub(1, Ordering::SeqCst);
+            }
+            (false, true) => {
+                self.current_size.fetch_add(1, Ordering::SeqCst);
+            }
+            _ => {}
+        }
+    }
+
+    // Evictable frame with given residency and largest backward k-distance.
+    fn pick_victim(&self, nodes: &HashMap<usize, LruNode>, residency: Residency) -> Option<usize> {
         let mut less_than_k: Option<(usize, u64)> = None;
         let mut k_history: Option<(usize, u64)> = None;
-        for (k, v) in guard.iter() {
-            if !v.evictable {
+        for (k, v) in nodes.iter() {
+            if !v.evictable || v.residency != residency {
                 continue;
             }
+            // a frame given a residency before its first access counts as oldest.
+            let first = v.history.first().copied().unwrap_or_default();
 
             if v.history.len() < self.k {
-                if less_than_k.map(|it| it.1.gt(&v.history[0])).unwrap_or(true) {
-                    less_than_k = Some((k.clone(), v.history[0].clone()));
+                if less_than_k.map(|it| it.1.gt(&first)).unwrap_or(true) {
+                    less_than_k = Some((*k, first));
                 }
-            } else if less_than_k.is_none() {
-                if k_history
-                    .map(|it| it.1.gt(&v.history.last().unwrap()))
+            } else if less_than_k.is_none()
+                && k_history
+                    .map(|it| it.1.gt(v.history.last().unwrap()))
                     .unwrap_or(true)
-                {
-                    k_history = Some((k.clone(), v.history.last().unwrap().clone()));
-                }
+            {
+                k_history = Some((*k, *v.history.last().unwrap()));
             }
         }
-        if let Some(frame_id) = less_than_k
+        less_than_k
             .map(|it| it.0)
             .or_else(|| k_history.map(|it| it.0))
-        {
-            guard.remove(&frame_id);
-            self.current_size.fetch_sub(1, Ordering::SeqCst);
-            return Some(frame_id);
+    }
+
+    /// Add an unpinned frame with access history carried over from an earlier run, oldest first.
+    /// Only last k accesses are kept. Frame is evictable right away.
+    pub(super) fn restore_history(&self, frame_id: usize, history: Vec<u64>) {
+        self.panic_if_not_valid_frame_id(frame_id);
+        let mut guard = self.node_store.lock().unwrap();
+        let mut node = LruNode::new(frame_id, self.k);
+        let skip = history.len().saturating_sub(self.k);
+        node.history.extend(history.into_iter().skip(skip));
+        node.evictable = true;
+        if let Some(old) = guard.insert(frame_id, node) {
+            if old.can_be_evicted() {
+                return;
+            }
         }
+        self.current_size.fetch_add(1, Ordering::SeqCst);
+    }
+
+    /// Change number of frames. Frames beyond new size are dropped along with their access history.
+    pub(super) fn set_size(&self, size: usize) {
+        let mut guard = self.node_store.lock().unwrap();
+        self.size.store(size, Ordering::SeqCst);
+        guard.retain(|frame_id, node| {
+            if *frame_id < size {
+                return true;
+            }
+            if node.can_be_evicted() {
+                self.current_size.fetch_sub(1, Ordering::SeqCst);
+            }
+            false
+        });
+    }
 
-        None
+    /// Evictable flag, residency and access history, oldest first, of every frame replacer knows about.
+    pub(super) fn snapshot(&self) -> HashMap<usize, (bool, Residency, Vec<u64>)> {
+        let guard = self.node_store.lock().unwrap();
+        guard
+            .iter()
+            .map(|(frame_id, node)| {
+                let state = (node.evictable, node.residency, node.history.clone());
+                (*frame_id, state)
+            })
+            .collect()
     }
 
     fn panic_if_not_valid_frame_id(&self, frame_id: usize) {
-        if self.size < frame_id {
+        if frame_id >= self.size.load(Ordering::SeqCst) {
             panic!("Invalid frame id {}.", frame_id);
         }
     }
@@ -171,7 +308,7 @@ impl LruKReplacer {
 
 #[cfg(test)]
 mod test {
-    use crate::lruk_replacer::LruKReplacer;
+    use crate::lruk_replacer::{LruKReplacer, ReplacementPolicy, Residency};
 
     #[test]
     fn sample_test() {
@@ -261,4 +398,81 @@ mod test {
         replacer.set_evictable(6, false);
         replacer.set_evictable(6, true);
     }
+
+    #[test]
+    fn residency_test() {
+        let replacer = LruKReplacer::new(4, 2);
+        for i in 0..4 {
+            replacer.record_access(i, None);
+            replacer.set_evictable(i, true);
+        }
+        replacer.set_residency(0, Residency::NeverEvict);
+        replacer.set_residency(1, Residency::KeepWarm);
+        assert_eq!(3, replacer.size());
+
+        // normal frames go first, oldest first.
+        assert_eq!(2, replacer.evict().unwrap());
+        assert_eq!(3, replacer.evict().unwrap());
+        assert_eq!(1, replacer.evict().unwrap());
+        assert_eq!(None, replacer.evict());
+        // never evict frame is evictable, but can not be picked so it is not counted.
+        assert_eq!(0, replacer.size());
+        replacer.set_evictable(0, false);
+        replacer.set_evictable(0, true);
+        assert_eq!(0, replacer.size());
+
+        replacer.set_residency(0, Residency::Normal);
+        assert_eq!(1, replacer.size());
+        assert_eq!(0, replacer.evict().unwrap());
+        assert_eq!(0, replacer.size());
+    }
+
+    #[test]
+    fn fifo_test() {
+        let replacer = LruKReplacer::with_policy(3, ReplacementPolicy::Fifo);
+        for i in 0..3 {
+            replacer.record_access(i, None);
+            replacer.set_evictable(i, true);
+        }
+        // accesses after first one do not matter.
+        replacer.record_access(0, None);
+        replacer.record_access(0, None);
+        assert_eq!(0, replacer.evict().unwrap());
+
+        replacer.record_access(0, None);
+        replacer.set_evictable(0, true);
+        replacer.record_access(1, None);
+        assert_eq!(1, replacer.evict().unwrap());
+        assert_eq!(2, replacer.evict().unwrap());
+        assert_eq!(0, replacer.evict().unwrap());
+    }
+
+    #[test]
+    fn set_size_test() {
+        let replacer = LruKReplacer::new(4, 2);
+        for i in 0..4 {
+            replacer.record_access(i, None);
+            replacer.set_evictable(i, true);
+        }
+        assert_eq!(4, replacer.size());
+
+        replacer.set_size(2);
+        assert_eq!(2, replacer.size());
+        assert_eq!(0, replacer.evict().unwrap());
+        assert_eq!(1, replacer.evict().unwrap());
+        assert_eq!(None, replacer.evict());
+
+        replacer.set_size(3);
+        replacer.record_access(2, None);
+        replacer.set_evictable(2, true);
+        assert_eq!(2, replacer.evict().unwrap());
+    }
+
+    #[test]
+    #[should_panic]
+    fn frame_id_beyond_size_test() {
+        let replacer = LruKReplacer::new(4, 2);
+        replacer.set_size(2);
+        replacer.record_access(2, None);
+    }
 }
@@ -0,0 +1,110 @@
+use std::{
+    ops::AddAssign,
+    sync::atomic::{AtomicU64, Ordering},
+};
+
+use crate::Residency;
+
+/// Point in time view of buffer pool, see `BufferPoolManager::stats`.
+///
+/// Counters add up since buffer pool was created. Frame counts are read frame by frame while the pool keeps
+/// running, so they may not add up to `num_frames` under load.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct BufferPoolStats {
+    /// Fetches which found page already in buffer pool.
+    pub hits: u64,
+    /// Fetches which had to bring page in a frame.
+    pub misses: u64,
+    /// Pages evicted to make room for another page.
+    pub evictions: u64,
+    /// Evicted pages which had to be written back first.
+    pub dirty_write_backs: u64,
+    pub disk_reads: u64,
+    /// Includes write backs on eviction and flushes.
+    pub disk_writes: u64,
+    pub num_frames: usize,
+    pub pinned_frames: usize,
+    pub dirty_frames: usize,
+    pub free_frames: usize,
+}
+
+// used to add up stats of several pools, see `BufferPools::stats`.
+impl AddAssign for BufferPoolStats {
+    fn add_assign(&mut self, other: Self) {
+        self.hits += other.hits;
+        self.misses += other.misses;
+        self.evictions += other.evictions;
+        self.dirty_write_backs += other.dirty_write_backs;
+        self.disk_reads += other.disk_reads;
+        self.disk_writes += other.disk_writes;
+        self.num_frames += other.num_frames;
+        self.pinned_frames += other.pinned_frames;
+        self.dirty_frames += other.dirty_frames;
+        self.free_frames += other.free_frames;
+    }
+}
+
+/// State of a single frame, see `BufferPoolManager::frame_stats`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FrameStats {
+    pub frame_id: usize,
+    /// None if frame is free.
+    pub page_id: Option<usize>,
+    pub pin_count: u16,
+    pub is_dirty: bool,
+    pub evictable: bool,
+    /// Residency of page held, as told to replacer.
+    pub residency: Residency,
+    /// Last k access timestamps kept by replacer, in nanoseconds since unix epoch, oldest first.
+    pub history: Vec<u64>,
+}
+
+// Counters are only ever added to, nothing is ordered by them, so relaxed ordering is enough.
+#[derive(Default)]
+pub(crate) struct Counters {
+    hits: AtomicU64,
+    misses: AtomicU64,
+    evictions: AtomicU64,
+    dirty_write_backs: AtomicU64,
+    disk_reads: AtomicU64,
+    disk_writes: AtomicU64,
+}
+
+impl Counters {
+    pub(crate) fn hit(&self) {
+        self.hits.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub(crate) fn miss(&self) {
+        self.misses.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub(crate) fn eviction(&self) {
+        self.evictions.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub(crate) fn dirty_write_back(&self) {
+        self.dirty_write_backs.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub(crate) fn disk_read(&self) {
+        self.disk_reads.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub(crate) fn disk_write(&self) {
+        self.disk_writes.fetch_add(1, Ordering::Relaxed);
+    }
+
+    // frame counts are left for caller to fill in.
+    pub(crate) fn snapshot(&self) -> BufferPoolStats {
+        BufferPoolStats {
+            hits: self.hits.load(Ordering::Relaxed),
+            misses: self.misses.load(Ordering::Relaxed),
+            evictions: self.evictions.load(Ordering::Relaxed),
+            dirty_write_backs: self.dirty_write_backs.load(Ordering::Relaxed),
+            disk_reads: self.disk_reads.load(Ordering::Relaxed),
+            disk_writes: self.disk_writes.load(Ordering::Relaxed),
+            ..Default::default()
+        }
+    }
+}
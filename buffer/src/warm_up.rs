@@ -0,0 +1,114 @@
+use std::{
+    fs,
+    io::{self, BufRead, BufReader, BufWriter, Write},
+    path::Path,
+};
+
+/// What buffer pool needs to come back warm after a restart, see `BufferPoolManager::save_warm_up`.
+///
+/// It is kept as a small text file, one line per entry:
+/// ```text
+/// next_page_id 12
+/// written_pages 0 1 2 3
+/// deleted_pages 5
+/// page 3 1712345000 1712399000
+/// ```
+/// A `page` line holds id of a resident page followed by its LRU-K access history, oldest first.
+#[derive(Debug, Default, PartialEq, Eq)]
+pub(crate) struct WarmUpState {
+    pub(crate) next_page_id: usize,
+    pub(crate) written_pages: Vec<usize>,
+    pub(crate) deleted_pages: Vec<usize>,
+    pub(crate) pages: Vec<(usize, Vec<u64>)>,
+}
+
+impl WarmUpState {
+    /// Written to a temporary file first and renamed over `path`, so a crash never leaves half a file behind.
+    pub(crate) fn save(&self, path: &Path) -> io::Result<()> {
+        let tmp_path = path.with_extension("tmp");
+        let mut writer = BufWriter::new(fs::File::create(&tmp_path)?);
+        writeln!(writer, "next_page_id {}", self.next_page_id)?;
+        write_line(&mut writer, "written_pages", &self.written_pages)?;
+        write_line(&mut writer, "deleted_pages", &self.deleted_pages)?;
+        for (page_id, history) in &self.pages {
+            write!(writer, "page {page_id}")?;
+            for timestamp in history {
+                write!(writer, " {timestamp}")?;
+            }
+            writeln!(writer)?;
+        }
+        writer.into_inner()?.sync_all()?;
+        fs::rename(tmp_path, path)
+    }
+
+    pub(crate) fn load(path: &Path) -> io::Result<Self> {
+        let mut state = WarmUpState::default();
+        for line in BufReader::new(fs::File::open(path)?).lines() {
+            let line = line?;
+            let mut fields = line.split_whitespace();
+            let Some(key) = fields.next() else {
+                continue;
+            };
+            match key {
+                "next_page_id" => state.next_page_id = parse_number(fields.next().unwrap_or(""))?,
+                "written_pages" => state.written_pages = parse_numbers(fields)?,
+                "deleted_pages" => state.deleted_pages = parse_numbers(fields)?,
+                "page" => {
+                    let page_id = parse_number(fields.next().unwrap_or(""))?;
+                    state.pages.push((page_id, parse_numbers(fields)?));
+                }
+                _ => return Err(invalid_data(format!("unknown warm up entry `{key}`"))),
+            }
+        }
+        Ok(state)
+    }
+}
+
+fn write_line(writer: &mut impl Write, key: &str, page_ids: &[usize]) -> io::Result<()> {
+    write!(writer, "{key}")?;
+    for page_id in page_ids {
+        write!(writer, " {page_id}")?;
+    }
+    writeln!(writer)
+}
+
+fn parse_number<T: std::str::FromStr>(field: &str) -> io::Result<T> {
+    field
+        .parse()
+        .map_err(|_| invalid_data(format!("`{field}` is not a number")))
+}
+
+fn parse_numbers<'a, T: std::str::FromStr>(
+    fields: impl Iterator<Item = &'a str>,
+) -> io::Result<Vec<T>> {
+    fields.map(parse_number).collect()
+}
+
+fn invalid_data(msg: String) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, msg)
+}
+
+#[cfg(test)]
+#[allow(clippy::bool_assert_comparison)]
+mod test {
+    use std::{env, fs, process};
+
+    use super::WarmUpState;
+
+    #[test]
+    fn save_and_load_test() {
+        let path = env::temp_dir().join(format!("warm_up_state_{}", process::id()));
+        let state = WarmUpState {
+            next_page_id: 12,
+            written_pages: vec![0, 1, 3],
+            deleted_pages: vec![2],
+            pages: vec![(3, vec![10, 20]), (1, vec![])],
+        };
+        state.save(&path).unwrap();
+        assert_eq!(state, WarmUpState::load(&path).unwrap());
+
+        fs::write(&path, "page x\n").unwrap();
+        assert_eq!(true, WarmUpState::load(&path).is_err());
+        fs::remove_file(path).unwrap();
+    }
+}
@@ -0,0 +1,50 @@
+#![allow(dead_code)]
+
+use crate::{schema::Schema, PoolAssignment};
+
+type IndexOid = u32;
+
+pub struct IndexInfo {
+    key_schema: Schema,
+    name: String,
+    // TODO add link to index.
+    index_oid: IndexOid,
+    table_name: String,
+    // buffer pool pages of this index go through, default pool if none.
+    buffer_pool: Option<String>,
+}
+
+impl IndexInfo {
+    pub fn new(
+        name: impl Into<String>,
+        key_schema: Schema,
+        index_oid: IndexOid,
+        table_name: impl Into<String>,
+    ) -> Self {
+        Self {
+            key_schema,
+            name: name.into(),
+            index_oid,
+            table_name: table_name.into(),
+            buffer_pool: None,
+        }
+    }
+
+    pub fn get_name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn get_table_name(&self) -> &str {
+        &self.table_name
+    }
+
+    pub fn set_buffer_pool(&mut self, buffer_pool: Option<String>) {
+        self.buffer_pool = buffer_pool;
+    }
+}
+
+impl PoolAssignment for IndexInfo {
+    fn buffer_pool(&self) -> Option<&str> {
+        self.buffer_pool.as_deref()
+    }
+}
@@ -6,4 +6,14 @@ mod test_helper;
 
 type TableOid = u32;
 pub use column::*;
+pub use index_info::*;
+pub use schema::*;
+pub use table_info::*;
 pub use test_helper::*;
+
+/// Tables and indexes name buffer pool their pages go through, so that say index pages do not compete with
+/// a large table scan for frames. Pools are looked up by that name, see `buffer::BufferPools`.
+pub trait PoolAssignment {
+    /// None for default pool.
+    fn buffer_pool(&self) -> Option<&str>;
+}
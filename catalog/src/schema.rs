@@ -24,6 +24,14 @@ impl Schema {
             .map(|it| it.0 as u32)
     }
 
+    pub fn get_column(&self, col_idx: u32) -> &Column {
+        &self.columns[col_idx as usize]
+    }
+
+    pub fn get_columns(&self) -> &[Column] {
+        &self.columns
+    }
+
     pub fn get_col_count(&self) -> u32 {
         self.columns.len() as u32
     }
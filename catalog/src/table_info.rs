@@ -1,10 +1,37 @@
 #![allow(dead_code)]
 
-use crate::{schema::Schema, TableOid};
+use crate::{schema::Schema, PoolAssignment, TableOid};
 
 pub struct TableInfo {
     schema: Schema,
     name: String,
     // TODO add link to table heap.
     table_oid: TableOid,
+    // buffer pool pages of this table go through, default pool if none.
+    buffer_pool: Option<String>,
+}
+
+impl TableInfo {
+    pub fn new(name: impl Into<String>, schema: Schema, table_oid: TableOid) -> Self {
+        Self {
+            schema,
+            name: name.into(),
+            table_oid,
+            buffer_pool: None,
+        }
+    }
+
+    pub fn get_name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn set_buffer_pool(&mut self, buffer_pool: Option<String>) {
+        self.buffer_pool = buffer_pool;
+    }
+}
+
+impl PoolAssignment for TableInfo {
+    fn buffer_pool(&self) -> Option<&str> {
+        self.buffer_pool.as_deref()
+    }
 }
@@ -12,6 +12,7 @@ pub fn parse_create_stmt(stmt: impl Into<String>) -> Schema {
 
     let mut columns = Vec::new();
     for token in stmt.split(',') {
+        let token = token.trim();
         let n = token.find(' ').unwrap();
         let column_name = &token[..n];
         let column_type = &token[n + 1..];
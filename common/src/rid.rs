@@ -1,13 +1,23 @@
 #![allow(dead_code)]
 
-#[derive(Debug, Hash, PartialEq, Eq, PartialOrd, Ord)]
+/// Record id, page of a tuple and its slot within that page.
+///
+/// Laid out without implicit padding, so it can be stored as is in index pages.
+#[repr(C)]
+#[derive(Debug, Clone, Copy, Hash, PartialEq, Eq, PartialOrd, Ord)]
 pub struct RID {
     pub page_id: usize,
     pub slot_num: u32,
+    // fills up to alignment of `page_id`, always zero.
+    reserved: u32,
 }
 
 impl RID {
     pub fn new(page_id: usize, slot_num: u32) -> Self {
-        Self { page_id, slot_num }
+        Self {
+            page_id,
+            slot_num,
+            reserved: 0,
+        }
     }
 }
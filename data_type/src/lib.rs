@@ -1,4 +1,3 @@
-#[allow(dead_code)]
 // pub trait DataType {
 //     fn get_type_size(&self) -> u64;
 //     fn is_coercible_from(&self, _other: &dyn DataType) -> bool {
@@ -8,6 +7,8 @@
 mod value;
 use std::{fmt::Display, str::FromStr};
 
+pub use value::Value;
+
 /// Every possible SQL type ID
 #[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
 pub enum DataType {
@@ -64,6 +65,20 @@ impl DataType {
         use DataType::*;
         matches!(self, TinyInt | SmallInt | Integer | BigInt)
     }
+
+    /// Bytes a value of this type takes inline, a varchar takes its declared length. None for types which
+    /// have no fixed size.
+    pub fn get_type_size(&self) -> Option<usize> {
+        use DataType::*;
+        match self {
+            Boolean | TinyInt => Some(1),
+            SmallInt => Some(2),
+            Integer => Some(4),
+            BigInt | Decimal | Timestamp => Some(8),
+            Varchar(size) => Some(*size as usize),
+            Invalid | Vector => None,
+        }
+    }
 }
 
 #[cfg(test)]
@@ -3,7 +3,7 @@
 /// A value represents a view over SQL data stored in
 /// some materialized state. All values have a type and comparison functions, but
 /// subclasses implement other type-specific functionality.
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq)]
 pub enum Value {
     Boolean(bool),
     TinyInt(i8),
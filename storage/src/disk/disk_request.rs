@@ -3,8 +3,8 @@ use std::io;
 
 use tokio::sync::oneshot;
 
-use crate::PAGE_SIZE;
-type BoxedData = Box<[u8; PAGE_SIZE]>;
+use crate::PageData;
+type BoxedData = Box<PageData>;
 #[derive(Debug)]
 pub enum DiskRequest {
     Read {
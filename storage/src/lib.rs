@@ -1,4 +1,7 @@
-use std::io;
+use std::{
+    io,
+    ops::{Deref, DerefMut},
+};
 pub(crate) mod disk;
 pub(crate) mod page;
 
@@ -6,12 +9,37 @@ pub use disk::disk_request::DiskRequest;
 pub use disk::disk_scheduler::DiskScheduler;
 pub use disk::memory_manager::MemoryManager;
 pub use page::page_guard::*;
-pub use page::BPLUS_TREE_INTERNAL_PAGE_HEADER_SIZE;
-pub use page::BPLUS_TREE_LEAF_PAGE_HEADER_SIZE;
 
 pub use page::b_plus_tree_page::*;
+pub use page::frame::*;
 pub use page::frame_header::*;
-const PAGE_SIZE: usize = (4 * 1024) / std::mem::size_of::<u8>();
+pub use page::page_pod::*;
+pub const PAGE_SIZE: usize = (4 * 1024) / std::mem::size_of::<u8>();
+
+/// Bytes of a page. Aligned so that `PagePod` layouts can be viewed in place over it.
+#[derive(Debug)]
+#[repr(C, align(8))]
+pub struct PageData([u8; PAGE_SIZE]);
+
+impl Default for PageData {
+    fn default() -> Self {
+        Self([0u8; PAGE_SIZE])
+    }
+}
+
+impl Deref for PageData {
+    type Target = [u8; PAGE_SIZE];
+
+    fn deref(&self) -> &Self::Target {
+        &self.0
+    }
+}
+
+impl DerefMut for PageData {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        &mut self.0
+    }
+}
 
 pub trait PageOperator: Send {
     fn write_page(&mut self, page_id: usize, data: &[u8; PAGE_SIZE]) -> io::Result<()>;
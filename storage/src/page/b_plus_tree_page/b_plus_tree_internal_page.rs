@@ -1,4 +1,14 @@
-use super::{BPlusTreeInternalHeader, SizeHelper};
+use std::{
+    cmp::Ordering,
+    marker::PhantomData,
+    ops::{Deref, DerefMut},
+};
+
+use crate::{FrameHeader, PagePod};
+
+use super::{
+    arrays, arrays_mut, header, init, insert, move_entries, page_type, remove, IndexPageType,
+};
 
 ///
 /// Store `n` indexed keys and `n + 1` child pointers (page_id) within internal page.
@@ -19,29 +29,161 @@ use super::{BPlusTreeInternalHeader, SizeHelper};
 /// | PAGE_ID(1) | PAGE_ID(2) | ... | PAGE_ID(n) |
 ///  ---------------------------------------------
 ///
-#[repr(C)]
-pub struct BPlusTreeInternalPage<KeyType, ValueType> {
-    keys: Vec<KeyType>,
-    values: Vec<ValueType>,
+/// Header is a `BPlusTreePageHeader`, its next page id is not used. Both arrays have room for one entry over
+/// max size, taken while page is being split.
+///
+/// This is a view over page bytes held by `Page`, a `&FrameHeader` or a `&mut FrameHeader`. Every change is
+/// made in place.
+pub struct BPlusTreeInternalPage<KeyType, ValueType, Page> {
+    page: Page,
+    entries: PhantomData<(KeyType, ValueType)>,
+}
+
+impl<'a, KeyType: PagePod, ValueType: PagePod>
+    BPlusTreeInternalPage<KeyType, ValueType, &'a FrameHeader>
+{
+    /// Panics if page does not hold an internal page.
+    pub fn new(page: &'a FrameHeader) -> Self {
+        assert_internal(page);
+        Self {
+            page,
+            entries: PhantomData,
+        }
+    }
 }
-pub const BPLUS_TREE_INTERNAL_PAGE_HEADER_SIZE: usize =
-    std::mem::size_of::<BPlusTreeInternalHeader>();
 
-impl<KeyType, ValueType> BPlusTreeInternalPage<KeyType, ValueType> {
-    fn new() -> Self {
+impl<'a, KeyType: PagePod, ValueType: PagePod>
+    BPlusTreeInternalPage<KeyType, ValueType, &'a mut FrameHeader>
+{
+    /// Panics if page does not hold an internal page.
+    pub fn new_mut(page: &'a mut FrameHeader) -> Self {
+        assert_internal(page);
         Self {
-            keys: Vec::with_capacity(SizeHelper::get_internal_page_slot_cnt::<
-                BPLUS_TREE_INTERNAL_PAGE_HEADER_SIZE,
-                KeyType,
-                ValueType,
-            >()),
-            values: Vec::with_capacity(SizeHelper::get_internal_page_slot_cnt::<
-                BPLUS_TREE_INTERNAL_PAGE_HEADER_SIZE,
-                KeyType,
-                ValueType,
-            >()),
+            page,
+            entries: PhantomData,
         }
     }
+
+    /// Lays out an empty internal page over page, whatever it held before.
+    pub fn init(page: &'a mut FrameHeader, max_size: u32) -> Self {
+        init(page, IndexPageType::InternalPage, max_size);
+        let internal = Self {
+            page,
+            entries: PhantomData,
+        };
+        // fails early if max size does not fit in a page.
+        internal.keys();
+        internal
+    }
+}
+
+impl<KeyType, ValueType, Page> BPlusTreeInternalPage<KeyType, ValueType, Page>
+where
+    KeyType: PagePod,
+    ValueType: PagePod,
+    Page: Deref<Target = FrameHeader>,
+{
+    /// Number of children.
+    pub fn len(&self) -> usize {
+        header(&self.page).size as usize
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    pub fn max_size(&self) -> u32 {
+        header(&self.page).max_size
+    }
+
+    /// Keys separating children, first one is not valid.
+    pub fn keys(&self) -> &[KeyType] {
+        arrays::<KeyType, ValueType>(&self.page, self.capacity()).0
+    }
+
+    pub fn values(&self) -> &[ValueType] {
+        arrays::<KeyType, ValueType>(&self.page, self.capacity()).1
+    }
+
+    /// Index of child whose subtree may hold `key`, keys ordered by `cmp`.
+    pub fn child_index(
+        &self,
+        key: &KeyType,
+        cmp: impl Fn(&KeyType, &KeyType) -> Ordering,
+    ) -> usize {
+        self.keys()[1..].partition_point(|it| cmp(it, key) != Ordering::Greater)
+    }
+
+    fn capacity(&self) -> usize {
+        self.max_size() as usize + 1
+    }
+}
+
+impl<KeyType, ValueType, Page> BPlusTreeInternalPage<KeyType, ValueType, Page>
+where
+    KeyType: PagePod,
+    ValueType: PagePod,
+    Page: DerefMut<Target = FrameHeader>,
+{
+    pub fn set_key(&mut self, index: usize, key: KeyType) {
+        assert!(
+            index < self.len(),
+            "index {index} is past size {}",
+            self.len()
+        );
+        let capacity = self.capacity();
+        arrays_mut::<KeyType, ValueType>(&mut self.page, capacity).0[index] = key;
+    }
+
+    /// Adds `value` as child at `index`, holding keys from `key` on. Page may go one child over max size,
+    /// it must be split before it is let go then.
+    pub fn insert(&mut self, index: usize, key: KeyType, value: ValueType) {
+        let capacity = self.capacity();
+        insert(&mut self.page, capacity, index, key, value);
+    }
+
+    pub fn remove(&mut self, index: usize) -> (KeyType, ValueType) {
+        let capacity = self.capacity();
+        remove(&mut self.page, capacity, index)
+    }
+
+    /// Moves children from `at` on into `right`, an empty page. Key at `at` becomes the invalid first key of
+    /// `right`, it separates the two pages in their parent.
+    pub fn split_into(
+        &mut self,
+        at: usize,
+        right: &mut BPlusTreeInternalPage<KeyType, ValueType, impl DerefMut<Target = FrameHeader>>,
+    ) {
+        assert!(right.is_empty(), "page split into is not empty");
+        let capacities = (self.capacity(), right.capacity());
+        move_entries::<KeyType, ValueType>(
+            (&mut self.page, capacities.0),
+            at,
+            (&mut right.page, capacities.1),
+        );
+    }
+
+    /// Moves all children of `right` to the end of this page. First key of `right` must already be set to the
+    /// key separating the two.
+    pub fn append(
+        &mut self,
+        right: &mut BPlusTreeInternalPage<KeyType, ValueType, impl DerefMut<Target = FrameHeader>>,
+    ) {
+        let capacities = (self.capacity(), right.capacity());
+        move_entries::<KeyType, ValueType>(
+            (&mut right.page, capacities.1),
+            0,
+            (&mut self.page, capacities.0),
+        );
+    }
+}
+
+fn assert_internal(page: &FrameHeader) {
+    assert_eq!(
+        IndexPageType::InternalPage as u32,
+        page_type(page),
+        "page is not an internal page"
+    );
 }
 
-keys_str!(BPlusTreeInternalPage);
+keys_str!(BPlusTreeInternalPage, 1);
@@ -1,9 +1,20 @@
-use super::{BPlusTreeLeafHeader, SizeHelper};
+use std::{
+    cmp::Ordering,
+    marker::PhantomData,
+    ops::{Deref, DerefMut},
+};
+
+use crate::{FrameHeader, PagePod};
+
+use super::{
+    arrays, header, header_mut, init, insert, move_entries, page_type, remove, IndexPageType,
+};
 
 ///
 /// Store indexed key and record id (record id = page id combined with slot id,
 /// see `include/common/rid.h` for detailed implementation) together within leaf
-/// page. Only support unique key.
+/// page. A key may show up more than once, tree keeps entries sharing a key
+/// ordered by record id.
 ///
 /// Leaf page format (keys are stored in order):
 ///  ---------
@@ -16,36 +27,175 @@ use super::{BPlusTreeLeafHeader, SizeHelper};
 /// | RID(1) | RID(2) | ... | RID(n) |
 ///  ---------------------------------
 ///
-///  Header format (size in byte, 16 bytes in total):
-///  -----------------------------------------------
-/// | PageType (4) | CurrentSize (4) | MaxSize (4) |
-///  -----------------------------------------------
-///  -----------------
-/// | NextPageId (4) |
-///  -----------------
+/// Header is a `BPlusTreePageHeader`, next and previous page ids in it link leaves both ways in key order.
+/// Both arrays have room for max size entries.
 ///
-#[repr(C)]
-pub struct BPlusTreeLeafPage<KeyType, ValueType> {
-    keys: Vec<KeyType>,
-    values: Vec<ValueType>,
+/// This is a view over page bytes held by `Page`, a `&FrameHeader` or a `&mut FrameHeader`. Every change is
+/// made in place.
+pub struct BPlusTreeLeafPage<KeyType, ValueType, Page> {
+    page: Page,
+    entries: PhantomData<(KeyType, ValueType)>,
 }
 
-pub const BPLUS_TREE_LEAF_PAGE_HEADER_SIZE: usize = std::mem::size_of::<BPlusTreeLeafHeader>();
-impl<KeyType, ValueType> BPlusTreeLeafPage<KeyType, ValueType> {
-    fn new() -> Self {
+impl<'a, KeyType: PagePod, ValueType: PagePod>
+    BPlusTreeLeafPage<KeyType, ValueType, &'a FrameHeader>
+{
+    /// Panics if page does not hold a leaf.
+    pub fn new(page: &'a FrameHeader) -> Self {
+        assert_leaf(page);
         Self {
-            keys: Vec::with_capacity(SizeHelper::get_internal_page_slot_cnt::<
-                BPLUS_TREE_LEAF_PAGE_HEADER_SIZE,
-                KeyType,
-                ValueType,
-            >()),
-            values: Vec::with_capacity(SizeHelper::get_internal_page_slot_cnt::<
-                BPLUS_TREE_LEAF_PAGE_HEADER_SIZE,
-                KeyType,
-                ValueType,
-            >()),
+            page,
+            entries: PhantomData,
+        }
+    }
+}
+
+impl<'a, KeyType: PagePod, ValueType: PagePod>
+    BPlusTreeLeafPage<KeyType, ValueType, &'a mut FrameHeader>
+{
+    /// Panics if page does not hold a leaf.
+    pub fn new_mut(page: &'a mut FrameHeader) -> Self {
+        assert_leaf(page);
+        Self {
+            page,
+            entries: PhantomData,
+        }
+    }
+
+    /// Lays out an empty leaf over page, whatever it held before. It has no previous leaf until one is set.
+    pub fn init(page: &'a mut FrameHeader, max_size: u32, next_page_id: usize) -> Self {
+        init(page, IndexPageType::LeafPage, max_size);
+        header_mut(page).next_page_id = next_page_id;
+        let leaf = Self {
+            page,
+            entries: PhantomData,
+        };
+        // fails early if max size does not fit in a page.
+        leaf.keys();
+        leaf
+    }
+}
+
+impl<KeyType, ValueType, Page> BPlusTreeLeafPage<KeyType, ValueType, Page>
+where
+    KeyType: PagePod,
+    ValueType: PagePod,
+    Page: Deref<Target = FrameHeader>,
+{
+    pub fn len(&self) -> usize {
+        header(&self.page).size as usize
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    pub fn max_size(&self) -> u32 {
+        header(&self.page).max_size
+    }
+
+    pub fn keys(&self) -> &[KeyType] {
+        arrays::<KeyType, ValueType>(&self.page, self.capacity()).0
+    }
+
+    pub fn values(&self) -> &[ValueType] {
+        arrays::<KeyType, ValueType>(&self.page, self.capacity()).1
+    }
+
+    pub fn next_page_id(&self) -> usize {
+        header(&self.page).next_page_id
+    }
+
+    pub fn prev_page_id(&self) -> usize {
+        header(&self.page).prev_page_id
+    }
+
+    /// Index of the entry looked for, or where it would be inserted if it is not in this leaf. `cmp` orders an
+    /// entry of this leaf against the one looked for, in the order entries are kept in.
+    pub fn lookup(&self, cmp: impl Fn(&KeyType, &ValueType) -> Ordering) -> Result<usize, usize> {
+        let (keys, values) = (self.keys(), self.values());
+        let (mut low, mut high) = (0, self.len());
+        while low < high {
+            let mid = low + (high - low) / 2;
+            match cmp(&keys[mid], &values[mid]) {
+                Ordering::Less => low = mid + 1,
+                Ordering::Greater => high = mid,
+                Ordering::Equal => return Ok(mid),
+            }
         }
+        Err(low)
+    }
+
+    fn capacity(&self) -> usize {
+        self.max_size() as usize
+    }
+}
+
+impl<KeyType, ValueType, Page> BPlusTreeLeafPage<KeyType, ValueType, Page>
+where
+    KeyType: PagePod,
+    ValueType: PagePod,
+    Page: DerefMut<Target = FrameHeader>,
+{
+    pub fn set_next_page_id(&mut self, next_page_id: usize) {
+        header_mut(&mut self.page).next_page_id = next_page_id;
+    }
+
+    pub fn set_prev_page_id(&mut self, prev_page_id: usize) {
+        header_mut(&mut self.page).prev_page_id = prev_page_id;
+    }
+
+    /// Panics if leaf already holds max size entries.
+    pub fn insert(&mut self, index: usize, key: KeyType, value: ValueType) {
+        let capacity = self.capacity();
+        insert(&mut self.page, capacity, index, key, value);
     }
+
+    pub fn remove(&mut self, index: usize) -> (KeyType, ValueType) {
+        let capacity = self.capacity();
+        remove(&mut self.page, capacity, index)
+    }
+
+    /// Moves entries from `at` on into `right`, an empty leaf which then follows this one. It takes over next
+    /// page id of this leaf. Links between this leaf, `right` and the one after are left to caller, it
+    /// alone knows page ids of the first two.
+    pub fn split_into(
+        &mut self,
+        at: usize,
+        right: &mut BPlusTreeLeafPage<KeyType, ValueType, impl DerefMut<Target = FrameHeader>>,
+    ) {
+        assert!(right.is_empty(), "leaf split into is not empty");
+        let capacities = (self.capacity(), right.capacity());
+        move_entries::<KeyType, ValueType>(
+            (&mut self.page, capacities.0),
+            at,
+            (&mut right.page, capacities.1),
+        );
+        right.set_next_page_id(self.next_page_id());
+    }
+
+    /// Moves all entries of `right`, the leaf right after this one, to the end of this leaf. This leaf takes
+    /// over next page id of `right`, previous page id of the leaf after is left to caller.
+    pub fn append(
+        &mut self,
+        right: &mut BPlusTreeLeafPage<KeyType, ValueType, impl DerefMut<Target = FrameHeader>>,
+    ) {
+        let capacities = (self.capacity(), right.capacity());
+        move_entries::<KeyType, ValueType>(
+            (&mut right.page, capacities.1),
+            0,
+            (&mut self.page, capacities.0),
+        );
+        self.set_next_page_id(right.next_page_id());
+    }
+}
+
+fn assert_leaf(page: &FrameHeader) {
+    assert_eq!(
+        IndexPageType::LeafPage as u32,
+        page_type(page),
+        "page is not a leaf"
+    );
 }
 
-keys_str!(BPlusTreeLeafPage);
+keys_str!(BPlusTreeLeafPage, 0);
@@ -0,0 +1,186 @@
+use std::{
+    cmp::Ordering,
+    marker::PhantomData,
+    ops::{Deref, DerefMut},
+};
+
+use crate::{FrameHeader, PagePod, PAGE_SIZE};
+
+use super::{header, page_type, slotted_page, IndexPageType};
+
+///
+/// Store `n` variable length keys and `n + 1` child pointers (page_id) within
+/// a slotted internal page. Pointer PAGE_ID(i) points to a subtree in which all
+/// keys K satisfy: K(i) <= K < K(i+1), keys ordered as bytes. As in
+/// `BPlusTreeInternalPage`, the first key is never looked at.
+///
+/// Page is laid out as `BPlusTreeVarLeafPage`, with child page ids for values. First key takes part in
+/// prefix all keys share, giving it the lower bound of page keeps that prefix long. Separators cut with
+/// `shortest_separator` keep keys short.
+///
+/// This is a view over page bytes held by `Page`, a `&FrameHeader` or a `&mut FrameHeader`. Every change is
+/// made in place.
+pub struct BPlusTreeVarInternalPage<ValueType, Page> {
+    page: Page,
+    values: PhantomData<ValueType>,
+}
+
+impl<'a, ValueType: PagePod> BPlusTreeVarInternalPage<ValueType, &'a FrameHeader> {
+    /// Panics if page does not hold an internal page with variable length keys.
+    pub fn new(page: &'a FrameHeader) -> Self {
+        assert_var_internal(page);
+        Self {
+            page,
+            values: PhantomData,
+        }
+    }
+}
+
+impl<'a, ValueType: PagePod> BPlusTreeVarInternalPage<ValueType, &'a mut FrameHeader> {
+    /// Panics if page does not hold an internal page with variable length keys.
+    pub fn new_mut(page: &'a mut FrameHeader) -> Self {
+        assert_var_internal(page);
+        Self {
+            page,
+            values: PhantomData,
+        }
+    }
+
+    /// Lays out an empty internal page over page, whatever it held before. Panics unless four children with
+    /// keys of `max_key_size` bytes fit in a page.
+    pub fn init(page: &'a mut FrameHeader, max_key_size: u32) -> Self {
+        slotted_page::init::<ValueType>(page, IndexPageType::VarInternalPage, max_key_size);
+        Self {
+            page,
+            values: PhantomData,
+        }
+    }
+}
+
+impl<ValueType, Page> BPlusTreeVarInternalPage<ValueType, Page>
+where
+    ValueType: PagePod,
+    Page: Deref<Target = FrameHeader>,
+{
+    /// Number of children.
+    pub fn len(&self) -> usize {
+        header(&self.page).size as usize
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    pub fn max_key_size(&self) -> u32 {
+        header(&self.page).max_size
+    }
+
+    /// Bytes every key of page starts with, first key included.
+    pub fn prefix(&self) -> &[u8] {
+        slotted_page::prefix(&self.page)
+    }
+
+    /// Key separating child at `index` from the one before, first one is not valid.
+    pub fn key(&self, index: usize) -> Vec<u8> {
+        slotted_page::key::<ValueType>(&self.page, index)
+    }
+
+    pub fn value(&self, index: usize) -> &ValueType {
+        slotted_page::value(&self.page, index)
+    }
+
+    /// Page bytes taken by header, prefix, slots and cells.
+    pub fn used_space(&self) -> usize {
+        slotted_page::used_space::<ValueType>(&self.page)
+    }
+
+    pub fn free_space(&self) -> usize {
+        PAGE_SIZE - self.used_space()
+    }
+
+    /// Index of child whose subtree may hold `key`.
+    pub fn child_index(&self, key: &[u8]) -> usize {
+        let (mut low, mut high) = (1, self.len());
+        while low < high {
+            let mid = low + (high - low) / 2;
+            match slotted_page::compare_key::<ValueType>(&self.page, mid, key) {
+                Ordering::Greater => high = mid,
+                Ordering::Less | Ordering::Equal => low = mid + 1,
+            }
+        }
+        low - 1
+    }
+}
+
+impl<ValueType, Page> BPlusTreeVarInternalPage<ValueType, Page>
+where
+    ValueType: PagePod,
+    Page: DerefMut<Target = FrameHeader>,
+{
+    /// False if key does not fit, page is left as it was then.
+    pub fn set_key(&mut self, index: usize, key: &[u8]) -> bool {
+        slotted_page::set_key::<ValueType>(&mut self.page, index, key)
+    }
+
+    /// Adds `value` as child at `index`, holding keys from `key` on. False if it does not fit, page is left
+    /// as it was then. Panics if key is over max key size.
+    pub fn insert(&mut self, index: usize, key: &[u8], value: ValueType) -> bool {
+        slotted_page::insert(&mut self.page, index, key, value)
+    }
+
+    pub fn remove(&mut self, index: usize) -> (Vec<u8>, ValueType) {
+        slotted_page::remove(&mut self.page, index)
+    }
+
+    /// Moves children from `at` on into `right`, an empty page. Key at `at` becomes the invalid first key of
+    /// `right`, it separates the two pages in their parent.
+    pub fn split_into(
+        &mut self,
+        at: usize,
+        right: &mut BPlusTreeVarInternalPage<ValueType, impl DerefMut<Target = FrameHeader>>,
+    ) {
+        slotted_page::split::<ValueType>(&mut self.page, at, &mut right.page);
+    }
+
+    /// Adds `value` as child at `index` of this page, which has no room for it, by moving children from some
+    /// point on into `right` as `split_into` does. Point is picked for both pages to hold about as many
+    /// bytes.
+    pub fn split_insert(
+        &mut self,
+        index: usize,
+        key: &[u8],
+        value: ValueType,
+        right: &mut BPlusTreeVarInternalPage<ValueType, impl DerefMut<Target = FrameHeader>>,
+    ) {
+        slotted_page::split_insert(&mut self.page, index, key, value, &mut right.page);
+    }
+
+    /// Moves all children of `right` to the end of this page, `separator` taking the place of the invalid
+    /// first key of `right`. It is the key separating the two in their parent. False if children do not fit
+    /// in one page, nothing is moved then.
+    pub fn append(
+        &mut self,
+        separator: &[u8],
+        right: &mut BPlusTreeVarInternalPage<ValueType, impl DerefMut<Target = FrameHeader>>,
+    ) -> bool {
+        slotted_page::append::<ValueType>(&mut self.page, Some(separator), &mut right.page)
+    }
+}
+
+/// Shortest key a parent can hold to separate a page ending with `left` from one starting with `right`, a
+/// prefix of `right` greater than `left`. Panics unless `left < right`.
+pub fn shortest_separator<'a>(left: &[u8], right: &'a [u8]) -> &'a [u8] {
+    assert!(left < right, "separated keys are not in order");
+    let shared = left.iter().zip(right).take_while(|(l, r)| l == r).count();
+    &right[..shared + 1]
+}
+
+fn assert_var_internal(page: &FrameHeader) {
+    assert_eq!(
+        IndexPageType::VarInternalPage as u32,
+        page_type(page),
+        "page is not an internal page with variable length keys"
+    );
+}
+
+var_keys_str!(BPlusTreeVarInternalPage, 1);
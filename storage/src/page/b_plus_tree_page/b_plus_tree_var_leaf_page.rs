@@ -0,0 +1,206 @@
+use std::{
+    cmp::Ordering,
+    marker::PhantomData,
+    ops::{Deref, DerefMut},
+};
+
+use crate::{FrameHeader, PagePod, PAGE_SIZE};
+
+use super::{header, header_mut, page_type, slotted_page, IndexPageType};
+
+///
+/// Store variable length keys and record ids together within a slotted leaf
+/// page. Keys are byte strings ordered as bytes, entries sharing a key are
+/// ordered by record id.
+///
+/// Slotted page format (slots are stored in key order):
+///  --------------------------------------------------------------------------
+/// | HEADER | PREFIX | SLOT(1) | ... | SLOT(n) | FREE | CELL(k) | ... | CELL(1) |
+///  --------------------------------------------------------------------------
+///
+/// Header is a `BPlusTreePageHeader`, with max size standing for the most bytes a key may take, followed by
+/// length of prefix and start of cells (8 bytes). Prefix holds bytes every key of page starts with, it is
+/// stored once and cut off each key. A slot (4 bytes) points at a cell holding value and what is left of
+/// key. Cells fill page from its end, in no particular order.
+///
+/// An insert fails when entry does not fit, leaf is to be split then. Page is laid out again whenever prefix
+/// gets shorter, or there is room for a cell only once space of removed ones is taken back.
+///
+/// This is a view over page bytes held by `Page`, a `&FrameHeader` or a `&mut FrameHeader`. Every change is
+/// made in place.
+pub struct BPlusTreeVarLeafPage<ValueType, Page> {
+    page: Page,
+    values: PhantomData<ValueType>,
+}
+
+impl<'a, ValueType: PagePod> BPlusTreeVarLeafPage<ValueType, &'a FrameHeader> {
+    /// Panics if page does not hold a leaf with variable length keys.
+    pub fn new(page: &'a FrameHeader) -> Self {
+        assert_var_leaf(page);
+        Self {
+            page,
+            values: PhantomData,
+        }
+    }
+}
+
+impl<'a, ValueType: PagePod> BPlusTreeVarLeafPage<ValueType, &'a mut FrameHeader> {
+    /// Panics if page does not hold a leaf with variable length keys.
+    pub fn new_mut(page: &'a mut FrameHeader) -> Self {
+        assert_var_leaf(page);
+        Self {
+            page,
+            values: PhantomData,
+        }
+    }
+
+    /// Lays out an empty leaf over page, whatever it held before. It has no previous leaf until one is set.
+    /// Panics unless four entries with keys of `max_key_size` bytes fit in a page.
+    pub fn init(page: &'a mut FrameHeader, max_key_size: u32, next_page_id: usize) -> Self {
+        slotted_page::init::<ValueType>(page, IndexPageType::VarLeafPage, max_key_size);
+        header_mut(page).next_page_id = next_page_id;
+        Self {
+            page,
+            values: PhantomData,
+        }
+    }
+}
+
+impl<ValueType, Page> BPlusTreeVarLeafPage<ValueType, Page>
+where
+    ValueType: PagePod,
+    Page: Deref<Target = FrameHeader>,
+{
+    pub fn len(&self) -> usize {
+        header(&self.page).size as usize
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    pub fn max_key_size(&self) -> u32 {
+        header(&self.page).max_size
+    }
+
+    /// Bytes every key of leaf starts with.
+    pub fn prefix(&self) -> &[u8] {
+        slotted_page::prefix(&self.page)
+    }
+
+    pub fn key(&self, index: usize) -> Vec<u8> {
+        slotted_page::key::<ValueType>(&self.page, index)
+    }
+
+    pub fn value(&self, index: usize) -> &ValueType {
+        slotted_page::value(&self.page, index)
+    }
+
+    pub fn next_page_id(&self) -> usize {
+        header(&self.page).next_page_id
+    }
+
+    pub fn prev_page_id(&self) -> usize {
+        header(&self.page).prev_page_id
+    }
+
+    /// Page bytes taken by header, prefix, slots and cells.
+    pub fn used_space(&self) -> usize {
+        slotted_page::used_space::<ValueType>(&self.page)
+    }
+
+    pub fn free_space(&self) -> usize {
+        PAGE_SIZE - self.used_space()
+    }
+
+    /// Index of entry with `key` whose value `cmp` finds equal, or where it would be inserted if it is not in
+    /// this leaf. `cmp` orders a value of this leaf against the one looked for. Giving `Ordering::Greater`
+    /// for every value finds first entry with `key` or a greater one.
+    pub fn lookup(&self, key: &[u8], cmp: impl Fn(&ValueType) -> Ordering) -> Result<usize, usize> {
+        let (mut low, mut high) = (0, self.len());
+        while low < high {
+            let mid = low + (high - low) / 2;
+            let order = slotted_page::compare_key::<ValueType>(&self.page, mid, key)
+                .then_with(|| cmp(self.value(mid)));
+            match order {
+                Ordering::Less => low = mid + 1,
+                Ordering::Greater => high = mid,
+                Ordering::Equal => return Ok(mid),
+            }
+        }
+        Err(low)
+    }
+}
+
+impl<ValueType, Page> BPlusTreeVarLeafPage<ValueType, Page>
+where
+    ValueType: PagePod,
+    Page: DerefMut<Target = FrameHeader>,
+{
+    pub fn set_next_page_id(&mut self, next_page_id: usize) {
+        header_mut(&mut self.page).next_page_id = next_page_id;
+    }
+
+    pub fn set_prev_page_id(&mut self, prev_page_id: usize) {
+        header_mut(&mut self.page).prev_page_id = prev_page_id;
+    }
+
+    /// False if entry does not fit, leaf is left as it was then. Panics if key is over max key size.
+    pub fn insert(&mut self, index: usize, key: &[u8], value: ValueType) -> bool {
+        slotted_page::insert(&mut self.page, index, key, value)
+    }
+
+    pub fn remove(&mut self, index: usize) -> (Vec<u8>, ValueType) {
+        slotted_page::remove(&mut self.page, index)
+    }
+
+    /// Moves entries from `at` on into `right`, an empty leaf which then follows this one. It takes over next
+    /// page id of this leaf. Links between this leaf, `right` and the one after are left to caller, it
+    /// alone knows page ids of the first two.
+    pub fn split_into(
+        &mut self,
+        at: usize,
+        right: &mut BPlusTreeVarLeafPage<ValueType, impl DerefMut<Target = FrameHeader>>,
+    ) {
+        slotted_page::split::<ValueType>(&mut self.page, at, &mut right.page);
+        right.set_next_page_id(self.next_page_id());
+    }
+
+    /// Adds an entry at `index` of this leaf, which has no room for it, by moving entries from some point on
+    /// into `right` as `split_into` does. Point is picked for both leaves to hold about as many bytes, entry
+    /// added may end up in either.
+    pub fn split_insert(
+        &mut self,
+        index: usize,
+        key: &[u8],
+        value: ValueType,
+        right: &mut BPlusTreeVarLeafPage<ValueType, impl DerefMut<Target = FrameHeader>>,
+    ) {
+        slotted_page::split_insert(&mut self.page, index, key, value, &mut right.page);
+        right.set_next_page_id(self.next_page_id());
+    }
+
+    /// Moves all entries of `right`, the leaf right after this one, to the end of this leaf. This leaf takes
+    /// over next page id of `right`, previous page id of the leaf after is left to caller. False if entries
+    /// do not fit in one leaf, nothing is moved then.
+    pub fn append(
+        &mut self,
+        right: &mut BPlusTreeVarLeafPage<ValueType, impl DerefMut<Target = FrameHeader>>,
+    ) -> bool {
+        if !slotted_page::append::<ValueType>(&mut self.page, None, &mut right.page) {
+            return false;
+        }
+        self.set_next_page_id(right.next_page_id());
+        true
+    }
+}
+
+fn assert_var_leaf(page: &FrameHeader) {
+    assert_eq!(
+        IndexPageType::VarLeafPage as u32,
+        page_type(page),
+        "page is not a leaf with variable length keys"
+    );
+}
+
+var_keys_str!(BPlusTreeVarLeafPage, 0);
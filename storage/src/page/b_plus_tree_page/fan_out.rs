@@ -0,0 +1,62 @@
+use std::fmt::{Display, Formatter};
+
+use super::SizeHelper;
+
+/// How many pages a B+ tree over some entries takes. Tells how much slotted pages with variable length keys
+/// gain over fixed width keys, comparing what `VarBPlusTree::fan_out` measures on a tree against
+/// `FanOut::fixed`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct FanOut {
+    pub entries: usize,
+    pub leaf_pages: usize,
+    pub internal_pages: usize,
+    /// Levels of internal pages above leaves.
+    pub height: u32,
+}
+
+impl FanOut {
+    /// Pages taken by `entries` entries in `BPlusTreeLeafPage`s, with internal pages keyed by `KeyType` as well.
+    /// Every page is packed full, as bulk load with a fill factor of 1 does.
+    pub fn fixed<KeyType, ValueType>(entries: usize) -> Self {
+        let leaf_pages = entries.div_ceil(SizeHelper::get_page_slot_cnt::<KeyType, ValueType>());
+        let children = SizeHelper::get_page_slot_cnt::<KeyType, usize>();
+        let (mut level, mut internal_pages, mut height) = (leaf_pages, 0, 0);
+        while level > 1 {
+            level = level.div_ceil(children);
+            internal_pages += level;
+            height += 1;
+        }
+        Self {
+            entries,
+            leaf_pages,
+            internal_pages,
+            height,
+        }
+    }
+
+    /// Entries per leaf, on average.
+    pub fn leaf_fan_out(&self) -> f64 {
+        self.entries as f64 / self.leaf_pages.max(1) as f64
+    }
+
+    /// Children per internal page, on average. Every page but root is a child of one.
+    pub fn internal_fan_out(&self) -> f64 {
+        let children = (self.leaf_pages + self.internal_pages).saturating_sub(1);
+        children as f64 / self.internal_pages.max(1) as f64
+    }
+}
+
+impl Display for FanOut {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{} entries in {} leaves ({:.1} per leaf) under {} internal pages ({:.1} children each), height {}",
+            self.entries,
+            self.leaf_pages,
+            self.leaf_fan_out(),
+            self.internal_pages,
+            self.internal_fan_out(),
+            self.height
+        )
+    }
+}
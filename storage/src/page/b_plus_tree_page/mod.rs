@@ -1,14 +1,27 @@
 #![allow(dead_code)]
 
-use b_plus_tree_internal_page::BPlusTreeInternalPage;
-use b_plus_tree_leaf_page::BPlusTreeLeafPage;
+use std::mem::{align_of, size_of};
+
+pub use b_plus_tree_internal_page::BPlusTreeInternalPage;
+pub use b_plus_tree_leaf_page::BPlusTreeLeafPage;
+pub use b_plus_tree_var_internal_page::{shortest_separator, BPlusTreeVarInternalPage};
+pub use b_plus_tree_var_leaf_page::BPlusTreeVarLeafPage;
+pub use fan_out::FanOut;
 use serde::Serialize;
 
-#[repr(C)]
-enum IndexPageType {
-    InvalidIndexPage,
-    LeafPage,
-    InternalPage,
+use crate::{cast_slice, cast_slice_mut, impl_page_pod, FrameHeader, PagePod, PAGE_SIZE};
+
+/// Kind of B+ tree page, stored first in its header. A page never laid out as part of a tree reads as 0.
+#[repr(u32)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[allow(clippy::enum_variant_names)]
+pub enum IndexPageType {
+    InvalidIndexPage = 1,
+    LeafPage = 2,
+    InternalPage = 3,
+    HeaderPage = 4,
+    VarLeafPage = 5,
+    VarInternalPage = 6,
 }
 
 impl Serialize for IndexPageType {
@@ -16,96 +29,532 @@ impl Serialize for IndexPageType {
     where
         S: serde::Serializer,
     {
-        let val: u32 = match self {
-            IndexPageType::InvalidIndexPage => 1,
-            IndexPageType::LeafPage => 2,
-            IndexPageType::InternalPage => 3,
-        };
-        serializer.serialize_u32(val)
+        serializer.serialize_u32(*self as u32)
     }
 }
 
+/// Page id stored where there is no page, like next page id of the last leaf.
+pub const INVALID_PAGE_ID: usize = usize::MAX;
+
+impl_page_pod! {
+    ///
+    /// Header at start of both leaf and internal pages.
+    ///
+    /// Header format (size in byte, 32 bytes in total):
+    /// --------------------------------------------------------------------------------------------------
+    /// | PageType (4) | CurrentSize (4) | MaxSize (4) | Reserved (4) | NextPageId (8) | PrevPageId (8) |
+    /// --------------------------------------------------------------------------------------------------
+    ///
+    /// Keys and values follow as two arrays, each aligned for its type and with room for `capacity` entries, see
+    /// `BPlusTreeLeafPage` and `BPlusTreeInternalPage`.
+    /// Pages with variable length keys are slotted instead, see `BPlusTreeVarLeafPage`.
+    #[derive(Clone, Copy)]
+    pub struct BPlusTreePageHeader {
+        page_type: u32,
+        size: u32,
+        max_size: u32,
+        reserved: u32,
+        next_page_id: usize,
+        prev_page_id: usize,
+    }
+}
+
+pub const BPLUS_TREE_PAGE_HEADER_SIZE: usize = size_of::<BPlusTreePageHeader>();
+
 pub struct SizeHelper;
 
 impl SizeHelper {
-    pub fn get_internal_page_slot_cnt<const N: usize, KeyType, ValueType>() -> usize {
-        (4096 - N - 8)
-            / (std::mem::size_of::<KeyType>()
-                + std::mem::size_of::<ValueType>()
-                // vec takes space for len and capacity. And we have two vec, one for key and one for value. 
-                + 2 * 4 * std::mem::size_of::<usize>())
+    /// Most entries a page can hold in its key and value arrays.
+    pub fn get_page_slot_cnt<KeyType, ValueType>() -> usize {
+        // each array may start up to its alignment past the end of what comes before it.
+        (PAGE_SIZE - BPLUS_TREE_PAGE_HEADER_SIZE - align_of::<KeyType>() - align_of::<ValueType>())
+            / (size_of::<KeyType>() + size_of::<ValueType>())
     }
 }
 
+// Offsets of key and value arrays, each with room for `capacity` entries.
+fn array_offsets<KeyType, ValueType>(capacity: usize) -> (usize, usize) {
+    let keys_offset = BPLUS_TREE_PAGE_HEADER_SIZE.next_multiple_of(align_of::<KeyType>());
+    let values_offset =
+        (keys_offset + capacity * size_of::<KeyType>()).next_multiple_of(align_of::<ValueType>());
+    assert!(
+        values_offset + capacity * size_of::<ValueType>() <= PAGE_SIZE,
+        "{capacity} entries do not fit in a page"
+    );
+    (keys_offset, values_offset)
+}
+
+fn header(page: &FrameHeader) -> &BPlusTreePageHeader {
+    page.get_readable_data_as()
+}
+
+fn header_mut(page: &mut FrameHeader) -> &mut BPlusTreePageHeader {
+    page.get_writeable_data_as()
+}
+
+fn page_type(page: &FrameHeader) -> u32 {
+    header(page).page_type
+}
+
+// Lays out an empty page of `page_type` over whatever page held before.
+fn init(page: &mut FrameHeader, page_type: IndexPageType, max_size: u32) {
+    *header_mut(page) = BPlusTreePageHeader {
+        page_type: page_type as u32,
+        size: 0,
+        max_size,
+        reserved: 0,
+        next_page_id: INVALID_PAGE_ID,
+        prev_page_id: INVALID_PAGE_ID,
+    };
+}
+
+// Key and value arrays up to current size.
+fn arrays<KeyType: PagePod, ValueType: PagePod>(
+    page: &FrameHeader,
+    capacity: usize,
+) -> (&[KeyType], &[ValueType]) {
+    let size = header(page).size as usize;
+    let (keys_offset, values_offset) = array_offsets::<KeyType, ValueType>(capacity);
+    let data = page.get_readable_data();
+    (
+        cast_slice(&data[keys_offset..], size),
+        cast_slice(&data[values_offset..], size),
+    )
+}
+
+// Whole key and value arrays, entries past current size included.
+fn arrays_mut<KeyType: PagePod, ValueType: PagePod>(
+    page: &mut FrameHeader,
+    capacity: usize,
+) -> (&mut [KeyType], &mut [ValueType]) {
+    let (keys_offset, values_offset) = array_offsets::<KeyType, ValueType>(capacity);
+    let (keys, values) = page.get_writeable_data().split_at_mut(values_offset);
+    (
+        cast_slice_mut(&mut keys[keys_offset..], capacity),
+        cast_slice_mut(values, capacity),
+    )
+}
+
+fn insert<KeyType: PagePod, ValueType: PagePod>(
+    page: &mut FrameHeader,
+    capacity: usize,
+    index: usize,
+    key: KeyType,
+    value: ValueType,
+) {
+    let size = header(page).size as usize;
+    assert!(size < capacity, "page is full");
+    assert!(index <= size, "index {index} is past size {size}");
+    let (keys, values) = arrays_mut(page, capacity);
+    keys.copy_within(index..size, index + 1);
+    values.copy_within(index..size, index + 1);
+    keys[index] = key;
+    values[index] = value;
+    header_mut(page).size += 1;
+}
+
+fn remove<KeyType: PagePod, ValueType: PagePod>(
+    page: &mut FrameHeader,
+    capacity: usize,
+    index: usize,
+) -> (KeyType, ValueType) {
+    let size = header(page).size as usize;
+    assert!(index < size, "index {index} is past size {size}");
+    let (keys, values) = arrays_mut::<KeyType, ValueType>(page, capacity);
+    let removed = (keys[index], values[index]);
+    keys.copy_within(index + 1..size, index);
+    values.copy_within(index + 1..size, index);
+    header_mut(page).size -= 1;
+    removed
+}
+
+// Moves entries of `from` starting at `at` to the end of `to`.
+fn move_entries<KeyType: PagePod, ValueType: PagePod>(
+    (from, from_capacity): (&mut FrameHeader, usize),
+    at: usize,
+    (to, to_capacity): (&mut FrameHeader, usize),
+) {
+    let (from_size, to_size) = (header(from).size as usize, header(to).size as usize);
+    let moved = from_size - at;
+    assert!(to_size + moved <= to_capacity, "page is full");
+    let (from_keys, from_values) = arrays_mut::<KeyType, ValueType>(from, from_capacity);
+    let (to_keys, to_values) = arrays_mut::<KeyType, ValueType>(to, to_capacity);
+    to_keys[to_size..to_size + moved].copy_from_slice(&from_keys[at..from_size]);
+    to_values[to_size..to_size + moved].copy_from_slice(&from_values[at..from_size]);
+    header_mut(from).size = at as u32;
+    header_mut(to).size = (to_size + moved) as u32;
+}
+
+// Display keys of a page view, leaving out the first `$skip` ones.
 macro_rules! keys_str {
     (
-        $name: ident
+        $name: ident, $skip: literal
     ) => {
-        impl<KeyType: std::fmt::Debug, ValueType: std::fmt::Debug> ToString
-            for $name<KeyType, ValueType>
+        impl<KeyType, ValueType, Page> std::fmt::Display for $name<KeyType, ValueType, Page>
+        where
+            KeyType: $crate::PagePod + std::fmt::Debug,
+            ValueType: $crate::PagePod,
+            Page: std::ops::Deref<Target = $crate::FrameHeader>,
         {
-            fn to_string(&self) -> String {
-                let mut k_str = String::from("(");
+            fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+                let keys = self
+                    .keys()
+                    .iter()
+                    .skip($skip)
+                    .map(|it| format!("{it:?}"))
+                    .collect::<Vec<_>>();
 
-                self.keys.iter().enumerate().skip(1).for_each(|it| {
-                    if it.0 != 0 {
-                        k_str.push_str(",");
-                    }
+                write!(f, "({})", keys.join(","))
+            }
+        }
+    };
+}
 
-                    k_str.push_str(&format!("{:?}", it.1));
-                });
+// Display keys of a page view with variable length keys, leaving out the first `$skip` ones.
+macro_rules! var_keys_str {
+    (
+        $name: ident, $skip: literal
+    ) => {
+        impl<ValueType, Page> std::fmt::Display for $name<ValueType, Page>
+        where
+            ValueType: $crate::PagePod,
+            Page: std::ops::Deref<Target = $crate::FrameHeader>,
+        {
+            fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+                let keys = ($skip..self.len())
+                    .map(|i| String::from_utf8_lossy(&self.key(i)).into_owned())
+                    .collect::<Vec<_>>();
 
-                k_str
+                write!(f, "({})", keys.join(","))
             }
         }
     };
 }
 
-pub struct BplusTreeHeaderPage(usize);
+impl_page_pod! {
+    /// First page of a B+ tree, it stays put while root moves around. Viewed in place with `get_readable_data_as`.
+    ///
+    /// Header page format (size in byte, 24 bytes in total):
+    /// ----------------------------------------------------------------------------------
+    /// | PageType (4) | Height (4) | LeafMaxSize (4) | InternalMaxSize (4) | RootPageId (8) |
+    /// ----------------------------------------------------------------------------------
+    #[derive(Clone, Copy)]
+    pub struct BplusTreeHeaderPage {
+        page_type: u32,
+        height: u32,
+        leaf_max_size: u32,
+        internal_max_size: u32,
+        root_page_id: usize,
+    }
+}
 
-pub mod b_plus_tree_internal_page;
-pub mod b_plus_tree_leaf_page;
+impl BplusTreeHeaderPage {
+    /// Header of an empty tree.
+    pub fn new(leaf_max_size: u32, internal_max_size: u32) -> Self {
+        Self {
+            page_type: IndexPageType::HeaderPage as u32,
+            height: 0,
+            leaf_max_size,
+            internal_max_size,
+            root_page_id: INVALID_PAGE_ID,
+        }
+    }
+
+    /// False if page was never laid out as a header page.
+    pub fn is_valid(&self) -> bool {
+        self.page_type == IndexPageType::HeaderPage as u32
+    }
+
+    /// None while tree is empty.
+    pub fn root_page_id(&self) -> Option<usize> {
+        (self.root_page_id != INVALID_PAGE_ID).then_some(self.root_page_id)
+    }
+
+    /// Levels of internal pages above leaves.
+    pub fn height(&self) -> u32 {
+        self.height
+    }
 
-///
-/// Both internal and leaf page are inherited from this page.
-///
-/// It actually serves as a header part for each B+ tree page and
-/// contains information shared by both leaf page and internal page.
-///
-/// Header format (size in byte, 12 bytes in total):
-/// ---------------------------------------------------------
-/// | PageType (4) | CurrentSize (4) | MaxSize (4) |  ...   |
-/// ---------------------------------------------------------
-///
-#[repr(C)]
-pub enum BPlusTreePage<KeyType, ValueType> {
-    BPlusTreeLeafPage {
-        header: BPlusTreeLeafHeader,
-        data: BPlusTreeLeafPage<KeyType, ValueType>,
-    },
-    BPlusTreeInternalPage {
-        header: BPlusTreeInternalHeader,
-        data: BPlusTreeInternalPage<KeyType, ValueType>,
-    },
-}
-
-#[repr(C)]
-pub struct BPlusTreeInternalHeader {
-    size: u32,
-    max_size: u32,
-}
-
-#[repr(C)]
-pub struct BPlusTreeLeafHeader {
-    size: u32,
-    max_size: u32,
-    next_page_id: usize,
+    pub fn set_root(&mut self, root_page_id: Option<usize>, height: u32) {
+        self.root_page_id = root_page_id.unwrap_or(INVALID_PAGE_ID);
+        self.height = height;
+    }
+
+    pub fn leaf_max_size(&self) -> u32 {
+        self.leaf_max_size
+    }
+
+    pub fn internal_max_size(&self) -> u32 {
+        self.internal_max_size
+    }
 }
 
+pub mod b_plus_tree_internal_page;
+pub mod b_plus_tree_leaf_page;
+pub mod b_plus_tree_var_internal_page;
+pub mod b_plus_tree_var_leaf_page;
+mod fan_out;
+mod slotted_page;
+
 #[cfg(test)]
+#[allow(clippy::bool_assert_comparison)]
 mod test {
+    use std::cmp::Ordering;
+
     use catalog::parse_create_stmt;
 
+    use crate::FrameHeader;
+
+    use super::{
+        shortest_separator, BPlusTreeInternalPage, BPlusTreeLeafPage, BPlusTreeVarInternalPage,
+        BPlusTreeVarLeafPage, IndexPageType, INVALID_PAGE_ID,
+    };
+
+    type Leaf<'a> = BPlusTreeLeafPage<u64, u32, &'a FrameHeader>;
+    type LeafMut<'a> = BPlusTreeLeafPage<u64, u32, &'a mut FrameHeader>;
+    type InternalMut<'a> = BPlusTreeInternalPage<u64, usize, &'a mut FrameHeader>;
+    type VarLeaf<'a> = BPlusTreeVarLeafPage<u32, &'a FrameHeader>;
+    type VarLeafMut<'a> = BPlusTreeVarLeafPage<u32, &'a mut FrameHeader>;
+    type VarInternalMut<'a> = BPlusTreeVarInternalPage<usize, &'a mut FrameHeader>;
+
+    // what a page looks like after it went to disk and was read back into another frame.
+    fn reload(page: &FrameHeader) -> FrameHeader {
+        let mut reloaded = FrameHeader::new(1);
+        reloaded
+            .get_writeable_data()
+            .copy_from_slice(page.get_readable_data());
+        reloaded
+    }
+
+    #[test]
+    fn leaf_layout_test() {
+        let mut page = FrameHeader::new(0);
+        let mut leaf = LeafMut::init(&mut page, 4, 7);
+        for key in [30, 10, 20] {
+            let index = leaf.lookup(|k, _| k.cmp(&key)).unwrap_err();
+            leaf.insert(index, key, key as u32 + 1);
+        }
+        assert_eq!(Err(3), leaf.lookup(|k, _| k.cmp(&40)));
+        // entries sharing a key are told apart by value.
+        let entry = |key: u64, value: u32| move |k: &u64, v: &u32| (k, v).cmp(&(&key, &value));
+        assert_eq!(Ok(1), leaf.lookup(entry(20, 21)));
+        assert_eq!(Err(1), leaf.lookup(entry(20, 5)));
+        assert_eq!((30, 31), leaf.remove(2));
+
+        // header and arrays sit at fixed offsets in page bytes.
+        let data = page.get_readable_data();
+        assert_eq!(
+            IndexPageType::LeafPage as u32,
+            u32::from_ne_bytes(data[..4].try_into().unwrap())
+        );
+        assert_eq!(2, u32::from_ne_bytes(data[4..8].try_into().unwrap()));
+        assert_eq!(4, u32::from_ne_bytes(data[8..12].try_into().unwrap()));
+        assert_eq!(7, usize::from_ne_bytes(data[16..24].try_into().unwrap()));
+        assert_eq!(
+            INVALID_PAGE_ID,
+            usize::from_ne_bytes(data[24..32].try_into().unwrap())
+        );
+        assert_eq!(10, u64::from_ne_bytes(data[32..40].try_into().unwrap()));
+
+        let reloaded = reload(&page);
+        let leaf = Leaf::new(&reloaded);
+        assert_eq!((&[10, 20][..], &[11, 21][..]), (leaf.keys(), leaf.values()));
+        assert_eq!((7, 4), (leaf.next_page_id(), leaf.max_size()));
+        assert_eq!("(10,20)", leaf.to_string());
+    }
+
+    #[test]
+    fn leaf_split_and_append_test() {
+        let (mut left_page, mut right_page) = (FrameHeader::new(0), FrameHeader::new(1));
+        let mut left = LeafMut::init(&mut left_page, 4, 9);
+        for key in 0..4 {
+            left.insert(key as usize, key, 0);
+        }
+        let mut right = LeafMut::init(&mut right_page, 4, INVALID_PAGE_ID);
+        left.split_into(2, &mut right);
+        assert_eq!((&[0, 1][..], &[2, 3][..]), (left.keys(), right.keys()));
+        assert_eq!(9, right.next_page_id());
+
+        left.append(&mut right);
+        assert_eq!(&[0, 1, 2, 3], left.keys());
+        assert_eq!(true, right.is_empty());
+    }
+
+    #[test]
+    fn internal_takes_one_over_max_size_test() {
+        let (mut page, mut right_page) = (FrameHeader::new(0), FrameHeader::new(1));
+        let mut internal = InternalMut::init(&mut page, 3);
+        for (i, key) in [0, 10, 20, 30].into_iter().enumerate() {
+            internal.insert(i, key, 100 + i);
+        }
+        assert_eq!(4, internal.len());
+        assert_eq!(
+            (0, 2, 3),
+            (
+                internal.child_index(&5, Ord::cmp),
+                internal.child_index(&20, Ord::cmp),
+                internal.child_index(&99, Ord::cmp)
+            )
+        );
+
+        let mut right = InternalMut::init(&mut right_page, 3);
+        internal.split_into(2, &mut right);
+        assert_eq!(
+            (&[100, 101][..], &[102, 103][..]),
+            (internal.values(), right.values())
+        );
+        // first key of right separates the two.
+        assert_eq!(20, right.keys()[0]);
+        assert_eq!("(30)", right.to_string());
+    }
+
+    #[test]
+    #[should_panic(expected = "page is not a leaf")]
+    fn view_of_wrong_kind_panics() {
+        let mut page = FrameHeader::new(0);
+        InternalMut::init(&mut page, 3);
+        Leaf::new(&page);
+    }
+
+    #[test]
+    fn var_leaf_prefix_test() {
+        let mut page = FrameHeader::new(0);
+        let mut leaf = VarLeafMut::init(&mut page, 32, 7);
+        for (key, value) in [("apple/3", 3), ("apple/1", 1), ("apple/2", 2)] {
+            let index = leaf.lookup(key.as_bytes(), |v| v.cmp(&value)).unwrap_err();
+            assert_eq!(true, leaf.insert(index, key.as_bytes(), value));
+        }
+        assert_eq!(b"apple/", leaf.prefix());
+        assert_eq!(Ok(1), leaf.lookup(b"apple/2", |v| v.cmp(&2)));
+        // entries sharing a key are told apart by value.
+        assert_eq!(Err(1), leaf.lookup(b"apple/2", |v| v.cmp(&0)));
+        assert_eq!(Err(0), leaf.lookup(b"apple", |_| Ordering::Greater));
+        assert_eq!(Err(3), leaf.lookup(b"b", |_| Ordering::Greater));
+
+        // a key not sharing whole prefix cuts it short, keys stay as they were.
+        assert_eq!(true, leaf.insert(3, b"apricot", 4));
+        assert_eq!(b"ap", leaf.prefix());
+        assert_eq!((b"apple/3".to_vec(), 3), leaf.remove(2));
+        assert_eq!(b"apricot", leaf.key(2).as_slice());
+
+        let reloaded = reload(&page);
+        let leaf = VarLeaf::new(&reloaded);
+        assert_eq!((7, 32), (leaf.next_page_id(), leaf.max_key_size()));
+        assert_eq!(4, *leaf.value(2));
+        assert_eq!("(apple/1,apple/2,apricot)", leaf.to_string());
+    }
+
+    #[test]
+    fn var_leaf_split_and_append_test() {
+        let (mut left_page, mut right_page) = (FrameHeader::new(0), FrameHeader::new(1));
+        let mut left = VarLeafMut::init(&mut left_page, 64, 9);
+        let key = |i: u32| format!("customer/{i:05}").into_bytes();
+        let mut count = 0;
+        while left.insert(count as usize, &key(count), count) {
+            count += 1;
+        }
+        // fixed width keys of 64 bytes would fit 60 entries.
+        assert_eq!(true, count > 200, "{count} entries");
+        assert_eq!(count as usize, left.len());
+        assert_eq!(b"customer/00", left.prefix());
+
+        let mut right = VarLeafMut::init(&mut right_page, 64, INVALID_PAGE_ID);
+        left.split_into(count as usize / 2, &mut right);
+        assert_eq!(9, right.next_page_id());
+        assert_eq!(key(count / 2), right.key(0));
+        assert_eq!(
+            (count as usize / 2, count as usize - count as usize / 2),
+            (left.len(), right.len())
+        );
+
+        assert_eq!(true, left.append(&mut right));
+        assert_eq!(true, right.is_empty());
+        assert_eq!(count as usize, left.len());
+        // a full leaf takes nothing more, and nothing moves.
+        assert_eq!(true, right.insert(0, b"customer/99999", 0));
+        assert_eq!(false, left.append(&mut right));
+        assert_eq!((count as usize, 1), (left.len(), right.len()));
+        assert_eq!(
+            (0..count).map(key).collect::<Vec<_>>(),
+            (0..left.len()).map(|i| left.key(i)).collect::<Vec<_>>()
+        );
+    }
+
+    #[test]
+    fn var_internal_separators_test() {
+        assert_eq!(b"apr", shortest_separator(b"apple", b"apricot"));
+        assert_eq!(b"abc", shortest_separator(b"ab", b"abcd"));
+
+        let mut page = FrameHeader::new(0);
+        let mut internal = VarInternalMut::init(&mut page, 32);
+        for (i, key) in ["user/a", "user/b", "user/d"].into_iter().enumerate() {
+            assert_eq!(true, internal.insert(i, key.as_bytes(), 100 + i));
+        }
+        assert_eq!(b"user/", internal.prefix());
+        assert_eq!(
+            (0, 1, 1, 2, 0),
+            (
+                internal.child_index(b"user/a"),
+                internal.child_index(b"user/b"),
+                internal.child_index(b"user/c"),
+                internal.child_index(b"user/zz"),
+                internal.child_index(b"aaa"),
+            )
+        );
+        assert_eq!(true, internal.set_key(2, b"user/e"));
+        assert_eq!(1, internal.child_index(b"user/d"));
+        assert_eq!("(user/b,user/e)", internal.to_string());
+    }
+
+    #[test]
+    #[should_panic(expected = "keys of 1024 bytes do not fit four to a page")]
+    fn var_page_with_too_long_keys_panics() {
+        let mut page = FrameHeader::new(0);
+        VarInternalMut::init(&mut page, 1024);
+    }
+
+    #[test]
+    fn var_split_insert_test() {
+        let (mut left_page, mut right_page) = (FrameHeader::new(0), FrameHeader::new(1));
+        let mut left = VarLeafMut::init(&mut left_page, 64, 9);
+        let key = |i: u32| format!("customer/{i:05}").into_bytes();
+        let mut count = 0;
+        while left.insert(count as usize, &key(2 * count), count) {
+            count += 1;
+        }
+        let mut right = VarLeafMut::init(&mut right_page, 64, INVALID_PAGE_ID);
+        left.split_insert(3, &key(5), 99, &mut right);
+        assert_eq!(count as usize + 1, left.len() + right.len());
+        assert_eq!(true, left.len().abs_diff(right.len()) <= 1);
+        assert_eq!((99, 9), (*left.value(3), right.next_page_id()));
+
+        // a key sharing no prefix with a full leaf makes keys it is split off with take all their bytes.
+        let mut left = VarLeafMut::init(&mut left_page, 64, 9);
+        let key = |i: usize| format!("https://example.com/customers/{i:06}/orders").into_bytes();
+        while left.insert(left.len(), &key(left.len()), 0) {}
+        let len = left.len();
+        let mut right = VarLeafMut::init(&mut right_page, 64, INVALID_PAGE_ID);
+        left.split_insert(len, b"order/1", 0, &mut right);
+        assert_eq!(len + 1, left.len() + right.len());
+        assert_eq!(true, left.len() > right.len());
+        assert_eq!(
+            (&b"https://example.com/customers/000"[..], &b""[..]),
+            (left.prefix(), right.prefix())
+        );
+        assert_eq!(b"order/1", right.key(right.len() - 1).as_slice());
+
+        // separator takes place of first key of right page as the two are merged.
+        let (mut left_page, mut right_page) = (FrameHeader::new(0), FrameHeader::new(1));
+        let mut left = VarInternalMut::init(&mut left_page, 32);
+        let mut right = VarInternalMut::init(&mut right_page, 32);
+        assert_eq!(true, left.insert(0, b"user/a", 100));
+        assert_eq!(true, right.insert(0, b"user/b", 101));
+        assert_eq!(true, right.insert(1, b"user/d", 102));
+        assert_eq!(true, left.append(b"user/c", &mut right));
+        assert_eq!((3, true), (left.len(), right.is_empty()));
+        assert_eq!(b"user/c", left.key(1).as_slice());
+    }
+
     #[test]
     fn insert_test_1() {
         let key_schema = parse_create_stmt("a bigint");
@@ -0,0 +1,347 @@
+use std::{
+    cmp::Ordering,
+    mem::{align_of, size_of, size_of_val},
+};
+
+use crate::{cast_slice, cast_slice_mut, impl_page_pod, FrameHeader, PagePod, PAGE_SIZE};
+
+use super::{header, header_mut, IndexPageType, BPLUS_TREE_PAGE_HEADER_SIZE};
+
+impl_page_pod! {
+    // Follows `BPlusTreePageHeader` in a page with variable length keys.
+    #[derive(Clone, Copy)]
+    struct SlottedHeader {
+        prefix_len: u16,
+        // cells fill page from its end towards slots, this is where the lowest one starts.
+        cells_start: u16,
+        reserved: u32,
+    }
+}
+
+impl_page_pod! {
+    // Where cell of an entry starts, and length of key suffix in it.
+    #[derive(Clone, Copy)]
+    struct Slot {
+        offset: u16,
+        len: u16,
+    }
+}
+
+const PREFIX_OFFSET: usize = BPLUS_TREE_PAGE_HEADER_SIZE + size_of::<SlottedHeader>();
+
+// Lays out an empty page of `page_type` holding keys of up to `max_key_size` bytes.
+pub(super) fn init<ValueType>(page: &mut FrameHeader, page_type: IndexPageType, max_key_size: u32) {
+    let max_key_size = max_key_size as usize;
+    assert!(
+        PREFIX_OFFSET
+            + max_key_size
+            + 4 * (size_of::<Slot>() + cell_size::<ValueType>(max_key_size))
+            <= PAGE_SIZE,
+        "keys of {max_key_size} bytes do not fit four to a page"
+    );
+    super::init(page, page_type, max_key_size as u32);
+    clear(page);
+}
+
+fn slotted_header(page: &FrameHeader) -> SlottedHeader {
+    cast_slice(&page.get_readable_data()[BPLUS_TREE_PAGE_HEADER_SIZE..], 1)[0]
+}
+
+fn slotted_header_mut(page: &mut FrameHeader) -> &mut SlottedHeader {
+    &mut cast_slice_mut(
+        &mut page.get_writeable_data()[BPLUS_TREE_PAGE_HEADER_SIZE..],
+        1,
+    )[0]
+}
+
+fn clear(page: &mut FrameHeader) {
+    header_mut(page).size = 0;
+    *slotted_header_mut(page) = SlottedHeader {
+        prefix_len: 0,
+        cells_start: PAGE_SIZE as u16,
+        reserved: 0,
+    };
+}
+
+fn slots_offset(prefix_len: usize) -> usize {
+    (PREFIX_OFFSET + prefix_len).next_multiple_of(align_of::<Slot>())
+}
+
+// Bytes a cell holding a value and `suffix_len` bytes of key takes, keeping the next one aligned for value.
+fn cell_size<ValueType>(suffix_len: usize) -> usize {
+    (size_of::<ValueType>() + suffix_len).next_multiple_of(align_of::<ValueType>())
+}
+
+fn slots(page: &FrameHeader) -> &[Slot] {
+    let offset = slots_offset(slotted_header(page).prefix_len as usize);
+    cast_slice(
+        &page.get_readable_data()[offset..],
+        header(page).size as usize,
+    )
+}
+
+// Key bytes all entries of page start with.
+pub(super) fn prefix(page: &FrameHeader) -> &[u8] {
+    let prefix_len = slotted_header(page).prefix_len as usize;
+    &page.get_readable_data()[PREFIX_OFFSET..PREFIX_OFFSET + prefix_len]
+}
+
+// Key bytes of entry at `index` past prefix.
+pub(super) fn suffix<ValueType>(page: &FrameHeader, index: usize) -> &[u8] {
+    let slot = slots(page)[index];
+    let start = slot.offset as usize + size_of::<ValueType>();
+    &page.get_readable_data()[start..start + slot.len as usize]
+}
+
+pub(super) fn key<ValueType>(page: &FrameHeader, index: usize) -> Vec<u8> {
+    [prefix(page), suffix::<ValueType>(page, index)].concat()
+}
+
+pub(super) fn value<ValueType: PagePod>(page: &FrameHeader, index: usize) -> &ValueType {
+    let offset = slots(page)[index].offset as usize;
+    &cast_slice(&page.get_readable_data()[offset..], 1)[0]
+}
+
+// Orders key at `index` against `key`, as bytes.
+pub(super) fn compare_key<ValueType>(page: &FrameHeader, index: usize, key: &[u8]) -> Ordering {
+    let prefix = prefix(page);
+    let shared = prefix.len().min(key.len());
+    prefix[..shared].cmp(&key[..shared]).then_with(|| {
+        if key.len() < prefix.len() {
+            Ordering::Greater
+        } else {
+            suffix::<ValueType>(page, index).cmp(&key[prefix.len()..])
+        }
+    })
+}
+
+// Page bytes in use, free space between slots and cells left out. Cells of removed entries count until page
+// is laid out again.
+pub(super) fn used_space<ValueType>(page: &FrameHeader) -> usize {
+    let prefix_len = slotted_header(page).prefix_len as usize;
+    let slots = slots(page);
+    let cells = slots
+        .iter()
+        .map(|it| cell_size::<ValueType>(it.len as usize));
+    slots_offset(prefix_len) + size_of_val(slots) + cells.sum::<usize>()
+}
+
+pub(super) fn entries<ValueType: PagePod>(page: &FrameHeader) -> Vec<(Vec<u8>, ValueType)> {
+    (0..header(page).size as usize)
+        .map(|i| (key::<ValueType>(page, i), *value(page, i)))
+        .collect()
+}
+
+// Adds an entry at `index`. False if it does not fit, page is left as it was then.
+pub(super) fn insert<ValueType: PagePod>(
+    page: &mut FrameHeader,
+    index: usize,
+    key: &[u8],
+    value: ValueType,
+) -> bool {
+    let size = header(page).size as usize;
+    assert!(index <= size, "index {index} is past size {size}");
+    let max_key_size = header(page).max_size as usize;
+    assert!(
+        key.len() <= max_key_size,
+        "key of {} bytes is over max key size {max_key_size}",
+        key.len()
+    );
+    let SlottedHeader {
+        prefix_len,
+        cells_start,
+        ..
+    } = slotted_header(page);
+    let (prefix_len, cells_start) = (prefix_len as usize, cells_start as usize);
+    // a key which does not share whole prefix makes every entry longer, page is laid out again for it.
+    if size > 0 && key.starts_with(prefix(page)) {
+        let cell = cell_size::<ValueType>(key.len() - prefix_len);
+        let slots_end = slots_offset(prefix_len) + (size + 1) * size_of::<Slot>();
+        if slots_end + cell <= cells_start {
+            let offset = cells_start - cell;
+            write_cell(page, offset, &key[prefix_len..], value);
+            let slots_offset = slots_offset(prefix_len);
+            let slots =
+                cast_slice_mut::<Slot>(&mut page.get_writeable_data()[slots_offset..], size + 1);
+            slots.copy_within(index..size, index + 1);
+            slots[index] = Slot {
+                offset: offset as u16,
+                len: (key.len() - prefix_len) as u16,
+            };
+            header_mut(page).size += 1;
+            slotted_header_mut(page).cells_start = offset as u16;
+            return true;
+        }
+    }
+    let mut entries = entries(page);
+    entries.insert(index, (key.to_vec(), value));
+    lay_out(page, &entries)
+}
+
+pub(super) fn remove<ValueType: PagePod>(
+    page: &mut FrameHeader,
+    index: usize,
+) -> (Vec<u8>, ValueType) {
+    let size = header(page).size as usize;
+    assert!(index < size, "index {index} is past size {size}");
+    let removed = (key::<ValueType>(page, index), *value(page, index));
+    if size == 1 {
+        clear(page);
+        return removed;
+    }
+    // cell stays where it is until page is laid out again.
+    let slots_offset = slots_offset(slotted_header(page).prefix_len as usize);
+    let slots = cast_slice_mut::<Slot>(&mut page.get_writeable_data()[slots_offset..], size);
+    slots.copy_within(index + 1..size, index);
+    header_mut(page).size -= 1;
+    removed
+}
+
+// Replaces key at `index`. False if it does not fit, page is left as it was then.
+pub(super) fn set_key<ValueType: PagePod>(
+    page: &mut FrameHeader,
+    index: usize,
+    key: &[u8],
+) -> bool {
+    let size = header(page).size as usize;
+    assert!(index < size, "index {index} is past size {size}");
+    let mut entries = entries::<ValueType>(page);
+    entries[index].0 = key.to_vec();
+    lay_out(page, &entries)
+}
+
+// Moves entries of `from` starting at `at` into `to`, which must be empty. Both pages are laid out again,
+// each with a prefix of its own.
+pub(super) fn split<ValueType: PagePod>(from: &mut FrameHeader, at: usize, to: &mut FrameHeader) {
+    assert_eq!(0, header(to).size, "page split into is not empty");
+    let entries = entries::<ValueType>(from);
+    // fewer entries share at least as long a prefix, so both halves fit.
+    assert!(lay_out(to, &entries[at..]));
+    assert!(lay_out(from, &entries[..at]));
+}
+
+// Adds an entry at `index` of `from`, which it does not fit in, by moving entries from some point on into
+// `to`, which must be empty. Point is picked for both pages to hold about as many bytes, as long as both fit.
+pub(super) fn split_insert<ValueType: PagePod>(
+    from: &mut FrameHeader,
+    index: usize,
+    key: &[u8],
+    value: ValueType,
+    to: &mut FrameHeader,
+) {
+    assert_eq!(0, header(to).size, "page split into is not empty");
+    let mut entries = entries::<ValueType>(from);
+    entries.insert(index, (key.to_vec(), value));
+    let at = split_point(&entries);
+    assert!(lay_out(to, &entries[at..]));
+    assert!(lay_out(from, &entries[..at]));
+}
+
+// Where to cut `entries` in two pages, as close to half of their key bytes as both halves fit. Entries came
+// from a page and one more, there is always such a point.
+fn split_point<ValueType>(entries: &[(Vec<u8>, ValueType)]) -> usize {
+    let total = entries.iter().map(|it| it.0.len()).sum::<usize>();
+    let mut half = 0;
+    let middle = entries
+        .iter()
+        .position(|it| {
+            half += it.0.len();
+            2 * half >= total
+        })
+        .map_or(0, |it| it + 1)
+        .clamp(1, entries.len() - 1);
+    let fits = |at: usize| {
+        layout_size(&entries[..at]) <= PAGE_SIZE && layout_size(&entries[at..]) <= PAGE_SIZE
+    };
+    (0..entries.len())
+        .flat_map(|distance| [middle.checked_sub(distance), Some(middle + distance)])
+        .flatten()
+        .filter(|at| (1..entries.len()).contains(at))
+        .find(|&at| fits(at))
+        .expect("entries of a split page fit in two")
+}
+
+// Moves all entries of `from` to the end of `to`, with `first_key` in place of key of the first one if given.
+// False if they do not fit, no entry is moved then.
+pub(super) fn append<ValueType: PagePod>(
+    to: &mut FrameHeader,
+    first_key: Option<&[u8]>,
+    from: &mut FrameHeader,
+) -> bool {
+    let mut entries = entries::<ValueType>(to);
+    let size = entries.len();
+    entries.extend(self::entries::<ValueType>(from));
+    if let (Some(first_key), Some(first)) = (first_key, entries.get_mut(size)) {
+        first.0 = first_key.to_vec();
+    }
+    if !lay_out(to, &entries) {
+        return false;
+    }
+    clear(from);
+    true
+}
+
+fn common_prefix<ValueType>(entries: &[(Vec<u8>, ValueType)]) -> usize {
+    entries.split_first().map_or(0, |(first, rest)| {
+        rest.iter().fold(first.0.len(), |len, it| {
+            common_prefix_len(&first.0[..len], &it.0)
+        })
+    })
+}
+
+// Page bytes `entries` take once laid out.
+fn layout_size<ValueType>(entries: &[(Vec<u8>, ValueType)]) -> usize {
+    let prefix_len = common_prefix(entries);
+    let cells = entries
+        .iter()
+        .map(|it| cell_size::<ValueType>(it.0.len() - prefix_len));
+    slots_offset(prefix_len) + entries.len() * size_of::<Slot>() + cells.sum::<usize>()
+}
+
+// Lays out `entries` over page, after the longest prefix they all share and with no space left between
+// cells. False if they do not fit, page is left as it was then.
+fn lay_out<ValueType: PagePod>(page: &mut FrameHeader, entries: &[(Vec<u8>, ValueType)]) -> bool {
+    if layout_size(entries) > PAGE_SIZE {
+        return false;
+    }
+    let prefix_len = common_prefix(entries);
+
+    let prefix = entries.first().map_or(&[][..], |it| &it.0[..prefix_len]);
+    page.get_writeable_data()[PREFIX_OFFSET..PREFIX_OFFSET + prefix_len].copy_from_slice(prefix);
+    let mut cells_start = PAGE_SIZE;
+    let mut slots = Vec::with_capacity(entries.len());
+    for (key, value) in entries {
+        cells_start -= cell_size::<ValueType>(key.len() - prefix_len);
+        write_cell(page, cells_start, &key[prefix_len..], *value);
+        slots.push(Slot {
+            offset: cells_start as u16,
+            len: (key.len() - prefix_len) as u16,
+        });
+    }
+    let slots_offset = slots_offset(prefix_len);
+    cast_slice_mut(&mut page.get_writeable_data()[slots_offset..], slots.len())
+        .copy_from_slice(&slots);
+    header_mut(page).size = entries.len() as u32;
+    *slotted_header_mut(page) = SlottedHeader {
+        prefix_len: prefix_len as u16,
+        cells_start: cells_start as u16,
+        reserved: 0,
+    };
+    true
+}
+
+fn write_cell<ValueType: PagePod>(
+    page: &mut FrameHeader,
+    offset: usize,
+    suffix: &[u8],
+    value: ValueType,
+) {
+    let data = page.get_writeable_data();
+    cast_slice_mut(&mut data[offset..], 1)[0] = value;
+    let start = offset + size_of::<ValueType>();
+    data[start..start + suffix.len()].copy_from_slice(suffix);
+}
+
+fn common_prefix_len(lhs: &[u8], rhs: &[u8]) -> usize {
+    lhs.iter().zip(rhs).take_while(|(l, r)| l == r).count()
+}
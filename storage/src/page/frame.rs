@@ -0,0 +1,115 @@
+#![allow(dead_code)]
+use std::sync::{
+    atomic::{AtomicBool, AtomicU16, AtomicU64, AtomicUsize, Ordering},
+    Arc,
+};
+
+use parking_lot::RwLock;
+
+use crate::page::{latch_debug, FrameHeader};
+
+// page id of a frame holding no page.
+const NO_PAGE: usize = usize::MAX;
+
+/// A slot in buffer pool.
+///
+/// Pin count lives next to the page latch rather than behind it. This way it can be read, and a pin can be
+/// released, without waiting on whoever holds the latch. It is the only place pin count of a frame is kept.
+pub struct Frame {
+    frame_id: usize,
+    // page held by this frame, or `NO_PAGE`. It is only changed while holding write latch, and kept outside
+    // latch so that buffer pool can be inspected without waiting on page latches.
+    page_id: AtomicUsize,
+    // this indicates how many operations are using this frame.
+    // A page associated with non zero pin_count should not be evicted.
+    pin_count: AtomicU16,
+    // set as a writer latches the frame, and cleared once page is written back.
+    // It is kept outside latch so that a flush only needs to share the page with readers.
+    is_dirty: AtomicBool,
+    // bumped by a writer once as it latches the frame and once as it releases it. So an odd version means a
+    // writer is in, and an unchanged even version means page content is same as when it was read.
+    version: AtomicU64,
+    // page guards keep their own clone of this arc, so latch outlives any guard on it.
+    latch: Arc<RwLock<FrameHeader>>,
+}
+
+impl Frame {
+    pub fn new(frame_id: usize) -> Self {
+        Self {
+            frame_id,
+            page_id: AtomicUsize::new(NO_PAGE),
+            pin_count: AtomicU16::default(),
+            is_dirty: AtomicBool::default(),
+            version: AtomicU64::default(),
+            latch: Arc::new(RwLock::new(FrameHeader::new(frame_id))),
+        }
+    }
+
+    pub fn frame_id(&self) -> usize {
+        self.frame_id
+    }
+
+    pub fn page_id(&self) -> Option<usize> {
+        let page_id = self.page_id.load(Ordering::SeqCst);
+        (page_id != NO_PAGE).then_some(page_id)
+    }
+
+    pub fn set_page_id(&self, page_id: Option<usize>) {
+        let page_id = page_id.unwrap_or(NO_PAGE);
+        let old = self.page_id.swap(page_id, Ordering::SeqCst);
+        if old != NO_PAGE && old != page_id {
+            latch_debug::forget(self, old);
+        }
+    }
+
+    pub fn latch(&self) -> &Arc<RwLock<FrameHeader>> {
+        &self.latch
+    }
+
+    pub fn pin_count(&self) -> u16 {
+        self.pin_count.load(Ordering::SeqCst)
+    }
+
+    /// Returns pin count after pinning.
+    pub fn pin(&self) -> u16 {
+        self.pin_count.fetch_add(1, Ordering::SeqCst) + 1
+    }
+
+    /// Returns pin count after unpinning.
+    pub(crate) fn unpin(&self) -> u16 {
+        self.pin_count.fetch_sub(1, Ordering::SeqCst) - 1
+    }
+
+    pub fn is_dirty(&self) -> bool {
+        self.is_dirty.load(Ordering::SeqCst)
+    }
+
+    pub fn set_dirty(&self, is_dirty: bool) {
+        self.is_dirty.store(is_dirty, Ordering::SeqCst);
+    }
+
+    pub fn version(&self) -> u64 {
+        self.version.load(Ordering::SeqCst)
+    }
+
+    // only to be called while holding write latch.
+    pub(crate) fn bump_version(&self) {
+        self.version.fetch_add(1, Ordering::SeqCst);
+    }
+}
+
+impl Drop for Frame {
+    // address of a dropped frame can be handed out to a new one, latch order seen on it must not carry over.
+    fn drop(&mut self) {
+        self.set_page_id(None);
+    }
+}
+
+/// Told about every frame whose pin count drops to zero, so that it can become a candidate for eviction.
+///
+/// This is called by page guard on the dropping thread, after page latch has been released.
+/// Frame may have been pinned again by the time this is called, so implementations need to check
+/// `Frame::pin_count` before acting on it.
+pub trait UnpinListener: Send + Sync {
+    fn on_unpinned(&self, frame: &Frame);
+}
@@ -0,0 +1,420 @@
+//! Latch order checks for page guards, compiled in with `latch-debug` feature.
+//!
+//! Every thread keeps track of page latches it holds. Taking a latch that can wait on one the same thread
+//! already holds on that page, or taking latches on two pages in the opposite order some thread took them
+//! before, panics instead of hanging. Panic tells where each latch involved was taken, with a backtrace of
+//! each. A backtrace is captured for every latch taken, its symbols are only resolved if it is printed. This
+//! makes latches several times slower, feature is meant for tests only.
+//!
+//! Latches are keyed by frame and page id held in it, so several buffer pools can share a process. Frames
+//! without a page are not tracked, and order seen on a page is forgotten once it is evicted or deleted.
+//!
+//! Without the feature every hook here is an empty function.
+#![allow(dead_code)]
+
+#[cfg(not(feature = "latch-debug"))]
+use crate::page::Frame;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum LatchMode {
+    Read,
+    Upgradable,
+    Write,
+}
+
+impl LatchMode {
+    // parking_lot lets a read in next to a read or an upgradable read. Anything else waits, so taking it
+    // again on the same thread never returns. A recursive read is let through here, it only hangs if a
+    // writer queues up in between.
+    fn blocks(self, held: LatchMode) -> bool {
+        !matches!(
+            (self, held),
+            (LatchMode::Read, LatchMode::Read)
+                | (LatchMode::Read, LatchMode::Upgradable)
+                | (LatchMode::Upgradable, LatchMode::Read)
+        )
+    }
+}
+
+#[cfg(feature = "latch-debug")]
+mod tracking {
+    use std::{
+        backtrace::Backtrace,
+        cell::RefCell,
+        collections::{hash_map::Entry, HashMap, HashSet},
+        panic::Location,
+        sync::{Arc, LazyLock, Mutex},
+        thread,
+    };
+
+    use super::LatchMode;
+    use crate::page::Frame;
+
+    // frame address and page id. Frame alone can be reused for another page, and page ids repeat across
+    // buffer pools.
+    type LatchId = (usize, usize);
+
+    // where a latch was taken, as seen by the first caller not marked `#[track_caller]`.
+    type Site = &'static Location<'static>;
+
+    fn latch_id(frame: &Frame) -> Option<LatchId> {
+        let page_id = frame.page_id()?;
+        Some((frame as *const Frame as usize, page_id))
+    }
+
+    // Backtrace is captured without symbols, they are only looked up if it is ever printed.
+    struct Held {
+        latch_id: LatchId,
+        mode: LatchMode,
+        site: Site,
+        backtrace: Arc<Backtrace>,
+    }
+
+    // `to` was latched while `from` was held, as both were taken then.
+    struct Edge {
+        from_site: Site,
+        from_backtrace: Arc<Backtrace>,
+        to_site: Site,
+        to_backtrace: Arc<Backtrace>,
+    }
+
+    // Latch order seen so far, only between pages resident right now. Edges of a page go away with it, so
+    // graph stays as big as buffer pools are.
+    #[derive(Default)]
+    struct Order {
+        edges: HashMap<LatchId, HashMap<LatchId, Edge>>,
+        // from latches of edges leading to a latch.
+        incoming: HashMap<LatchId, HashSet<LatchId>>,
+    }
+
+    impl Order {
+        fn add(&mut self, from: LatchId, to: LatchId, edge: impl FnOnce() -> Edge) {
+            if let Entry::Vacant(entry) = self.edges.entry(from).or_default().entry(to) {
+                entry.insert(edge());
+                self.incoming.entry(to).or_default().insert(from);
+            }
+        }
+
+        fn contains(&self, from: LatchId, to: LatchId) -> bool {
+            self.edges.get(&from).is_some_and(|it| it.contains_key(&to))
+        }
+
+        fn remove(&mut self, latch_id: LatchId) {
+            for to in self.edges.remove(&latch_id).unwrap_or_default().into_keys() {
+                if let Some(from) = self.incoming.get_mut(&to) {
+                    from.remove(&latch_id);
+                }
+            }
+            for from in self.incoming.remove(&latch_id).unwrap_or_default() {
+                if let Some(edges) = self.edges.get_mut(&from) {
+                    edges.remove(&latch_id);
+                }
+            }
+        }
+
+        // next latch after `from` on a path of edges leading to `to`.
+        fn path(&self, from: LatchId, to: LatchId) -> Option<LatchId> {
+            let mut seen = HashSet::new();
+            let mut stack = self
+                .edges
+                .get(&from)?
+                .keys()
+                .map(|&next| (next, next))
+                .collect::<Vec<_>>();
+            while let Some((first, latch_id)) = stack.pop() {
+                if latch_id == to {
+                    return Some(first);
+                }
+                if !seen.insert(latch_id) {
+                    continue;
+                }
+                if let Some(edges) = self.edges.get(&latch_id) {
+                    stack.extend(edges.keys().map(|&next| (first, next)));
+                }
+            }
+            None
+        }
+    }
+
+    thread_local! {
+        static HELD: RefCell<Vec<Held>> = const { RefCell::new(Vec::new()) };
+    }
+
+    static ORDER: LazyLock<Mutex<Order>> = LazyLock::new(Mutex::default);
+
+    #[track_caller]
+    pub(crate) fn before_acquire(frame: &Frame, mode: LatchMode) {
+        let Some(latch_id) = latch_id(frame) else {
+            return;
+        };
+        let page_id = latch_id.1;
+        let site = Location::caller();
+        HELD.with_borrow(|held| {
+            if let Some(same) = held
+                .iter()
+                .find(|it| it.latch_id == latch_id && mode.blocks(it.mode))
+            {
+                panic!(
+                    "self deadlock: thread {} takes {mode:?} latch on page {page_id} at {site} while holding \
+                     {:?} latch on it taken at {}\n\nheld latch taken at:\n{}\n\nnew latch taken at:\n{}",
+                    thread_name(),
+                    same.mode,
+                    same.site,
+                    same.backtrace,
+                    Backtrace::force_capture()
+                );
+            }
+
+            let order = ORDER.lock().unwrap_or_else(|err| err.into_inner());
+            for it in held {
+                // an order seen before adds no cycle, it would have been caught back then.
+                if it.latch_id == latch_id || order.contains(it.latch_id, latch_id) {
+                    continue;
+                }
+                let Some(next) = order.path(latch_id, it.latch_id) else {
+                    continue;
+                };
+                let edge = &order.edges[&latch_id][&next];
+                panic!(
+                    "latch order cycle: thread {} takes latch on page {page_id} at {site} while holding page \
+                     {} taken at {}, but page {page_id} was latched at {} before page {} was latched at {} \
+                     earlier\n\nheld latch taken at:\n{}\n\nnew latch taken at:\n{}\n\nearlier latch on \
+                     page {page_id} taken at:\n{}\n\nearlier latch on page {} taken at:\n{}",
+                    thread_name(),
+                    it.latch_id.1,
+                    it.site,
+                    edge.from_site,
+                    next.1,
+                    edge.to_site,
+                    it.backtrace,
+                    Backtrace::force_capture(),
+                    edge.from_backtrace,
+                    next.1,
+                    edge.to_backtrace
+                );
+            }
+        });
+    }
+
+    // `ordered` is false for latches taken without waiting, those can not deadlock and taking them out of
+    // order is the usual way around a cycle.
+    #[track_caller]
+    pub(crate) fn acquired(frame: &Frame, mode: LatchMode, ordered: bool) {
+        let Some(latch_id) = latch_id(frame) else {
+            return;
+        };
+        let site = Location::caller();
+        let backtrace = Arc::new(Backtrace::force_capture());
+        HELD.with_borrow_mut(|held| {
+            if ordered && held.iter().any(|it| it.latch_id != latch_id) {
+                let mut order = ORDER.lock().unwrap_or_else(|err| err.into_inner());
+                for it in held.iter().filter(|it| it.latch_id != latch_id) {
+                    order.add(it.latch_id, latch_id, || Edge {
+                        from_site: it.site,
+                        from_backtrace: it.backtrace.clone(),
+                        to_site: site,
+                        to_backtrace: backtrace.clone(),
+                    });
+                }
+            }
+            held.push(Held {
+                latch_id,
+                mode,
+                site,
+                backtrace,
+            });
+        });
+    }
+
+    #[track_caller]
+    pub(crate) fn before_upgrade(frame: &Frame) {
+        let Some(latch_id) = latch_id(frame) else {
+            return;
+        };
+        HELD.with_borrow(|held| {
+            // upgrade waits for every reader to leave, including ones on this thread.
+            if let Some(same) = held
+                .iter()
+                .find(|it| it.latch_id == latch_id && it.mode != LatchMode::Upgradable)
+            {
+                panic!(
+                    "self deadlock: thread {} upgrades latch on page {} at {} while holding {:?} latch on it \
+                     taken at {}\n\nheld latch taken at:\n{}\n\nupgrade at:\n{}",
+                    thread_name(),
+                    latch_id.1,
+                    Location::caller(),
+                    same.mode,
+                    same.site,
+                    same.backtrace,
+                    Backtrace::force_capture()
+                );
+            }
+        });
+    }
+
+    #[track_caller]
+    pub(crate) fn upgraded(frame: &Frame) {
+        released(frame, LatchMode::Upgradable);
+        acquired(frame, LatchMode::Write, true);
+    }
+
+    pub(crate) fn released(frame: &Frame, mode: LatchMode) {
+        let Some(latch_id) = latch_id(frame) else {
+            return;
+        };
+        HELD.with_borrow_mut(|held| {
+            if let Some(i) = held
+                .iter()
+                .rposition(|it| it.latch_id == latch_id && it.mode == mode)
+            {
+                held.remove(i);
+            }
+        });
+    }
+
+    // Frame no longer holds `page_id`, it was evicted, deleted or frame is gone. Order seen on it is dropped,
+    // next time page is read in it may well be in another frame.
+    pub(crate) fn forget(frame: &Frame, page_id: usize) {
+        let latch_id = (frame as *const Frame as usize, page_id);
+        ORDER
+            .lock()
+            .unwrap_or_else(|err| err.into_inner())
+            .remove(latch_id);
+    }
+
+    fn thread_name() -> String {
+        let current = thread::current();
+        match current.name() {
+            Some(name) => name.to_string(),
+            None => format!("{:?}", current.id()),
+        }
+    }
+}
+
+#[cfg(feature = "latch-debug")]
+pub(crate) use tracking::*;
+
+#[cfg(not(feature = "latch-debug"))]
+pub(crate) fn before_acquire(_frame: &Frame, _mode: LatchMode) {}
+
+#[cfg(not(feature = "latch-debug"))]
+pub(crate) fn acquired(_frame: &Frame, _mode: LatchMode, _ordered: bool) {}
+
+#[cfg(not(feature = "latch-debug"))]
+pub(crate) fn before_upgrade(_frame: &Frame) {}
+
+#[cfg(not(feature = "latch-debug"))]
+pub(crate) fn upgraded(_frame: &Frame) {}
+
+#[cfg(not(feature = "latch-debug"))]
+pub(crate) fn released(_frame: &Frame, _mode: LatchMode) {}
+
+#[cfg(not(feature = "latch-debug"))]
+pub(crate) fn forget(_frame: &Frame, _page_id: usize) {}
+
+#[cfg(all(test, feature = "latch-debug"))]
+mod test {
+    use std::sync::{Arc, Barrier};
+    use std::thread;
+
+    use crate::page::{
+        page_guard::{read_page_guard, upgradable_read_page_guard, write_page_guard},
+        Frame, UnpinListener,
+    };
+
+    struct NoopListener;
+
+    impl UnpinListener for NoopListener {
+        fn on_unpinned(&self, _frame: &Frame) {}
+    }
+
+    fn frame(page_id: usize) -> Arc<Frame> {
+        let frame = Arc::new(Frame::new(0));
+        frame.set_page_id(Some(page_id));
+        frame
+    }
+
+    fn pinned(frame: &Arc<Frame>) -> Arc<Frame> {
+        frame.pin();
+        frame.clone()
+    }
+
+    #[test]
+    #[should_panic(expected = "self deadlock")]
+    fn write_latch_twice_panics() {
+        let frame = frame(100);
+        let _guard = write_page_guard(pinned(&frame), Arc::new(NoopListener));
+        let _again = read_page_guard(pinned(&frame), Arc::new(NoopListener));
+    }
+
+    #[test]
+    #[should_panic(expected = "self deadlock")]
+    fn upgrade_with_own_reader_panics() {
+        let frame = frame(101);
+        let _reader = read_page_guard(pinned(&frame), Arc::new(NoopListener));
+        let upgradable = upgradable_read_page_guard(pinned(&frame), Arc::new(NoopListener));
+        let _ = upgradable.upgrade();
+    }
+
+    #[test]
+    fn recursive_read_is_allowed() {
+        let frame = frame(102);
+        let _guard = read_page_guard(pinned(&frame), Arc::new(NoopListener));
+        let _again = read_page_guard(pinned(&frame), Arc::new(NoopListener));
+    }
+
+    #[test]
+    fn opposite_order_panics() {
+        let (a, b) = (frame(103), frame(104));
+        let done = Barrier::new(2);
+        thread::scope(|s| {
+            s.spawn(|| {
+                let _a = write_page_guard(pinned(&a), Arc::new(NoopListener));
+                let _b = write_page_guard(pinned(&b), Arc::new(NoopListener));
+                done.wait();
+            });
+            done.wait();
+        });
+
+        // other thread is long gone, the order it used is remembered.
+        let res = thread::spawn(move || {
+            let _b = write_page_guard(pinned(&b), Arc::new(NoopListener));
+            let _a = write_page_guard(pinned(&a), Arc::new(NoopListener));
+        })
+        .join();
+        let msg = *res.unwrap_err().downcast::<String>().unwrap();
+        assert!(msg.starts_with("latch order cycle"), "{msg}");
+        // both sides of cycle, as taken now and as taken before.
+        for side in [
+            "held latch taken at:",
+            "new latch taken at:",
+            "earlier latch on page 104 taken at:",
+            "earlier latch on page 103 taken at:",
+        ] {
+            assert!(msg.contains(side), "{msg}");
+        }
+    }
+
+    #[test]
+    fn same_order_is_allowed() {
+        let (a, b) = (frame(105), frame(106));
+        for _ in 0..2 {
+            let _a = read_page_guard(pinned(&a), Arc::new(NoopListener));
+            let _b = write_page_guard(pinned(&b), Arc::new(NoopListener));
+        }
+    }
+
+    #[test]
+    fn order_of_evicted_page_is_forgotten() {
+        let (a, b) = (frame(107), frame(108));
+        {
+            let _a = write_page_guard(pinned(&a), Arc::new(NoopListener));
+            let _b = write_page_guard(pinned(&b), Arc::new(NoopListener));
+        }
+        // page 108 is evicted and read back into same frame.
+        b.set_page_id(None);
+        b.set_page_id(Some(108));
+        let _b = write_page_guard(pinned(&b), Arc::new(NoopListener));
+        let _a = write_page_guard(pinned(&a), Arc::new(NoopListener));
+    }
+}
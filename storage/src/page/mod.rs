@@ -1,7 +1,9 @@
 pub(crate) mod b_plus_tree_page;
+pub(crate) mod frame;
 pub(crate) mod frame_header;
+pub(crate) mod latch_debug;
 pub(crate) mod page_guard;
+pub(crate) mod page_pod;
 
-pub use b_plus_tree_page::b_plus_tree_internal_page::BPLUS_TREE_INTERNAL_PAGE_HEADER_SIZE;
-pub use b_plus_tree_page::b_plus_tree_leaf_page::BPLUS_TREE_LEAF_PAGE_HEADER_SIZE;
+pub use frame::*;
 pub use frame_header::*;
@@ -1,87 +1,378 @@
 #![allow(dead_code)]
-use std::sync::{mpsc::Sender, Arc, RwLock, RwLockReadGuard, RwLockWriteGuard};
+use std::{sync::Arc, time::Duration};
 
-use tokio::sync::oneshot;
+use parking_lot::{
+    ArcRwLockReadGuard, ArcRwLockUpgradableReadGuard, ArcRwLockWriteGuard, RawRwLock,
+};
 
-use crate::page::FrameHeader;
+use crate::{
+    page::{
+        latch_debug::{self, LatchMode},
+        Frame, FrameHeader, UnpinListener,
+    },
+    PagePod,
+};
 
-type DexTxSender = Sender<(usize, oneshot::Sender<()>)>;
+/// Read latch on a frame. It owns a clone of latch arc, so it carries no borrow of the frame.
+pub type FrameReadGuard = ArcRwLockReadGuard<RawRwLock, FrameHeader>;
+/// Write latch on a frame. It owns a clone of latch arc, so it carries no borrow of the frame.
+pub type FrameWriteGuard = ArcRwLockWriteGuard<RawRwLock, FrameHeader>;
+/// Upgradable read latch on a frame. It owns a clone of latch arc, so it carries no borrow of the frame.
+pub type FrameUpgradableReadGuard = ArcRwLockUpgradableReadGuard<RawRwLock, FrameHeader>;
 
 pub struct ReadPageGuard {
-    lock: Arc<RwLock<FrameHeader>>,
-    read_guard: Option<RwLockReadGuard<'static, FrameHeader>>,
-    dec_tx: DexTxSender,
+    frame: Arc<Frame>,
+    read_guard: Option<FrameReadGuard>,
+    listener: Arc<dyn UnpinListener>,
 }
 pub struct WritePageGuard {
-    lock: Arc<RwLock<FrameHeader>>,
-    write_guard: Option<RwLockWriteGuard<'static, FrameHeader>>,
-    dec_tx: DexTxSender,
+    frame: Arc<Frame>,
+    write_guard: Option<FrameWriteGuard>,
+    listener: Arc<dyn UnpinListener>,
 }
 
-pub fn read_page_guard(lock: Arc<RwLock<FrameHeader>>, dec_tx: DexTxSender) -> ReadPageGuard {
-    let read_guard = unsafe {
-        std::mem::transmute::<RwLockReadGuard<'_, FrameHeader>, RwLockReadGuard<'static, FrameHeader>>(
-            lock.read().unwrap(),
-        )
-    };
-    read_guard.incr_pin_count();
+/// Shares the page with readers, but only one upgradable reader is let in at a time.
+/// It can be turned into a `WritePageGuard` without giving up its pin.
+pub struct UpgradableReadPageGuard {
+    frame: Arc<Frame>,
+    // None once upgraded, pin then belongs to the write guard.
+    upgradable_guard: Option<FrameUpgradableReadGuard>,
+    listener: Arc<dyn UnpinListener>,
+}
+
+/// Pins a page without latching it. Page can be read, without waiting on writers, as long as no writer
+/// latched it since this guard was taken. Meant for hot pages, like upper levels of an index, where readers
+/// would otherwise queue up behind writers.
+///
+/// This is not a seqlock: page bytes are only read under a read latch taken with `try_read`, see
+/// `try_read_validated`. A reader never waits, but it still takes and releases latch of the page.
+pub struct OptimisticReadGuard {
+    frame: Arc<Frame>,
+    version: u64,
+    listener: Arc<dyn UnpinListener>,
+}
+
+/// Frame must already be pinned by caller. Returned guard owns that pin and releases it on drop.
+#[track_caller]
+pub fn read_page_guard(frame: Arc<Frame>, listener: Arc<dyn UnpinListener>) -> ReadPageGuard {
+    latch_debug::before_acquire(&frame, LatchMode::Read);
+    let read_guard = frame.latch().read_arc();
+    latch_debug::acquired(&frame, LatchMode::Read, true);
     ReadPageGuard {
-        lock,
+        frame,
+        read_guard: Some(read_guard),
+        listener,
+    }
+}
+
+/// Same as `read_page_guard`, but gives up if latch can not be taken right away.
+/// Pin handed in by the caller is released in that case.
+#[track_caller]
+pub fn try_read_page_guard(
+    frame: Arc<Frame>,
+    listener: Arc<dyn UnpinListener>,
+) -> Option<ReadPageGuard> {
+    let Some(read_guard) = frame.latch().try_read_arc() else {
+        release_pin(&frame, listener.as_ref());
+        return None;
+    };
+    latch_debug::acquired(&frame, LatchMode::Read, false);
+    Some(ReadPageGuard {
+        frame,
         read_guard: Some(read_guard),
-        dec_tx,
+        listener,
+    })
+}
+
+/// Same as `read_page_guard`, but gives up if latch can not be taken within `timeout`.
+/// Pin handed in by the caller is released in that case.
+#[track_caller]
+pub fn try_read_page_guard_for(
+    frame: Arc<Frame>,
+    listener: Arc<dyn UnpinListener>,
+    timeout: Duration,
+) -> Option<ReadPageGuard> {
+    let Some(read_guard) = frame.latch().try_read_arc_for(timeout) else {
+        release_pin(&frame, listener.as_ref());
+        return None;
+    };
+    latch_debug::acquired(&frame, LatchMode::Read, false);
+    Some(ReadPageGuard {
+        frame,
+        read_guard: Some(read_guard),
+        listener,
+    })
+}
+
+/// Frame must already be pinned by caller. Returned guard owns that pin and releases it on drop.
+#[track_caller]
+pub fn upgradable_read_page_guard(
+    frame: Arc<Frame>,
+    listener: Arc<dyn UnpinListener>,
+) -> UpgradableReadPageGuard {
+    latch_debug::before_acquire(&frame, LatchMode::Upgradable);
+    let upgradable_guard = frame.latch().upgradable_read_arc();
+    latch_debug::acquired(&frame, LatchMode::Upgradable, true);
+    UpgradableReadPageGuard {
+        frame,
+        upgradable_guard: Some(upgradable_guard),
+        listener,
+    }
+}
+
+/// Frame must already be pinned by caller. Returned guard owns that pin and releases it on drop.
+pub fn optimistic_read_guard(
+    frame: Arc<Frame>,
+    listener: Arc<dyn UnpinListener>,
+) -> OptimisticReadGuard {
+    let version = frame.version();
+    OptimisticReadGuard {
+        frame,
+        version,
+        listener,
     }
 }
 
 impl WritePageGuard {
-    pub fn get_write_guard(&mut self) -> &mut RwLockWriteGuard<'static, FrameHeader> {
+    pub fn get_write_guard(&mut self) -> &mut FrameWriteGuard {
         self.write_guard.as_mut().unwrap()
     }
+
+    pub fn get_readable_data_as<T: PagePod>(&self) -> &T {
+        self.write_guard.as_ref().unwrap().get_readable_data_as()
+    }
+
+    pub fn get_writeable_data_as<T: PagePod>(&mut self) -> &mut T {
+        self.get_write_guard().get_writeable_data_as()
+    }
 }
 
 impl ReadPageGuard {
-    pub fn get_read_guard(&self) -> &RwLockReadGuard<'static, FrameHeader> {
+    pub fn get_read_guard(&self) -> &FrameReadGuard {
         self.read_guard.as_ref().unwrap()
     }
+
+    pub fn get_readable_data_as<T: PagePod>(&self) -> &T {
+        self.get_read_guard().get_readable_data_as()
+    }
+}
+
+impl UpgradableReadPageGuard {
+    pub fn get_read_guard(&self) -> &FrameUpgradableReadGuard {
+        self.upgradable_guard.as_ref().unwrap()
+    }
+
+    pub fn get_readable_data_as<T: PagePod>(&self) -> &T {
+        self.get_read_guard().get_readable_data_as()
+    }
+
+    /// Waits for other readers to leave and takes write latch. Page stays pinned all along.
+    #[track_caller]
+    pub fn upgrade(mut self) -> WritePageGuard {
+        latch_debug::before_upgrade(&self.frame);
+        let upgradable_guard = self.upgradable_guard.take().unwrap();
+        let write_guard = ArcRwLockUpgradableReadGuard::upgrade(upgradable_guard);
+        latch_debug::upgraded(&self.frame);
+        new_write_page_guard(self.frame.clone(), write_guard, self.listener.clone())
+    }
+}
+
+impl OptimisticReadGuard {
+    /// False if a writer latched the page since this guard was taken.
+    pub fn validate(&self) -> bool {
+        self.frame.version() == self.version
+    }
+
+    /// Non-blocking validated read. Runs `f` over page under a read latch, if that latch is free right now
+    /// and page is unchanged since this guard was taken. Never waits on a writer, `None` tells caller to
+    /// retry with a `ReadPageGuard`.
+    pub fn try_read_validated<R>(&self, f: impl FnOnce(&FrameHeader) -> R) -> Option<R> {
+        let read_guard = self.frame.latch().try_read()?;
+        // writer bumps version while holding latch, so version can not move while we hold read latch.
+        if !self.validate() {
+            return None;
+        }
+        Some(f(&read_guard))
+    }
+}
+
+/// Frame must already be pinned by caller. Returned guard owns that pin and releases it on drop.
+#[track_caller]
+pub fn write_page_guard(frame: Arc<Frame>, listener: Arc<dyn UnpinListener>) -> WritePageGuard {
+    latch_debug::before_acquire(&frame, LatchMode::Write);
+    let write_guard = frame.latch().write_arc();
+    latch_debug::acquired(&frame, LatchMode::Write, true);
+    new_write_page_guard(frame, write_guard, listener)
 }
 
-pub fn write_page_guard(lock: Arc<RwLock<FrameHeader>>, dec_tx: DexTxSender) -> WritePageGuard {
-    let mut write_guard = unsafe {
-        std::mem::transmute::<
-            RwLockWriteGuard<'_, FrameHeader>,
-            RwLockWriteGuard<'static, FrameHeader>,
-        >(lock.write().unwrap())
+/// Same as `write_page_guard`, but gives up if latch can not be taken right away.
+/// Pin handed in by the caller is released in that case.
+#[track_caller]
+pub fn try_write_page_guard(
+    frame: Arc<Frame>,
+    listener: Arc<dyn UnpinListener>,
+) -> Option<WritePageGuard> {
+    let Some(write_guard) = frame.latch().try_write_arc() else {
+        release_pin(&frame, listener.as_ref());
+        return None;
     };
-    write_guard.incr_pin_count();
-    write_guard.set_dirty(true);
+    latch_debug::acquired(&frame, LatchMode::Write, false);
+    Some(new_write_page_guard(frame, write_guard, listener))
+}
+
+/// Same as `write_page_guard`, but gives up if latch can not be taken within `timeout`.
+/// Pin handed in by the caller is released in that case.
+#[track_caller]
+pub fn try_write_page_guard_for(
+    frame: Arc<Frame>,
+    listener: Arc<dyn UnpinListener>,
+    timeout: Duration,
+) -> Option<WritePageGuard> {
+    let Some(write_guard) = frame.latch().try_write_arc_for(timeout) else {
+        release_pin(&frame, listener.as_ref());
+        return None;
+    };
+    latch_debug::acquired(&frame, LatchMode::Write, false);
+    Some(new_write_page_guard(frame, write_guard, listener))
+}
+
+fn new_write_page_guard(
+    frame: Arc<Frame>,
+    write_guard: FrameWriteGuard,
+    listener: Arc<dyn UnpinListener>,
+) -> WritePageGuard {
+    frame.bump_version();
+    frame.set_dirty(true);
     WritePageGuard {
-        lock,
+        frame,
         write_guard: Some(write_guard),
-        dec_tx,
+        listener,
+    }
+}
+
+fn release_pin(frame: &Frame, listener: &dyn UnpinListener) {
+    if frame.unpin() == 0 {
+        listener.on_unpinned(frame);
     }
 }
 
 impl Drop for ReadPageGuard {
     fn drop(&mut self) {
-        let read_guard = self.read_guard.take().unwrap();
-        read_guard.decr_pin_count();
-        let (tx, rx) = oneshot::channel();
-        self.dec_tx.send((read_guard.frame_id(), tx)).unwrap();
-        // dropping this guard is important. Otherwise say a write thread for same frame,
-        // holding bpm lock, will be held. And dec_tx also needs that lock.
-        drop(read_guard);
-        rx.blocking_recv().unwrap();
+        // latch goes first. Once pin count is zero the frame can be picked for eviction, and evicting
+        // thread will wait on this latch while holding bpm lock.
+        drop(self.read_guard.take());
+        latch_debug::released(&self.frame, LatchMode::Read);
+        release_pin(&self.frame, self.listener.as_ref());
     }
 }
 
 impl Drop for WritePageGuard {
     fn drop(&mut self) {
-        let write_guard = self.write_guard.take().unwrap();
-        write_guard.decr_pin_count();
-        let (tx, rx) = oneshot::channel();
-        self.dec_tx.send((write_guard.frame_id(), tx)).unwrap();
-        // dropping this guard is important. Otherwise say a reader thread for same frame,
-        // holding bpm lock, will be held. And dec_tx also needs that lock.
-        drop(write_guard);
-        rx.blocking_recv().unwrap();
+        self.frame.bump_version();
+        // latch goes first. Once pin count is zero the frame can be picked for eviction, and evicting
+        // thread will wait on this latch while holding bpm lock.
+        drop(self.write_guard.take());
+        latch_debug::released(&self.frame, LatchMode::Write);
+        release_pin(&self.frame, self.listener.as_ref());
+    }
+}
+
+impl Drop for UpgradableReadPageGuard {
+    fn drop(&mut self) {
+        // an upgraded guard handed its pin over to the write guard.
+        let Some(upgradable_guard) = self.upgradable_guard.take() else {
+            return;
+        };
+        drop(upgradable_guard);
+        latch_debug::released(&self.frame, LatchMode::Upgradable);
+        release_pin(&self.frame, self.listener.as_ref());
+    }
+}
+
+impl Drop for OptimisticReadGuard {
+    fn drop(&mut self) {
+        release_pin(&self.frame, self.listener.as_ref());
+    }
+}
+
+// These tests stay away from disk scheduler and tokio, so they can run under `cargo miri test -p storage`.
+#[cfg(test)]
+#[allow(clippy::bool_assert_comparison)]
+mod test {
+    use std::{
+        sync::{Arc, Barrier, Mutex},
+        thread,
+    };
+
+    use crate::page::{Frame, UnpinListener};
+
+    use super::{read_page_guard, write_page_guard};
+
+    // Records, for each unpin notification, whether latch was already free at that point.
+    #[derive(Default)]
+    struct RecordingListener {
+        latch_free_on_unpin: Mutex<Vec<bool>>,
+    }
+
+    impl UnpinListener for RecordingListener {
+        fn on_unpinned(&self, frame: &Frame) {
+            let latch_free = frame.latch().try_write().is_some();
+            self.latch_free_on_unpin.lock().unwrap().push(latch_free);
+        }
+    }
+
+    #[test]
+    fn latch_is_released_before_unpin() {
+        let listener = Arc::new(RecordingListener::default());
+        let frame = Arc::new(Frame::new(0));
+
+        frame.pin();
+        drop(read_page_guard(frame.clone(), listener.clone()));
+        frame.pin();
+        drop(write_page_guard(frame.clone(), listener.clone()));
+
+        assert_eq!(0, frame.pin_count());
+        assert_eq!(
+            vec![true, true],
+            *listener.latch_free_on_unpin.lock().unwrap()
+        );
+    }
+
+    #[test]
+    fn guard_outlives_frame_handle() {
+        let listener = Arc::new(RecordingListener::default());
+        let frame = Arc::new(Frame::new(0));
+
+        frame.pin();
+        let mut guard = write_page_guard(frame, listener.clone());
+        // guard is the last owner of the frame now.
+        guard.get_write_guard().get_writeable_data()[0] = 42;
+        assert_eq!(42, guard.get_write_guard().get_readable_data()[0]);
+        drop(guard);
+
+        assert_eq!(1, listener.latch_free_on_unpin.lock().unwrap().len());
+    }
+
+    #[test]
+    fn only_last_reader_notifies() {
+        let listener = Arc::new(RecordingListener::default());
+        let frame = Arc::new(Frame::new(0));
+        let all_pinned = Barrier::new(3);
+
+        // every reader holds its guard until all of them are in, then drops it on its own thread.
+        thread::scope(|s| {
+            for _ in 0..3 {
+                s.spawn(|| {
+                    frame.pin();
+                    let guard = read_page_guard(frame.clone(), listener.clone());
+                    all_pinned.wait();
+                    assert_eq!(true, frame.pin_count() > 0);
+                    drop(guard);
+                });
+            }
+        });
+
+        assert_eq!(0, frame.pin_count());
+        assert_eq!(vec![true], *listener.latch_free_on_unpin.lock().unwrap());
     }
 }
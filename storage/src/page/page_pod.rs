@@ -0,0 +1,217 @@
+use std::mem::{align_of, size_of};
+
+use crate::{PageData, PAGE_SIZE};
+
+/// Plain old data layout which can be viewed in place over page bytes, see `FrameHeader::get_readable_data_as`.
+///
+/// Only types implementing it can be viewed, anything else is rejected at compile time.
+/// ```compile_fail
+/// # use storage::FrameHeader;
+/// // bool is not valid for every bit pattern.
+/// let header = FrameHeader::new(0);
+/// let _ = header.get_readable_data_as::<bool>();
+/// ```
+/// Size and alignment are checked against page when view is instantiated.
+/// ```compile_fail
+/// # use storage::FrameHeader;
+/// let header = FrameHeader::new(0);
+/// let _ = header.get_readable_data_as::<[u64; 513]>();
+/// ```
+///
+/// # Safety
+/// Implementor must be valid for any bit pattern, must not have padding bytes and must not hold pointers.
+/// For structs that means `#[repr(C)]` with `PagePod` fields only. Prefer `impl_page_pod!`, which declares
+/// the struct itself and checks both its fields and padding at compile time.
+pub unsafe trait PagePod: Copy + 'static {}
+
+macro_rules! impl_primitive_page_pod {
+    ($($t: ty),*) => {
+        $(unsafe impl PagePod for $t {})*
+    };
+}
+
+impl_primitive_page_pod!(u8, u16, u32, u64, usize, i8, i16, i32, i64, isize);
+
+unsafe impl<T: PagePod, const N: usize> PagePod for [T; N] {}
+
+/// Declares a `#[repr(C)]` struct and implements `PagePod` for it. Takes the whole struct definition, so every
+/// field it checks is one the struct really has. `#[repr(C)]` is added by the macro.
+/// ```
+/// storage::impl_page_pod! {
+///     #[derive(Clone, Copy)]
+///     pub struct Header {
+///         size: u32,
+///         max_size: u32,
+///         next_page_id: usize,
+///     }
+/// }
+/// ```
+///
+/// Fails to compile if struct has padding,
+/// ```compile_fail,E0080
+/// storage::impl_page_pod! {
+///     #[derive(Clone, Copy)]
+///     struct Padded {
+///         a: u8,
+///         b: u32,
+///     }
+/// }
+/// ```
+/// or if any field is not `PagePod`, be it a primitive
+/// ```compile_fail,E0277
+/// storage::impl_page_pod! {
+///     #[derive(Clone, Copy)]
+///     struct Flag {
+///         set: bool,
+///         rest: [u8; 7],
+///     }
+/// }
+/// ```
+/// or a struct which was not declared with this macro.
+/// ```compile_fail,E0277
+/// #[repr(C)]
+/// #[derive(Clone, Copy)]
+/// struct Id {
+///     value: u64,
+/// }
+/// storage::impl_page_pod! {
+///     #[derive(Clone, Copy)]
+///     struct Entry {
+///         id: Id,
+///         len: u64,
+///     }
+/// }
+/// ```
+///
+/// A struct defined elsewhere needs a hand written `unsafe impl PagePod`, with its layout argued for in a
+/// `// Safety:` comment.
+#[macro_export]
+macro_rules! impl_page_pod {
+    (
+        $(#[$attr: meta])*
+        $vis: vis struct $name: ident {
+            $($(#[$field_attr: meta])* $field_vis: vis $field: ident: $field_ty: ty),* $(,)?
+        }
+    ) => {
+        $(#[$attr])*
+        #[repr(C)]
+        $vis struct $name {
+            $($(#[$field_attr])* $field_vis $field: $field_ty),*
+        }
+        const _: () = {
+            #[allow(dead_code)]
+            fn fields_are_page_pod() {
+                fn is_page_pod<T: $crate::PagePod>() {}
+                $(is_page_pod::<$field_ty>();)*
+            }
+            assert!(
+                std::mem::size_of::<$name>() == 0 $(+ std::mem::size_of::<$field_ty>())*,
+                "page layout must not have padding"
+            );
+        };
+        // Safety: struct is `#[repr(C)]`, its fields are all `PagePod` and there is no padding between them.
+        unsafe impl $crate::PagePod for $name {}
+    };
+}
+
+// Safety: leaf pages of an index hold record ids. `RID` is `#[repr(C)]` with a `usize` page id followed by two
+// `u32`s, so it has no padding, and integers are valid for any bit pattern.
+unsafe impl PagePod for common::RID {}
+const _: () = assert!(size_of::<common::RID>() == size_of::<usize>() + 2 * size_of::<u32>());
+
+const fn assert_fits_page<T: PagePod>() {
+    assert!(size_of::<T>() <= PAGE_SIZE, "layout is larger than a page");
+    assert!(
+        align_of::<T>() <= align_of::<PageData>(),
+        "layout needs stricter alignment than a page"
+    );
+}
+
+/// View start of page as `T`.
+pub(crate) fn page_as<T: PagePod>(page: &PageData) -> &T {
+    const { assert_fits_page::<T>() };
+    // Safety: size and alignment are checked above, and any bit pattern is a valid `T`.
+    unsafe { &*(page.as_ptr() as *const T) }
+}
+
+/// View start of page as mutable `T`.
+pub(crate) fn page_as_mut<T: PagePod>(page: &mut PageData) -> &mut T {
+    const { assert_fits_page::<T>() };
+    // Safety: size and alignment are checked above, and any bit pattern is a valid `T`.
+    unsafe { &mut *(page.as_mut_ptr() as *mut T) }
+}
+
+/// View `bytes` as `len` consecutive `T`s. Panics if bytes are too short or misaligned for `T`.
+pub fn cast_slice<T: PagePod>(bytes: &[u8], len: usize) -> &[T] {
+    assert_castable::<T>(bytes, len);
+    // Safety: length and alignment are checked above, and any bit pattern is a valid `T`.
+    unsafe { std::slice::from_raw_parts(bytes.as_ptr() as *const T, len) }
+}
+
+/// View `bytes` as `len` consecutive mutable `T`s. Panics if bytes are too short or misaligned for `T`.
+pub fn cast_slice_mut<T: PagePod>(bytes: &mut [u8], len: usize) -> &mut [T] {
+    assert_castable::<T>(bytes, len);
+    // Safety: length and alignment are checked above, and any bit pattern is a valid `T`.
+    unsafe { std::slice::from_raw_parts_mut(bytes.as_mut_ptr() as *mut T, len) }
+}
+
+fn assert_castable<T: PagePod>(bytes: &[u8], len: usize) {
+    assert!(
+        size_of::<T>() * len <= bytes.len(),
+        "{} bytes can not hold {len} elements of {} bytes",
+        bytes.len(),
+        size_of::<T>()
+    );
+    assert!(
+        bytes.as_ptr().cast::<T>().is_aligned(),
+        "bytes are not aligned for element type"
+    );
+}
+
+#[cfg(test)]
+mod test {
+    use crate::{FrameHeader, PageData};
+
+    use super::{cast_slice, cast_slice_mut};
+
+    impl_page_pod! {
+        #[derive(Clone, Copy)]
+        struct Header {
+            size: u32,
+            max_size: u32,
+            next_page_id: usize,
+        }
+    }
+
+    #[test]
+    fn typed_view_writes_through_page_bytes() {
+        let mut frame = FrameHeader::new(0);
+        {
+            let header = frame.get_writeable_data_as::<Header>();
+            header.size = 1;
+            header.max_size = 2;
+            header.next_page_id = 3;
+        }
+
+        let header = frame.get_readable_data_as::<Header>();
+        assert_eq!(
+            (1, 2, 3),
+            (header.size, header.max_size, header.next_page_id)
+        );
+        assert_eq!(1u32.to_ne_bytes(), frame.get_readable_data()[..4]);
+    }
+
+    #[test]
+    fn slice_view_after_header() {
+        let mut page = PageData::default();
+        cast_slice_mut::<u64>(&mut page[16..], 3).copy_from_slice(&[7, 8, 9]);
+        assert_eq!(&[7, 8, 9], cast_slice::<u64>(&page[16..], 3));
+    }
+
+    #[test]
+    #[should_panic]
+    fn misaligned_slice_view_panics() {
+        let page = PageData::default();
+        cast_slice::<u64>(&page[1..], 1);
+    }
+}
@@ -0,0 +1,325 @@
+//! Seeded stress test of buffer pool. Many threads read, write, create and delete pages of a small pool, and
+//! every page read is checked against a shadow model of what was last written to it.
+//!
+//! Seed is printed at start, and shown when test fails. Replay it with
+//! `STRESS_SEED=<seed> cargo test -p tests --test buffer_pool_stress_test`. A seed fixes what every thread
+//! does, not how threads interleave, so a replay is likely but not certain to hit the same failure.
+use std::{
+    collections::{HashMap, HashSet},
+    env,
+    panic::{self, AssertUnwindSafe},
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Barrier, Mutex,
+    },
+    thread,
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+use buffer::{BufferPoolError, BufferPoolManager};
+use storage::{MemoryManager, PAGE_SIZE};
+
+const FRAMES: usize = 8;
+const K_DIST: usize = 2;
+const THREADS: usize = 8;
+const ROUNDS: usize = 4;
+const OPS_PER_ROUND: usize = 500;
+// kept small against frame count, so pages keep getting evicted and read back.
+const MAX_LIVE_PAGES: usize = 48;
+const DISK_PAGES: usize = 4096;
+
+// splitmix64, good enough to pick operations and keeps the test free of dependencies.
+struct Rng(u64);
+
+impl Rng {
+    fn next(&mut self) -> u64 {
+        self.0 = self.0.wrapping_add(0x9e37_79b9_7f4a_7c15);
+        let mut z = self.0;
+        z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+        z ^ (z >> 31)
+    }
+
+    fn below(&mut self, n: usize) -> usize {
+        (self.next() % n as u64) as usize
+    }
+}
+
+// Version of every page last written. Only changed by a thread holding write latch on that page, and read by
+// one holding a latch on it, so a page and its model entry can not disagree while the latch is held.
+#[derive(Default)]
+struct Model {
+    versions: HashMap<usize, u64>,
+    deleted: HashSet<usize>,
+}
+
+impl Model {
+    fn live_page(&self, rng: &mut Rng) -> Option<usize> {
+        if self.versions.is_empty() {
+            return None;
+        }
+        // hash map order is fixed for a given set of keys, sorting keeps picks reproducible anyway.
+        let mut page_ids = self.versions.keys().copied().collect::<Vec<_>>();
+        page_ids.sort_unstable();
+        Some(page_ids[rng.below(page_ids.len())])
+    }
+}
+
+fn content(page_id: usize, version: u64) -> Vec<u8> {
+    let mut data = vec![0u8; PAGE_SIZE];
+    data[..8].copy_from_slice(&(page_id as u64).to_le_bytes());
+    data[8..16].copy_from_slice(&version.to_le_bytes());
+    for (i, byte) in data[16..].iter_mut().enumerate() {
+        *byte = (page_id as u64 * 31 + version * 7 + i as u64) as u8;
+    }
+    data
+}
+
+fn check_content(page_id: usize, data: &[u8], model: &Model) {
+    let version = model.versions[&page_id];
+    assert!(
+        data == content(page_id, version).as_slice(),
+        "page {page_id} does not hold version {version}, header is {:?}",
+        &data[..16]
+    );
+}
+
+// A fetch can fail for reasons other threads cause, anything else is a bug.
+fn expect_fetch_error(err: BufferPoolError, page_id: usize, model: &Mutex<Model>) {
+    match err {
+        BufferPoolError::NoFreeFrame | BufferPoolError::TimedOut => {}
+        BufferPoolError::PageDeleted(id) => {
+            assert_eq!(page_id, id);
+            assert!(model.lock().unwrap().deleted.contains(&page_id));
+        }
+        err => panic!("fetch of page {page_id} failed: {err}"),
+    }
+}
+
+fn assert_pinned(bpm: &BufferPoolManager, page_id: usize) {
+    let frames = bpm
+        .frame_stats()
+        .into_iter()
+        .filter(|it| it.page_id == Some(page_id))
+        .collect::<Vec<_>>();
+    assert_eq!(1, frames.len(), "page {page_id} is in {frames:?}");
+    assert!(frames[0].pin_count > 0, "page {page_id} is not pinned");
+    assert!(!frames[0].evictable, "pinned page {page_id} is evictable");
+}
+
+fn read(bpm: &BufferPoolManager, model: &Mutex<Model>, rng: &mut Rng) {
+    let Some(page_id) = model.lock().unwrap().live_page(rng) else {
+        return;
+    };
+    let guard = match rng.below(3) {
+        0 => bpm.read_page_timeout(page_id, Duration::from_millis(50)),
+        _ => bpm.read_page(page_id),
+    };
+    match guard {
+        Ok(guard) => {
+            check_content(
+                page_id,
+                guard.get_read_guard().get_readable_data(),
+                &model.lock().unwrap(),
+            );
+            if rng.below(16) == 0 {
+                assert_pinned(bpm, page_id);
+            }
+        }
+        Err(err) => expect_fetch_error(err, page_id, model),
+    }
+}
+
+fn optimistic_read(bpm: &BufferPoolManager, model: &Mutex<Model>, rng: &mut Rng) {
+    let Some(page_id) = model.lock().unwrap().live_page(rng) else {
+        return;
+    };
+    match bpm.optimistic_read_page(page_id) {
+        Ok(guard) => {
+            // a writer may have been in meanwhile, then there is nothing to check.
+            guard.try_read_validated(|page| {
+                check_content(page_id, page.get_readable_data(), &model.lock().unwrap())
+            });
+        }
+        Err(err) => expect_fetch_error(err, page_id, model),
+    }
+}
+
+fn write(bpm: &BufferPoolManager, model: &Mutex<Model>, rng: &mut Rng) {
+    let Some(page_id) = model.lock().unwrap().live_page(rng) else {
+        return;
+    };
+    let guard = match rng.below(3) {
+        0 => bpm.write_page_timeout(page_id, Duration::from_millis(50)),
+        _ => bpm.write_page(page_id),
+    };
+    let mut guard = match guard {
+        Ok(guard) => guard,
+        Err(err) => return expect_fetch_error(err, page_id, model),
+    };
+    let mut model = model.lock().unwrap();
+    let data = guard.get_write_guard().get_writeable_data();
+    check_content(page_id, data, &model);
+    let version = model.versions[&page_id] + 1;
+    data.copy_from_slice(&content(page_id, version));
+    model.versions.insert(page_id, version);
+}
+
+fn new_page(bpm: &BufferPoolManager, model: &Mutex<Model>, rng: &mut Rng) {
+    if model.lock().unwrap().versions.len() >= MAX_LIVE_PAGES {
+        return write(bpm, model, rng);
+    }
+    let page_id = bpm.new_page_id();
+    // nobody else knows the page yet, so it can wait for a frame as long as it takes.
+    let mut guard = loop {
+        match bpm.write_page_timeout(page_id, Duration::from_millis(50)) {
+            Ok(guard) => break guard,
+            Err(BufferPoolError::TimedOut) => continue,
+            Err(err) => panic!("new page {page_id} failed: {err}"),
+        }
+    };
+    let data = guard.get_write_guard().get_writeable_data();
+    assert!(
+        data.iter().all(|&byte| byte == 0),
+        "new page {page_id} is not blank"
+    );
+    data.copy_from_slice(&content(page_id, 0));
+    model.lock().unwrap().versions.insert(page_id, 0);
+}
+
+fn delete(bpm: &BufferPoolManager, model: &Mutex<Model>, rng: &mut Rng) {
+    // model lock is held across delete, so a fetch failing with `PageDeleted` always finds page deleted in it.
+    let mut model = model.lock().unwrap();
+    let Some(page_id) = model.live_page(rng) else {
+        return;
+    };
+    match bpm.delete_page(page_id) {
+        Ok(true) => {
+            model.versions.remove(&page_id);
+            model.deleted.insert(page_id);
+        }
+        // pinned by another thread.
+        Ok(false) => {}
+        Err(err) => panic!("delete of page {page_id} failed: {err}"),
+    }
+}
+
+fn flush(bpm: &BufferPoolManager, model: &Mutex<Model>, rng: &mut Rng) {
+    let Some(page_id) = model.lock().unwrap().live_page(rng) else {
+        return;
+    };
+    match bpm.flush_page(page_id) {
+        Ok(()) => {}
+        Err(err) => expect_fetch_error(err, page_id, model),
+    }
+}
+
+fn run_ops(bpm: &BufferPoolManager, model: &Mutex<Model>, rng: &mut Rng) {
+    for _ in 0..OPS_PER_ROUND {
+        match rng.below(100) {
+            0..=44 => read(bpm, model, rng),
+            45..=49 => optimistic_read(bpm, model, rng),
+            50..=79 => write(bpm, model, rng),
+            80..=89 => new_page(bpm, model, rng),
+            90..=94 => delete(bpm, model, rng),
+            _ => flush(bpm, model, rng),
+        }
+    }
+}
+
+// Only called while no thread is running operations.
+fn check_quiescent(bpm: &BufferPoolManager, model: &Model) {
+    let stats = bpm.stats();
+    assert_eq!(0, stats.pinned_frames, "{stats:?}");
+
+    let mut resident = HashSet::new();
+    for frame in bpm.frame_stats() {
+        assert_eq!(0, frame.pin_count, "{frame:?}");
+        let Some(page_id) = frame.page_id else {
+            continue;
+        };
+        assert!(resident.insert(page_id), "page {page_id} is in two frames");
+        assert!(
+            !model.deleted.contains(&page_id),
+            "deleted page {page_id} is resident"
+        );
+    }
+
+    let mut page_ids = model.versions.keys().copied().collect::<Vec<_>>();
+    page_ids.sort_unstable();
+    for page_id in page_ids {
+        let guard = bpm.read_page(page_id).unwrap();
+        check_content(page_id, guard.get_read_guard().get_readable_data(), model);
+    }
+    for &page_id in &model.deleted {
+        assert!(matches!(
+            bpm.read_page(page_id),
+            Err(BufferPoolError::PageDeleted(_))
+        ));
+    }
+}
+
+#[test]
+fn buffer_pool_stress_test() {
+    let seed = env::var("STRESS_SEED")
+        .map(|seed| seed.parse::<u64>().expect("STRESS_SEED is not a number"))
+        .unwrap_or_else(|_| {
+            SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .unwrap()
+                .as_nanos() as u64
+        });
+    eprintln!("buffer pool stress seed {seed}, replay with STRESS_SEED={seed}");
+
+    let bpm = BufferPoolManager::new(FRAMES, K_DIST, Box::new(MemoryManager::new(DISK_PAGES)));
+    let model = Mutex::new(Model::default());
+    let round_done = Barrier::new(THREADS + 1);
+    let checked = Barrier::new(THREADS + 1);
+    // a failed thread still goes through both barriers, so nobody is left waiting on it.
+    let failed = AtomicBool::new(false);
+
+    thread::scope(|s| {
+        for t in 0..THREADS {
+            let (bpm, model, round_done, checked, failed) =
+                (&bpm, &model, &round_done, &checked, &failed);
+            s.spawn(move || {
+                let mut rng = Rng(seed ^ (t as u64 + 1).wrapping_mul(0x2545_f491_4f6c_dd1d));
+                for _ in 0..ROUNDS {
+                    let res =
+                        panic::catch_unwind(AssertUnwindSafe(|| run_ops(bpm, model, &mut rng)));
+                    if res.is_err() {
+                        failed.store(true, Ordering::SeqCst);
+                    }
+                    round_done.wait();
+                    checked.wait();
+                    if let Err(err) = res {
+                        panic::resume_unwind(err);
+                    }
+                    if failed.load(Ordering::SeqCst) {
+                        return;
+                    }
+                }
+            });
+        }
+        for _ in 0..ROUNDS {
+            round_done.wait();
+            let res = if failed.load(Ordering::SeqCst) {
+                Ok(())
+            } else {
+                panic::catch_unwind(AssertUnwindSafe(|| {
+                    check_quiescent(&bpm, &model.lock().unwrap())
+                }))
+            };
+            if res.is_err() {
+                failed.store(true, Ordering::SeqCst);
+            }
+            checked.wait();
+            if let Err(err) = res {
+                panic::resume_unwind(err);
+            }
+            if failed.load(Ordering::SeqCst) {
+                return;
+            }
+        }
+    });
+}